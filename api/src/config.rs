@@ -0,0 +1,209 @@
+use crate::WorkspaceManifest;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Where a plugin's configuration table was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// `[workspace.metadata.forgen.plugins.<id>]` in the workspace manifest.
+    Workspace,
+    /// `[package.metadata.forgen.plugins.<id>]` of the named package.
+    Package(String),
+}
+
+/// A plugin configuration table failed to deserialize.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub plugin_id: String,
+    /// The table that supplied the offending key.
+    pub source: ConfigSource,
+    /// Dotted path of the offending key inside the plugin's table, or `None`
+    /// when the table as a whole is wrong (for example not a table).
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table = match &self.source {
+            ConfigSource::Workspace => {
+                format!("[workspace.metadata.forgen.plugins.{}]", self.plugin_id)
+            }
+            ConfigSource::Package(package) => format!(
+                "[package.metadata.forgen.plugins.{}] of package `{package}`",
+                self.plugin_id
+            ),
+        };
+
+        match &self.key {
+            Some(key) => write!(
+                f,
+                "invalid configuration for plugin `{}`: key `{key}` in {table}: {}",
+                self.plugin_id, self.message
+            ),
+            None => write!(
+                f,
+                "invalid configuration for plugin `{}` in {table}: {}",
+                self.plugin_id, self.message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// `forgen.plugins.<plugin_id>` inside a raw `[*.metadata]` table.
+fn plugin_table<'a>(metadata: &'a Value, plugin_id: &str) -> Option<&'a Value> {
+    metadata.get("forgen")?.get("plugins")?.get(plugin_id)
+}
+
+/// Deserialize the configuration of `plugin_id`, optionally with the
+/// overrides of the package that owns `file_path` merged on top.
+///
+/// A missing table deserializes from an empty table, so config types whose
+/// fields all have `#[serde(default)]` work without any configuration.
+pub(crate) fn load<T>(
+    manifest: &WorkspaceManifest,
+    plugin_id: &str,
+    file_path: Option<&str>,
+) -> Result<T, ConfigError>
+where
+    T: DeserializeOwned,
+{
+    let workspace = plugin_table(&manifest.metadata, plugin_id);
+    let package = file_path
+        .and_then(|path| manifest.package_for_file(path))
+        .and_then(|package| {
+            plugin_table(&package.metadata, plugin_id).map(|table| (package.name.as_str(), table))
+        });
+
+    let merged = match (workspace, package) {
+        (None, None) => Value::Object(Map::new()),
+        (Some(workspace), None) => workspace.clone(),
+        (base, Some((_, overrides))) => {
+            let mut merged = base.cloned().unwrap_or_else(|| Value::Object(Map::new()));
+            merge(&mut merged, overrides);
+            merged
+        }
+    };
+
+    serde_path_to_error::deserialize(&merged).map_err(|err| {
+        let key = match err.path().to_string() {
+            path if path == "." => None,
+            path => Some(path),
+        };
+        let first_key = key
+            .as_deref()
+            .map(|key| key.split(['.', '[']).next().unwrap_or(key));
+        let source = match package {
+            Some((name, overrides))
+                if first_key.is_none_or(|first| overrides.get(first).is_some()) =>
+            {
+                ConfigSource::Package(name.to_owned())
+            }
+            _ => ConfigSource::Workspace,
+        };
+
+        ConfigError {
+            plugin_id: plugin_id.to_owned(),
+            source,
+            key,
+            message: err.into_inner().to_string(),
+        }
+    })
+}
+
+/// Recursively merge `overrides` into `base`. Tables are merged key by key;
+/// every other value in `overrides` replaces the one in `base`.
+fn merge(base: &mut Value, overrides: &Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    struct DemoConfig {
+        #[serde(default)]
+        anchor: String,
+        #[serde(default)]
+        limits: Limits,
+    }
+
+    #[derive(Debug, Default, Deserialize, PartialEq)]
+    struct Limits {
+        #[serde(default)]
+        min: u8,
+        #[serde(default)]
+        max: u8,
+    }
+
+    fn manifest() -> WorkspaceManifest {
+        serde_json::from_value(json!({
+            "members": [{
+                "name": "app",
+                "root": "crates/app",
+                "version": "0.1.0",
+                "edition": "2021",
+                "authors": [],
+                "description": null,
+                "license": null,
+                "repository": null,
+                "dependencies": [],
+                "dev_dependencies": [],
+                "build_dependencies": [],
+                "features": {},
+                "metadata": { "forgen": { "plugins": { "demo": { "limits": { "max": "lots" } } } } }
+            }],
+            "workspace_root": "/ws",
+            "target_directory": "/ws/target",
+            "metadata": { "forgen": { "plugins": { "demo": { "anchor": "counter", "limits": { "min": 1, "max": 9 } } } } }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn package_tables_override_workspace_tables() {
+        let mut manifest = manifest();
+        manifest.members[0].metadata =
+            json!({ "forgen": { "plugins": { "demo": { "limits": { "max": 50 } } } } });
+
+        let workspace: DemoConfig = load(&manifest, "demo", None).unwrap();
+        assert_eq!(workspace.limits, Limits { min: 1, max: 9 });
+
+        let package: DemoConfig = load(&manifest, "demo", Some("crates/app/src/lib.rs")).unwrap();
+        assert_eq!(package.anchor, "counter");
+        assert_eq!(package.limits, Limits { min: 1, max: 50 });
+
+        let missing: Limits = load(&manifest, "other", None).unwrap();
+        assert_eq!(missing, Limits::default());
+    }
+
+    #[test]
+    fn errors_name_the_plugin_table_and_key() {
+        let err =
+            load::<DemoConfig>(&manifest(), "demo", Some("crates/app/src/main.rs")).unwrap_err();
+        assert_eq!(err.source, ConfigSource::Package("app".to_owned()));
+        assert_eq!(err.key.as_deref(), Some("limits.max"));
+        assert!(err
+            .to_string()
+            .starts_with("invalid configuration for plugin `demo`: key `limits.max` in [package.metadata.forgen.plugins.demo] of package `app`"));
+    }
+}
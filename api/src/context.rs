@@ -1,3 +1,5 @@
+use crate::line_index::{LineCol, LineIndex};
+use crate::profile::{self, EventKind};
 use crate::query::SemanticHandle;
 use crate::runtime::{parse_generated_regions, GeneratedRegion};
 use crate::syntax::raw::RawNode;
@@ -55,6 +57,13 @@ impl<T> LazyValue<T> {
     pub fn is_initialized(&self) -> bool {
         self.value.get().is_some()
     }
+
+    /// Like [`LazyValue::get`], timing the initialization as `field` for the
+    /// plugin that triggers it.
+    fn get_profiled(&self, field: &str) -> &T {
+        self.value
+            .get_or_init(|| profile::time(EventKind::LazyInit, field, || (self.init)()))
+    }
 }
 
 impl<T: Clone + Send + Sync + 'static> Clone for LazyValue<T> {
@@ -155,7 +164,7 @@ impl WorkspaceContext {
 
     /// The source file tree rooted at the workspace root.
     pub fn file_tree(&self) -> &DirNode {
-        self.file_tree.get()
+        self.file_tree.get_profiled("file_tree")
     }
 
     /// Returns `true` if the file tree has already been computed.
@@ -189,6 +198,8 @@ pub struct FileContext {
     structs: LazyValue<Vec<StructDef>>,
     enums: LazyValue<Vec<EnumDef>>,
     impls: LazyValue<Vec<ImplDef>>,
+    /// Built from `source()` on first use.
+    line_index: OnceLock<LineIndex>,
 
     /// Oracle for semantic (RA-backed) queries on this file.
     /// `None` when running without a live rust-analyzer context (tests).
@@ -219,6 +230,7 @@ impl FileContext {
             structs,
             enums,
             impls,
+            line_index: OnceLock::new(),
             semantics,
         }
     }
@@ -228,12 +240,14 @@ impl FileContext {
     /// On the first pass this is the on-disk source. On later passes it may
     /// include earlier plugin output wrapped in generated-region markers.
     pub fn source(&self) -> &str {
-        self.source.get().as_str()
+        self.source.get_profiled("source").as_str()
     }
 
     /// All generated regions currently present in `source()`.
     pub fn generated_regions(&self) -> &[GeneratedRegion] {
-        self.generated_regions.get().as_slice()
+        self.generated_regions
+            .get_profiled("generated_regions")
+            .as_slice()
     }
 
     /// Iterate over generated regions emitted by `plugin_id` in earlier passes.
@@ -248,33 +262,33 @@ impl FileContext {
 
     /// The full CST of this file, with all trivia (whitespace, comments).
     pub fn tree(&self) -> &RawNode {
-        self.tree.get()
+        self.tree.get_profiled("tree")
     }
 
     /// Every `let` binding in the file, across all scopes (flattened).
     /// This includes bindings inside function bodies, closures, blocks, etc.
     pub fn let_bindings(&self) -> &[LetBinding] {
-        self.let_bindings.get().as_slice()
+        self.let_bindings.get_profiled("let_bindings").as_slice()
     }
 
     /// All function and method definitions in the file.
     pub fn functions(&self) -> &[FnDef] {
-        self.functions.get().as_slice()
+        self.functions.get_profiled("functions").as_slice()
     }
 
     /// Struct definitions.
     pub fn structs(&self) -> &[StructDef] {
-        self.structs.get().as_slice()
+        self.structs.get_profiled("structs").as_slice()
     }
 
     /// Enum definitions.
     pub fn enums(&self) -> &[EnumDef] {
-        self.enums.get().as_slice()
+        self.enums.get_profiled("enums").as_slice()
     }
 
     /// `impl` blocks (both inherent impls and trait impls).
     pub fn impls(&self) -> &[ImplDef] {
-        self.impls.get().as_slice()
+        self.impls.get_profiled("impls").as_slice()
     }
 
     /// Returns `true` if the file source has already been loaded.
@@ -317,6 +331,45 @@ impl FileContext {
         self.impls.is_initialized()
     }
 
+    /// Returns `true` if the line index has already been built.
+    pub fn is_line_index_initialized(&self) -> bool {
+        self.line_index.get().is_some()
+    }
+
+    /// Line/column index over `source()`, built on first use.
+    pub fn line_index(&self) -> &LineIndex {
+        self.line_index.get_or_init(|| {
+            profile::time(EventKind::LazyInit, "line_index", || {
+                LineIndex::new(self.source())
+            })
+        })
+    }
+
+    /// Zero-based line containing the byte `offset`.
+    pub fn line_of(&self, offset: u32) -> u32 {
+        self.line_index().line_of(offset)
+    }
+
+    /// Zero-based line/column (UTF-8 byte column) of the byte `offset`.
+    pub fn line_col(&self, offset: u32) -> LineCol {
+        self.line_index().line_col(offset)
+    }
+
+    /// Text of the zero-based line `n`, without its line terminator.
+    pub fn line_text(&self, n: u32) -> Option<&str> {
+        let range = self.line_index().line_range(n)?;
+        self.source().get(range.start as usize..range.end as usize)
+    }
+
+    /// Leading spaces and tabs of the line containing `offset`.
+    ///
+    /// Useful for indenting inserted statements to match their anchor.
+    pub fn indent_at(&self, offset: u32) -> &str {
+        let line = self.line_text(self.line_of(offset)).unwrap_or("");
+        let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+        &line[..indent_len]
+    }
+
     /// Find a `let` binding by variable name.
     pub fn binding(&self, name: &str) -> Option<&LetBinding> {
         self.let_bindings().iter().find(|b| b.name == name)
@@ -420,7 +473,7 @@ impl LetBinding {
     pub fn ty(&self) -> Option<&str> {
         self.explicit_type
             .as_deref()
-            .or_else(|| self.inferred_type.get().as_deref())
+            .or_else(|| self.inferred_type.get_profiled("binding_type").as_deref())
     }
 
     /// Returns `true` if this binding's effective type matches `ty`.
@@ -0,0 +1,39 @@
+use crate::TextRange;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A message a plugin attaches to a range of a workspace file, reported with
+/// [`PluginRuntime::warn`] or [`PluginRuntime::error`].
+///
+/// `range` is a byte range in the file as the plugin saw it in that pass
+/// ([`FileContext::source`]). The CLI maps it back to the original on-disk
+/// source before printing or saving it.
+///
+/// [`PluginRuntime::warn`]: crate::PluginRuntime::warn
+/// [`PluginRuntime::error`]: crate::PluginRuntime::error
+/// [`FileContext::source`]: crate::FileContext::source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub plugin_id: String,
+    pub severity: Severity,
+    /// Workspace-relative path, as in [`FileContext::path`](crate::FileContext::path).
+    pub file: String,
+    pub range: TextRange,
+    pub message: String,
+}
@@ -0,0 +1,146 @@
+//! Per-file output cache for [`PluginRuntime::for_each_file`].
+//!
+//! [`PluginRuntime::for_each_file`]: crate::PluginRuntime::for_each_file
+
+use crate::{Diagnostic, PluginState, Replacement, WorkspaceManifest};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// How many per-file runs were answered from the cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// Outputs of `run_file`, shared by all clones of a [`SuiteRuntime`].
+///
+/// [`SuiteRuntime`]: crate::SuiteRuntime
+#[derive(Debug, Default)]
+pub(crate) struct FileCache {
+    entries: Mutex<HashMap<CacheKey, Entry>>,
+    /// Incremented by every [`FileCache::start_run`].
+    run: AtomicU64,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    plugin_id: String,
+    path: String,
+    /// Hash of the file content and every input besides it.
+    fingerprint: u64,
+}
+
+/// What one `run_file` call produced.
+#[derive(Debug, Clone)]
+pub(crate) struct CachedRun {
+    pub replacements: Vec<Replacement>,
+    pub state: PluginState,
+    pub new_pins: Vec<(String, u64)>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl FileCache {
+    /// Start a new run and drop the entries the previous run did not use.
+    ///
+    /// Entries used in the previous run are kept, so the first pass of a
+    /// watch rerun still finds the results for unchanged files.
+    pub(crate) fn start_run(&self) {
+        let run = self.run.fetch_add(1, Ordering::Relaxed) + 1;
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|_, entry| entry.last_used + 1 >= run);
+    }
+
+    pub(crate) fn get(&self, key: &CacheKey) -> Option<CachedRun> {
+        let run = self.run.load(Ordering::Relaxed);
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get_mut(key) {
+            Some(entry) => {
+                entry.last_used = run;
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(entry.run.clone())
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn insert(&self, key: CacheKey, run: CachedRun) {
+        let last_used = self.run.load(Ordering::Relaxed);
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, Entry { run, last_used });
+    }
+
+    pub(crate) fn take_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.swap(0, Ordering::Relaxed),
+            misses: self.misses.swap(0, Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    run: CachedRun,
+    last_used: u64,
+}
+
+impl CacheKey {
+    /// `salt` covers the inputs shared by every file of a plugin run (plugin
+    /// identity, suite seed, configuration).
+    pub(crate) fn new(
+        plugin_id: &str,
+        path: &str,
+        salt: u64,
+        source: &str,
+        state: &PluginState,
+        pinned_seed: Option<u64>,
+    ) -> Self {
+        let mut hasher = DefaultHasher::new();
+        salt.hash(&mut hasher);
+        source.hash(&mut hasher);
+        serde_json::to_string(state)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        pinned_seed.hash(&mut hasher);
+        Self {
+            plugin_id: plugin_id.to_owned(),
+            path: path.to_owned(),
+            fingerprint: hasher.finish(),
+        }
+    }
+}
+
+/// Hash the inputs that apply to every file of one plugin run. The manifest
+/// stands in for the plugin's configuration.
+pub(crate) fn run_salt(
+    plugin_id: &str,
+    state_version: u32,
+    suite_seed: u64,
+    manifest: &WorkspaceManifest,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    plugin_id.hash(&mut hasher);
+    state_version.hash(&mut hasher);
+    suite_seed.hash(&mut hasher);
+    serde_json::to_string(manifest)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,139 @@
+use crate::FileContext;
+
+/// Which files a plugin's [`Plugin::run_file`] needs to see, returned by
+/// [`Plugin::interest`].
+///
+/// Files that do not match are not passed to `run_file` at all, and their
+/// per-file state is kept as it is. With `cached` set, the output of
+/// `run_file` is also cached per file: a file whose content, plugin state,
+/// seed and configuration are unchanged reuses the previous result instead
+/// of running the plugin again, across passes and watch reruns.
+///
+/// [`Plugin::run_file`]: crate::Plugin::run_file
+/// [`Plugin::interest`]: crate::Plugin::interest
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Interest {
+    /// Globs over workspace-relative paths (`*`, `?` and `**`); empty matches
+    /// every file.
+    pub paths: Vec<String>,
+    /// Only files containing at least one item of these kinds; empty matches
+    /// every file.
+    pub items: Vec<ItemKind>,
+    /// `run_file` only depends on its own file, not on the `WorkspaceContext`,
+    /// inferred types or semantic queries, so its output can be cached per
+    /// file. Off unless the plugin asks for it with [`Interest::cached`].
+    pub cached: bool,
+}
+
+/// Item kinds an [`Interest`] can ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ItemKind {
+    Functions,
+    Structs,
+    Enums,
+    Impls,
+    LetBindings,
+}
+
+impl Interest {
+    /// Every file, without per-file caching. This is the default.
+    pub fn all_files() -> Self {
+        Self::default()
+    }
+
+    /// Files matching any of `globs`, e.g. `"src/**/*.rs"`.
+    pub fn paths<I, S>(globs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            paths: globs.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Cache the output of `run_file` per file. Only correct when `run_file`
+    /// reads nothing but `file`, its per-file state, its configuration and
+    /// `rng_for_file`; anything else can change without the file changing.
+    pub fn cached(mut self) -> Self {
+        self.cached = true;
+        self
+    }
+
+    /// Narrow to files that contain at least one item of `kinds`.
+    pub fn with_items(mut self, kinds: impl IntoIterator<Item = ItemKind>) -> Self {
+        self.items.extend(kinds);
+        self
+    }
+
+    /// Whether `file` should be passed to `run_file`. Checking item kinds
+    /// initializes the corresponding lazy fields of `file`.
+    pub fn matches(&self, file: &FileContext) -> bool {
+        let path_matches = self.paths.is_empty()
+            || self
+                .paths
+                .iter()
+                .any(|pattern| glob_matches(pattern, &file.path));
+        path_matches
+            && (self.items.is_empty()
+                || self.items.iter().any(|kind| match kind {
+                    ItemKind::Functions => !file.functions().is_empty(),
+                    ItemKind::Structs => !file.structs().is_empty(),
+                    ItemKind::Enums => !file.enums().is_empty(),
+                    ItemKind::Impls => !file.impls().is_empty(),
+                    ItemKind::LetBindings => !file.let_bindings().is_empty(),
+                }))
+    }
+}
+
+/// Match a workspace-relative `path` against `pattern`.
+///
+/// `*` and `?` match within one path segment, `**` matches any number of
+/// whole segments (including none).
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    match_segments(&pattern, &path)
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                let segment: Vec<char> = segment.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                match_segment(&segment, &name) && match_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_segment(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| match_segment(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && match_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_segment(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_segments_and_double_stars() {
+        assert!(glob_matches("src/*.rs", "src/lib.rs"));
+        assert!(!glob_matches("src/*.rs", "src/nested/lib.rs"));
+        assert!(glob_matches("src/**/*.rs", "src/lib.rs"));
+        assert!(glob_matches("src/**/*.rs", "src/a/b/lib.rs"));
+        assert!(glob_matches("**/tests/*.rs", "crates/x/tests/it.rs"));
+        assert!(glob_matches("src/ma?n.rs", "src/main.rs"));
+        assert!(!glob_matches("src/ma?n.rs", "src/man.rs"));
+        assert!(!glob_matches("src/*.rs", "src/lib.rs.bak"));
+    }
+}
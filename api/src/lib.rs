@@ -51,7 +51,7 @@
 //!                 }
 //!
 //!                 let insert_at = binding.range.end;
-//!                 let indent = leading_indent(file.source(), insert_at);
+//!                 let indent = file.indent_at(insert_at);
 //!                 let sample: u8 = rand::Rng::gen_range(&mut rng, 0..=9);
 //!
 //!                 replacements.push(Replacement::insert(
@@ -74,16 +74,6 @@
 //!     }
 //! }
 //!
-//! fn leading_indent(source: &str, offset: u32) -> String {
-//!     let up_to = (offset as usize).min(source.len());
-//!     let line_start = source[..up_to].rfind('\n').map(|i| i + 1).unwrap_or(0);
-//!
-//!     source[line_start..]
-//!         .chars()
-//!         .take_while(|c| *c == ' ' || *c == '\t')
-//!         .collect()
-//! }
-//!
 //! fn run(ctx: &WorkspaceContext, runtime: &mut SuiteRuntime) -> Vec<FileReplacement> {
 //!     runtime.run_plugin(&MyPlugin, ctx)
 //! }
@@ -91,30 +81,49 @@
 //! plugin_suite!(run);
 //! ```
 
+mod config;
 mod context;
+mod diagnostic;
+mod file_cache;
+mod interest;
+mod line_index;
 pub mod manifest;
+pub mod parallel;
 mod plugin;
+pub mod profile;
 pub mod query;
 mod replacement;
 mod runtime;
+pub mod saved;
+mod schedule;
+mod seed_lock;
 pub mod syntax;
+mod template;
 pub mod tree;
 
 // Re-export everything so plugin authors only need `use forgen_api::*;`
 // (or cherry-pick individual names).
 
+pub use config::{ConfigError, ConfigSource};
 pub use context::{
     EnumDef, FieldDef, FileContext, FnDef, FnParam, ImplDef, LazyValue, LetBinding, StructDef,
     VariantDef, WorkspaceContext,
 };
+pub use diagnostic::{Diagnostic, Severity};
+pub use file_cache::CacheStats;
+pub use interest::{glob_matches, Interest, ItemKind};
+pub use line_index::{LineCol, LineIndex, Utf16LineCol};
 pub use manifest::{Dependency, DependencySource, PackageManifest, WorkspaceManifest};
-pub use plugin::Plugin;
+pub use plugin::{Plugin, PluginInfo};
+pub use profile::Profiler;
 pub use query::{SemanticHandle, SemanticQuery, SemanticResult};
 pub use rand;
 pub use runtime::{
-    is_valid_plugin_id, parse_generated_regions, GeneratedRegion, PluginRuntime, PluginState,
-    SuiteRuntime,
+    is_valid_plugin_id, panic_message, parse_generated_regions, GeneratedRegion, PluginFilter,
+    PluginPanic, PluginRuntime, PluginState, SuiteRuntime,
 };
+pub use schedule::{Plan, Registration, Schedule, ScheduleError, DEFAULT_PHASE};
+pub use seed_lock::{LockedFile, SeedLock};
 
 /// Compile-time FNV-1a hash of a byte string.
 ///
@@ -141,9 +150,17 @@ const fn fnv1a(s: &[u8]) -> u64 {
 /// error message.
 ///
 /// **You never need to bump this by hand.** Incrementing the `[package]
-/// version` in `forgen-api/Cargo.toml` changes the hash automatically.
-pub const FORGEN_ABI_VERSION: u64 = fnv1a(env!("CARGO_PKG_VERSION").as_bytes());
+/// version` in `forgen-api/Cargo.toml` changes the hash automatically, so
+/// bump that version whenever anything that crosses the dylib boundary
+/// changes: the exported symbols, the layout of `SuiteRuntime`, the context
+/// types or `FileReplacement`, or the methods of the `Plugin` trait.
+pub const FORGEN_ABI_VERSION: u64 = fnv1a(FORGEN_API_VERSION.as_bytes());
+
+/// The `forgen-api` release this crate is, e.g. for pinning it in a new
+/// suite's `Cargo.toml` so that its [`FORGEN_ABI_VERSION`] matches.
+pub const FORGEN_API_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub use replacement::{FileReplacement, Replacement, TextRange};
+pub use template::{Fragment, Template, TemplateError, ValueKind};
 pub use tree::{DirNode, FileRef, FsEntry};
 
 // Re-export serde_json so the proc-macro helpers can reference it as
@@ -0,0 +1,287 @@
+use crate::TextRange;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Zero-based line/column position where `col` counts UTF-8 bytes from the
+/// start of the line.
+///
+/// `Display` prints the one-based `line:col` form used in human-readable
+/// messages (`12:5`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LineCol {
+    pub line: u32,
+    pub col: u32,
+}
+
+impl fmt::Display for LineCol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line + 1, self.col + 1)
+    }
+}
+
+/// Zero-based line/column position where `col` counts UTF-16 code units, as
+/// used by LSP and most editors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Utf16LineCol {
+    pub line: u32,
+    pub col: u32,
+}
+
+/// A non-ASCII character on a line, recorded so UTF-16 columns can be
+/// converted without keeping the source text around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WideChar {
+    /// Byte offset of the character relative to the start of its line.
+    start: u32,
+    /// Length of the character in UTF-8 bytes.
+    utf8_len: u32,
+    /// Length of the character in UTF-16 code units.
+    utf16_len: u32,
+}
+
+/// Maps byte offsets in a source file to line/column positions and back.
+///
+/// Every API in `forgen-api` speaks `u32` byte offsets ([`TextRange`]). Use a
+/// `LineIndex` (usually via [`FileContext::line_index`]) when a position has
+/// to be shown to a human or exchanged with an editor.
+///
+/// Lines are split on `\n`; a `\r` directly before it is treated as part of
+/// the line terminator by [`LineIndex::line_range`].
+///
+/// [`FileContext::line_index`]: crate::FileContext::line_index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    /// Byte offset of the first character of every line. Always starts with 0.
+    line_starts: Vec<u32>,
+    /// Non-ASCII characters, keyed by line number. Lines without any are absent.
+    wide_chars: HashMap<u32, Vec<WideChar>>,
+    /// Sorted numbers of the lines whose terminator is `\r\n`.
+    crlf_lines: Vec<u32>,
+    len: u32,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut line_starts = vec![0u32];
+        let mut wide_chars: HashMap<u32, Vec<WideChar>> = HashMap::new();
+        let mut crlf_lines = Vec::new();
+        let mut line = 0u32;
+        let mut previous = '\0';
+
+        for (offset, ch) in text.char_indices() {
+            let offset = offset as u32;
+
+            if ch == '\n' {
+                if previous == '\r' {
+                    crlf_lines.push(line);
+                }
+                line += 1;
+                line_starts.push(offset + 1);
+            } else if !ch.is_ascii() {
+                let line_start = line_starts[line as usize];
+                wide_chars.entry(line).or_default().push(WideChar {
+                    start: offset - line_start,
+                    utf8_len: ch.len_utf8() as u32,
+                    utf16_len: ch.len_utf16() as u32,
+                });
+            }
+
+            previous = ch;
+        }
+
+        Self {
+            line_starts,
+            wide_chars,
+            crlf_lines,
+            len: text.len() as u32,
+        }
+    }
+
+    /// Number of lines. An empty file and a file without a trailing newline
+    /// both count their last (possibly empty) line.
+    pub fn line_count(&self) -> u32 {
+        self.line_starts.len() as u32
+    }
+
+    /// Total length of the indexed text in bytes.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns `true` if the indexed text is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Zero-based line containing `offset`. Offsets past the end of the text
+    /// resolve to the last line.
+    pub fn line_of(&self, offset: u32) -> u32 {
+        let offset = offset.min(self.len);
+        (self.line_starts.partition_point(|&start| start <= offset) - 1) as u32
+    }
+
+    /// Byte offset where `line` starts, or `None` if the line does not exist.
+    pub fn line_start(&self, line: u32) -> Option<u32> {
+        self.line_starts.get(line as usize).copied()
+    }
+
+    /// Byte range of `line`, excluding its `\n` or `\r\n` terminator.
+    pub fn line_range(&self, line: u32) -> Option<TextRange> {
+        let start = self.line_start(line)?;
+        let end = match self.line_start(line + 1) {
+            Some(next) if self.crlf_lines.binary_search(&line).is_ok() => next - 2,
+            Some(next) => next - 1,
+            None => self.len,
+        };
+        Some(TextRange::new(start, end))
+    }
+
+    /// Byte range of `line`, including its line terminator (if any).
+    pub fn full_line_range(&self, line: u32) -> Option<TextRange> {
+        let start = self.line_start(line)?;
+        let end = self.line_start(line + 1).unwrap_or(self.len);
+        Some(TextRange::new(start, end))
+    }
+
+    /// Convert a byte offset to a line/column pair with a UTF-8 byte column.
+    pub fn line_col(&self, offset: u32) -> LineCol {
+        let offset = offset.min(self.len);
+        let line = self.line_of(offset);
+        LineCol {
+            line,
+            col: offset - self.line_starts[line as usize],
+        }
+    }
+
+    /// Convert a byte offset to a line/column pair with a UTF-16 column.
+    pub fn line_col_utf16(&self, offset: u32) -> Utf16LineCol {
+        let LineCol { line, col } = self.line_col(offset);
+        Utf16LineCol {
+            line,
+            col: self.utf8_to_utf16_col(line, col),
+        }
+    }
+
+    /// Convert a UTF-8 line/column pair back to a byte offset.
+    ///
+    /// Returns `None` if the line does not exist or the column lies past the
+    /// end of the line. The end of the line is the offset of its terminator;
+    /// columns inside `\n` or `\r\n` are rejected.
+    pub fn offset(&self, pos: LineCol) -> Option<u32> {
+        let range = self.line_range(pos.line)?;
+        let offset = range.start.checked_add(pos.col)?;
+        (offset <= range.end).then_some(offset)
+    }
+
+    /// Convert a UTF-16 line/column pair back to a byte offset.
+    ///
+    /// Returns `None` if the line does not exist or the column lies past the
+    /// end of the line.
+    pub fn offset_utf16(&self, pos: Utf16LineCol) -> Option<u32> {
+        let col = self.utf16_to_utf8_col(pos.line, pos.col);
+        self.offset(LineCol {
+            line: pos.line,
+            col,
+        })
+    }
+
+    /// Convert a UTF-8 line/column pair to its UTF-16 equivalent.
+    pub fn to_utf16(&self, pos: LineCol) -> Utf16LineCol {
+        Utf16LineCol {
+            line: pos.line,
+            col: self.utf8_to_utf16_col(pos.line, pos.col),
+        }
+    }
+
+    /// Convert a UTF-16 line/column pair to its UTF-8 equivalent.
+    pub fn to_utf8(&self, pos: Utf16LineCol) -> LineCol {
+        LineCol {
+            line: pos.line,
+            col: self.utf16_to_utf8_col(pos.line, pos.col),
+        }
+    }
+
+    fn utf8_to_utf16_col(&self, line: u32, col: u32) -> u32 {
+        let mut result = col;
+        if let Some(chars) = self.wide_chars.get(&line) {
+            for ch in chars {
+                if ch.start >= col {
+                    break;
+                }
+                result -= ch.utf8_len - ch.utf16_len;
+            }
+        }
+        result
+    }
+
+    fn utf16_to_utf8_col(&self, line: u32, col: u32) -> u32 {
+        let mut result = col;
+        if let Some(chars) = self.wide_chars.get(&line) {
+            for ch in chars {
+                // `ch.start` is a UTF-8 column; compare it against the UTF-8
+                // column accumulated so far.
+                if ch.start >= result {
+                    break;
+                }
+                result += ch.utf8_len - ch.utf16_len;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_lines_and_columns() {
+        let index = LineIndex::new("fn main() {\n    let x = 1;\n}\n");
+
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.line_col(0), LineCol { line: 0, col: 0 });
+        assert_eq!(index.line_col(16), LineCol { line: 1, col: 4 });
+        assert_eq!(index.line_of(27), 2);
+        assert_eq!(index.offset(LineCol { line: 1, col: 4 }), Some(16));
+        assert_eq!(index.offset(LineCol { line: 1, col: 40 }), None);
+        assert_eq!(index.line_col(16).to_string(), "2:5");
+    }
+
+    #[test]
+    fn line_ranges_exclude_terminators() {
+        let index = LineIndex::new("a\r\nbc\nd");
+
+        assert_eq!(index.line_range(0), Some(TextRange::new(0, 1)));
+        assert_eq!(index.full_line_range(0), Some(TextRange::new(0, 3)));
+        assert_eq!(index.line_range(1), Some(TextRange::new(3, 5)));
+        assert_eq!(index.line_range(2), Some(TextRange::new(6, 7)));
+        assert_eq!(index.line_range(3), None);
+    }
+
+    #[test]
+    fn offsets_stop_at_the_line_terminator() {
+        let index = LineIndex::new("a\r\nbc\nd");
+
+        assert_eq!(index.offset(LineCol { line: 0, col: 1 }), Some(1));
+        assert_eq!(index.offset(LineCol { line: 0, col: 2 }), None);
+        assert_eq!(index.offset(LineCol { line: 0, col: 3 }), None);
+        assert_eq!(index.offset(LineCol { line: 1, col: 2 }), Some(5));
+        assert_eq!(index.offset(LineCol { line: 1, col: 3 }), None);
+        assert_eq!(index.offset(LineCol { line: 2, col: 1 }), Some(7));
+        assert_eq!(index.offset(LineCol { line: 2, col: 2 }), None);
+    }
+
+    #[test]
+    fn converts_utf16_columns() {
+        // `é` is 2 UTF-8 bytes / 1 UTF-16 unit, `𝕏` is 4 bytes / 2 units.
+        let text = "let s = \"é𝕏\"; x";
+        let index = LineIndex::new(text);
+        let x = text.find('x').unwrap() as u32;
+
+        let pos = index.line_col_utf16(x);
+        assert_eq!(pos, Utf16LineCol { line: 0, col: 15 });
+        assert_eq!(index.line_col(x).col, 18);
+        assert_eq!(index.offset_utf16(pos), Some(x));
+    }
+}
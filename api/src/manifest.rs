@@ -20,6 +20,20 @@ impl WorkspaceManifest {
         self.members.iter().find(|p| p.name == name)
     }
 
+    /// Find the package that owns a workspace-relative file path, i.e. the
+    /// member with the longest [`PackageManifest::root`] that contains it.
+    pub fn package_for_file(&self, path: &str) -> Option<&PackageManifest> {
+        self.members
+            .iter()
+            .filter(|p| {
+                p.root.is_empty()
+                    || path
+                        .strip_prefix(p.root.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .max_by_key(|p| p.root.len())
+    }
+
     /// Read a typed value from `[workspace.metadata.forgen.<key>]`.
     /// Returns `None` if the key is absent or cannot be deserialized to `T`.
     pub fn forgen_metadata<T: serde::de::DeserializeOwned>(&self, key: &str) -> Option<T> {
@@ -32,6 +46,10 @@ impl WorkspaceManifest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PackageManifest {
     pub name: String,
+    /// Directory containing the package's `Cargo.toml`, relative to the
+    /// workspace root (forward slashes, empty for the root package).
+    #[serde(default)]
+    pub root: String,
     pub version: String,
     pub edition: String,
     pub authors: Vec<String>,
@@ -0,0 +1,82 @@
+//! Ordered fan-out over scoped threads, shared by the plugin runtime and the
+//! CLI.
+
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Number of worker threads to use when none is configured.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Apply `f` to every item on up to `jobs` threads and return the results in
+/// input order.
+///
+/// Runs inline when `jobs <= 1` or there is at most one item. If `f` panics,
+/// the remaining items still run and the first panic (in input order) is
+/// resumed on the calling thread afterwards, so callers see the original
+/// payload instead of a generic "scoped thread panicked".
+pub fn map_ordered<T, R, F>(jobs: usize, items: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let workers = jobs.min(items.len());
+    if workers <= 1 {
+        return items.into_iter().map(f).collect();
+    }
+
+    let inputs: Vec<Mutex<Option<T>>> = items.into_iter().map(|t| Mutex::new(Some(t))).collect();
+    let outputs: Vec<Mutex<Option<thread::Result<R>>>> =
+        inputs.iter().map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(index) else {
+                    break;
+                };
+                let item = input.lock().unwrap().take().expect("item taken twice");
+                let result = panic::catch_unwind(AssertUnwindSafe(|| f(item)));
+                *outputs[index].lock().unwrap() = Some(result);
+            });
+        }
+    });
+
+    let mut results = Vec::with_capacity(outputs.len());
+    for output in outputs {
+        match output.into_inner().unwrap().expect("every item ran") {
+            Ok(result) => results.push(result),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_input_order_and_resumes_the_first_panic() {
+        let squares = map_ordered(4, (0..100u64).collect(), |n| n * n);
+        assert_eq!(squares, (0..100u64).map(|n| n * n).collect::<Vec<_>>());
+
+        let caught = panic::catch_unwind(|| {
+            map_ordered(4, (0..10).collect(), |n: i32| {
+                if n >= 7 {
+                    panic!("item {n}");
+                }
+                n
+            })
+        })
+        .unwrap_err();
+        assert_eq!(caught.downcast_ref::<String>().unwrap(), "item 7");
+    }
+}
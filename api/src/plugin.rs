@@ -1,4 +1,5 @@
-use crate::{FileReplacement, PluginRuntime, WorkspaceContext};
+use crate::{FileContext, FileReplacement, Interest, PluginRuntime, Replacement, WorkspaceContext};
+use serde::{Deserialize, Serialize};
 
 /// Implemented by every Forgen plugin.
 ///
@@ -16,6 +17,30 @@ pub trait Plugin: Send + Sync {
     /// The value must contain only ASCII letters, digits, `_`, or `-`.
     fn name(&self) -> &str;
 
+    /// Version of the layout this plugin stores in its [`PluginState`].
+    ///
+    /// When the CLI persists state between runs it records this value next to
+    /// the state. If a later run reports a different version, the stored state
+    /// is discarded before the plugin runs so it never reads data in a layout
+    /// it no longer understands. Bump it whenever the meaning of a state key
+    /// changes.
+    ///
+    /// [`PluginState`]: crate::PluginState
+    fn state_version(&self) -> u32 {
+        0
+    }
+
+    /// Version shown by `cargo forgen list`, usually
+    /// `env!("CARGO_PKG_VERSION")` of the crate that defines the plugin.
+    fn version(&self) -> Option<&str> {
+        None
+    }
+
+    /// One-line summary shown by `cargo forgen list`.
+    fn description(&self) -> Option<&str> {
+        None
+    }
+
     /// Analyse the workspace snapshot for the current pass and return any new
     /// replacements to apply.
     ///
@@ -34,10 +59,73 @@ pub trait Plugin: Send + Sync {
     ///   plugins do not need to account for position shifts from earlier
     ///   insertions in the same file.
     /// - Returning an empty `Vec` is valid and means "nothing new to replace".
-    fn run(&self, ctx: &WorkspaceContext, runtime: &mut PluginRuntime<'_>) -> Vec<FileReplacement>;
+    ///
+    /// The default implementation calls [`Plugin::run_file`] for every file,
+    /// spread across worker threads (see [`PluginRuntime::for_each_file`]).
+    /// Override `run` instead when the plugin needs to see several files at
+    /// once.
+    fn run(&self, ctx: &WorkspaceContext, runtime: &mut PluginRuntime<'_>) -> Vec<FileReplacement> {
+        runtime.for_each_file(ctx, |file, runtime| self.run_file(file, ctx, runtime))
+    }
+
+    /// Which files [`Plugin::run_file`] is called for, and whether its output
+    /// may be cached per file. Defaults to every file, uncached.
+    ///
+    /// Add [`Interest::cached`] only when `run_file` does not read other files
+    /// through `ctx` and does not rely on inferred types or semantic queries;
+    /// otherwise a cached result goes stale when only those files change.
+    fn interest(&self) -> Interest {
+        Interest::default()
+    }
+
+    /// Per-file entry point used by the default [`Plugin::run`]; returns the
+    /// replacements for `file` only.
+    ///
+    /// Files are processed concurrently, so `runtime.state()` is scoped to
+    /// `file` and `ctx` should only be read. The same idempotency rules as
+    /// for `run` apply.
+    fn run_file(
+        &self,
+        file: &FileContext,
+        ctx: &WorkspaceContext,
+        runtime: &mut PluginRuntime<'_>,
+    ) -> Vec<Replacement> {
+        let _ = (file, ctx, runtime);
+        Vec::new()
+    }
+}
+
+/// What a suite reports about one of its plugins without running it; see
+/// [`SuiteRuntime::discover_plugins`](crate::SuiteRuntime::discover_plugins).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginInfo {
+    /// [`Plugin::name`].
+    pub id: String,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub state_version: u32,
+    /// The [`Schedule`](crate::Schedule) phase the plugin is registered in;
+    /// `None` for plugins run with
+    /// [`SuiteRuntime::run_plugin`](crate::SuiteRuntime::run_plugin).
+    pub phase: Option<String>,
+}
+
+impl PluginInfo {
+    pub fn of<P>(plugin: &P, phase: Option<&str>) -> Self
+    where
+        P: Plugin + ?Sized,
+    {
+        Self {
+            id: plugin.name().to_owned(),
+            version: plugin.version().map(str::to_owned),
+            description: plugin.description().map(str::to_owned),
+            state_version: plugin.state_version(),
+            phase: phase.map(str::to_owned),
+        }
+    }
 }
 
-/// Generates the three C-ABI entry points required for a Forgen suite
+/// Generates the C-ABI entry points required for a Forgen suite
 /// `cdylib` crate.
 ///
 /// The single argument is any expression that, when called with
@@ -52,6 +140,7 @@ pub trait Plugin: Send + Sync {
 /// | `forgen_abi_version`  | `extern "C" fn() -> u64`                                      |
 /// | `forgen_run`          | `unsafe extern "C" fn(*const WorkspaceContext, *mut SuiteRuntime) -> *mut Vec<FileReplacement>` |
 /// | `forgen_free`         | `unsafe extern "C" fn(*mut Vec<FileReplacement>)`             |
+/// | `forgen_plugins`      | `extern "C" fn() -> *mut Vec<PluginInfo>`                     |
 ///
 /// `forgen_run` receives a raw pointer to the `WorkspaceContext` that lives
 /// on the CLI's stack plus a raw pointer to the in-memory [`SuiteRuntime`]
@@ -59,6 +148,14 @@ pub trait Plugin: Send + Sync {
 /// frees it with `Box::from_raw`; both sides must use the system allocator
 /// (no custom `#[global_allocator]`).
 ///
+/// Panics never cross the `extern "C"` boundary: [`SuiteRuntime::run_plugin`]
+/// isolates each plugin, and a panic in the suite function itself makes
+/// `forgen_run` return null, which the CLI reports as a failed pass.
+///
+/// `forgen_plugins` calls the suite function once with
+/// [`SuiteRuntime::discover_plugins`], which records the plugins it would run
+/// instead of running them, so `cargo forgen list` needs no workspace.
+///
 /// # Safety requirements
 ///
 /// - Both the CLI binary and the suite dylib must be compiled with the
@@ -116,7 +213,8 @@ macro_rules! plugin_suite {
         /// `__ctx` must be a valid, non-null pointer to a `WorkspaceContext`
         /// that remains valid for the entire duration of this call.  The
         /// returned pointer is a `Box`-backed heap allocation; the caller
-        /// must eventually pass it to [`forgen_free`].
+        /// must eventually pass it to [`forgen_free`]. Returns null if the
+        /// suite function panicked.
         #[no_mangle]
         pub unsafe extern "C" fn forgen_run(
             __ctx: *const $crate::WorkspaceContext,
@@ -127,9 +225,23 @@ macro_rules! plugin_suite {
             // call.
             let __ctx_ref: &$crate::WorkspaceContext = unsafe { &*__ctx };
             let __runtime_ref: &mut $crate::SuiteRuntime = unsafe { &mut *__runtime };
-            let __result: ::std::vec::Vec<$crate::FileReplacement> =
-                ($run_fn)(__ctx_ref, __runtime_ref);
-            ::std::boxed::Box::into_raw(::std::boxed::Box::new(__result))
+            let __result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+                let __replacements: ::std::vec::Vec<$crate::FileReplacement> =
+                    ($run_fn)(__ctx_ref, __runtime_ref);
+                __replacements
+            }));
+            match __result {
+                Ok(__replacements) => {
+                    ::std::boxed::Box::into_raw(::std::boxed::Box::new(__replacements))
+                }
+                Err(__payload) => {
+                    ::std::eprintln!(
+                        "[forgen] plugin suite panicked outside of a plugin: {}",
+                        $crate::panic_message(__payload.as_ref())
+                    );
+                    ::std::ptr::null_mut()
+                }
+            }
         }
 
         /// Frees a `Vec<FileReplacement>` previously returned by
@@ -147,5 +259,25 @@ macro_rules! plugin_suite {
                 drop(::std::boxed::Box::from_raw(__ptr));
             }
         }
+
+        /// Lists the plugins this suite runs, without running them. The
+        /// returned pointer is a `Box`-backed heap allocation owned by the
+        /// caller. Returns null if the suite function panicked.
+        #[no_mangle]
+        pub extern "C" fn forgen_plugins() -> *mut ::std::vec::Vec<$crate::PluginInfo> {
+            let __result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+                $crate::SuiteRuntime::discover_plugins($run_fn)
+            }));
+            match __result {
+                Ok(__plugins) => ::std::boxed::Box::into_raw(::std::boxed::Box::new(__plugins)),
+                Err(__payload) => {
+                    ::std::eprintln!(
+                        "[forgen] plugin suite panicked while listing its plugins: {}",
+                        $crate::panic_message(__payload.as_ref())
+                    );
+                    ::std::ptr::null_mut()
+                }
+            }
+        }
     };
 }
@@ -0,0 +1,376 @@
+//! Per-plugin profiling.
+//!
+//! When a [`Profiler`] is installed with [`SuiteRuntime::set_profiler`], every
+//! plugin run records how long it took and how many replacements it returned,
+//! and the semantic queries and lazy [`FileContext`] fields it triggers are
+//! timed and attributed to it — also on the worker threads of
+//! [`PluginRuntime::for_each_file`].
+//!
+//! [`SuiteRuntime::set_profiler`]: crate::SuiteRuntime::set_profiler
+//! [`FileContext`]: crate::FileContext
+//! [`PluginRuntime::for_each_file`]: crate::PluginRuntime::for_each_file
+
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// What a [`ProfileEvent`] measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// One [`Plugin::run`](crate::Plugin::run) call; `name` is the plugin id.
+    Run,
+    /// One [`Plugin::run_file`](crate::Plugin::run_file) call; `name` is the
+    /// file path.
+    File,
+    /// One [`SemanticHandle::query`](crate::SemanticHandle::query); `name` is
+    /// the query kind, e.g. `InferTypeAt`.
+    Query,
+    /// First access to a lazy context field; `name` is the field, e.g.
+    /// `tree` or `binding_type`.
+    LazyInit,
+    /// A span recorded by the runner itself, e.g. a whole pass.
+    Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileEvent {
+    pub kind: EventKind,
+    pub name: String,
+    /// The plugin that was running; `None` for runner spans.
+    pub plugin_id: Option<String>,
+    /// The pass set with [`Profiler::set_pass`] when the event started.
+    pub pass: usize,
+    /// Small per-process thread number; `0` is reserved for runner spans.
+    pub thread: u64,
+    /// Offset from the profiler's creation.
+    pub start: Duration,
+    pub duration: Duration,
+    /// Replacements returned by a `Run` or `File` event.
+    pub replacements: usize,
+}
+
+/// Collects [`ProfileEvent`]s from every thread of a run.
+#[derive(Debug)]
+pub struct Profiler {
+    epoch: Instant,
+    pass: AtomicUsize,
+    events: Mutex<Vec<ProfileEvent>>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            pass: AtomicUsize::new(0),
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Attribute the events that start from now on to `pass`.
+    pub fn set_pass(&self, pass: usize) {
+        self.pass.store(pass, Ordering::Relaxed);
+    }
+
+    /// Record a runner span that started at `start` and ends now.
+    pub fn span(&self, name: impl Into<String>, start: Instant) {
+        self.push(EventKind::Span, name.into(), None, 0, start, 0);
+    }
+
+    /// Take the events recorded so far.
+    pub fn take(&self) -> Profile {
+        Profile {
+            events: std::mem::take(&mut *self.events.lock().unwrap()),
+        }
+    }
+
+    /// The plugin runs of `pass` recorded so far. Unlike [`Profiler::take`],
+    /// this leaves the events in place.
+    pub fn runs_in_pass(&self, pass: usize) -> Vec<ProfileEvent> {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| event.kind == EventKind::Run && event.pass == pass)
+            .cloned()
+            .collect()
+    }
+
+    fn push(
+        &self,
+        kind: EventKind,
+        name: String,
+        plugin_id: Option<String>,
+        thread: u64,
+        start: Instant,
+        replacements: usize,
+    ) {
+        let event = ProfileEvent {
+            kind,
+            name,
+            plugin_id,
+            pass: self.pass.load(Ordering::Relaxed),
+            thread,
+            start: start.saturating_duration_since(self.epoch),
+            duration: start.elapsed(),
+            replacements,
+        };
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+/// The events of one or more runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    pub events: Vec<ProfileEvent>,
+}
+
+/// Totals for one plugin across all passes of a [`Profile`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginProfile {
+    pub plugin_id: String,
+    pub runs: usize,
+    pub run_time: Duration,
+    pub max_run_time: Duration,
+    pub replacements: usize,
+    pub queries: usize,
+    pub query_time: Duration,
+    pub lazy_inits: usize,
+    pub lazy_init_time: Duration,
+}
+
+/// Count and latency of one query kind or lazy field.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimingStats {
+    pub name: String,
+    pub count: usize,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl TimingStats {
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.total / self.count as u32
+        }
+    }
+
+    fn add(&mut self, duration: Duration) {
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileSummary {
+    /// Slowest plugin first.
+    pub plugins: Vec<PluginProfile>,
+    /// Per query kind, most total time first.
+    pub queries: Vec<TimingStats>,
+    /// Per lazy field, most total time first.
+    pub lazy_fields: Vec<TimingStats>,
+}
+
+impl Profile {
+    pub fn summary(&self) -> ProfileSummary {
+        let mut plugins: BTreeMap<&str, PluginProfile> = BTreeMap::new();
+        let mut queries: BTreeMap<&str, TimingStats> = BTreeMap::new();
+        let mut lazy_fields: BTreeMap<&str, TimingStats> = BTreeMap::new();
+
+        for event in &self.events {
+            let plugin = event.plugin_id.as_deref().map(|plugin_id| {
+                plugins.entry(plugin_id).or_insert_with(|| PluginProfile {
+                    plugin_id: plugin_id.to_owned(),
+                    ..PluginProfile::default()
+                })
+            });
+            match (event.kind, plugin) {
+                (EventKind::Run, Some(plugin)) => {
+                    plugin.runs += 1;
+                    plugin.run_time += event.duration;
+                    plugin.max_run_time = plugin.max_run_time.max(event.duration);
+                    plugin.replacements += event.replacements;
+                }
+                (EventKind::Query, plugin) => {
+                    if let Some(plugin) = plugin {
+                        plugin.queries += 1;
+                        plugin.query_time += event.duration;
+                    }
+                    stats_entry(&mut queries, &event.name).add(event.duration);
+                }
+                (EventKind::LazyInit, plugin) => {
+                    if let Some(plugin) = plugin {
+                        plugin.lazy_inits += 1;
+                        plugin.lazy_init_time += event.duration;
+                    }
+                    stats_entry(&mut lazy_fields, &event.name).add(event.duration);
+                }
+                _ => {}
+            }
+        }
+
+        let mut plugins: Vec<_> = plugins.into_values().collect();
+        plugins.sort_by_key(|plugin| Reverse(plugin.run_time));
+        ProfileSummary {
+            plugins,
+            queries: by_total(queries),
+            lazy_fields: by_total(lazy_fields),
+        }
+    }
+}
+
+fn stats_entry<'m, 'a>(
+    stats: &'m mut BTreeMap<&'a str, TimingStats>,
+    name: &'a str,
+) -> &'m mut TimingStats {
+    stats.entry(name).or_insert_with(|| TimingStats {
+        name: name.to_owned(),
+        ..TimingStats::default()
+    })
+}
+
+fn by_total(stats: BTreeMap<&str, TimingStats>) -> Vec<TimingStats> {
+    let mut stats: Vec<_> = stats.into_values().collect();
+    stats.sort_by_key(|stats| Reverse(stats.total));
+    stats
+}
+
+// ---------------------------------------------------------------------------
+// Attribution to the running plugin
+// ---------------------------------------------------------------------------
+
+#[derive(Clone)]
+struct Active {
+    profiler: Arc<Profiler>,
+    plugin_id: String,
+}
+
+thread_local! {
+    static ACTIVE: RefCell<Option<Active>> = const { RefCell::new(None) };
+    static THREAD: u64 = {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    };
+}
+
+/// Attributes queries and lazy fields on this thread to a plugin until it is
+/// finished or dropped.
+pub(crate) struct PluginScope {
+    active: Active,
+    previous: Option<Active>,
+    start: Instant,
+}
+
+impl PluginScope {
+    pub(crate) fn enter(profiler: &Arc<Profiler>, plugin_id: &str) -> Self {
+        let active = Active {
+            profiler: Arc::clone(profiler),
+            plugin_id: plugin_id.to_owned(),
+        };
+        let previous = ACTIVE.with(|current| current.replace(Some(active.clone())));
+        Self {
+            active,
+            previous,
+            start: Instant::now(),
+        }
+    }
+
+    /// Record the scope as a `Run` or `File` event.
+    pub(crate) fn finish(self, kind: EventKind, name: &str, replacements: usize) {
+        self.active.profiler.push(
+            kind,
+            name.to_owned(),
+            Some(self.active.plugin_id.clone()),
+            THREAD.with(|thread| *thread),
+            self.start,
+            replacements,
+        );
+    }
+}
+
+impl Drop for PluginScope {
+    fn drop(&mut self) {
+        ACTIVE.with(|current| *current.borrow_mut() = self.previous.take());
+    }
+}
+
+/// The profiler of the plugin running on this thread, if any.
+pub(crate) fn current_profiler() -> Option<Arc<Profiler>> {
+    ACTIVE.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .map(|active| Arc::clone(&active.profiler))
+    })
+}
+
+/// Run `f`, recording it for the plugin running on this thread.
+pub(crate) fn time<R>(kind: EventKind, name: &str, f: impl FnOnce() -> R) -> R {
+    let Some(active) = ACTIVE.with(|current| current.borrow().clone()) else {
+        return f();
+    };
+    let start = Instant::now();
+    let result = f();
+    active.profiler.push(
+        kind,
+        name.to_owned(),
+        Some(active.plugin_id),
+        THREAD.with(|thread| *thread),
+        start,
+        0,
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes_nested_work_to_the_running_plugin() {
+        let profiler = Arc::new(Profiler::new());
+        time(EventKind::Query, "ignored", || ());
+
+        profiler.set_pass(2);
+        let scope = PluginScope::enter(&profiler, "demo");
+        time(EventKind::Query, "InferTypeAt", || ());
+        time(EventKind::Query, "InferTypeAt", || ());
+        time(EventKind::LazyInit, "tree", || ());
+        scope.finish(EventKind::Run, "demo", 3);
+        time(EventKind::Query, "ignored", || ());
+
+        let runs = profiler.runs_in_pass(2);
+        assert_eq!(runs.len(), 1);
+        assert_eq!((runs[0].name.as_str(), runs[0].replacements), ("demo", 3));
+        assert!(profiler.runs_in_pass(1).is_empty());
+
+        let profile = profiler.take();
+        assert_eq!(profile.events.len(), 4);
+        assert!(profile.events.iter().all(|event| event.pass == 2));
+
+        let summary = profile.summary();
+        let demo = &summary.plugins[0];
+        assert_eq!(
+            (demo.plugin_id.as_str(), demo.runs, demo.replacements),
+            ("demo", 1, 3)
+        );
+        assert_eq!((demo.queries, demo.lazy_inits), (2, 1));
+        assert_eq!(summary.queries[0].name, "InferTypeAt");
+        assert_eq!(summary.queries[0].count, 2);
+        assert_eq!(summary.lazy_fields[0].name, "tree");
+        assert!(profiler.take().events.is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::profile::{self, EventKind};
 use crate::{ImplDef, LetBinding, TextRange};
 use std::sync::Arc;
 
@@ -31,6 +32,19 @@ pub enum SemanticQuery {
     TraitImplementors { trait_path: String },
 }
 
+impl SemanticQuery {
+    /// The variant name, e.g. `"InferTypeAt"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SemanticQuery::LetBindings { .. } => "LetBindings",
+            SemanticQuery::LetBindingsInScope { .. } => "LetBindingsInScope",
+            SemanticQuery::InferTypeAt { .. } => "InferTypeAt",
+            SemanticQuery::ResolveItemAt { .. } => "ResolveItemAt",
+            SemanticQuery::TraitImplementors { .. } => "TraitImplementors",
+        }
+    }
+}
+
 /// Result of a [`SemanticQuery`].
 #[non_exhaustive]
 pub enum SemanticResult {
@@ -62,7 +76,7 @@ impl std::fmt::Debug for SemanticHandle {
 impl SemanticHandle {
     /// Issue a raw query. Prefer the typed helpers below.
     pub fn query(&self, q: SemanticQuery) -> SemanticResult {
-        (self.oracle)(q)
+        profile::time(EventKind::Query, q.kind(), || (self.oracle)(q))
     }
 
     // ── File-scoped helpers ───────────────────────────────────────────────
@@ -1,14 +1,30 @@
-use crate::{FileReplacement, Plugin, Replacement, TextRange, WorkspaceContext};
+use crate::config::{self, ConfigError};
+use crate::file_cache::{self, CacheKey, CacheStats, CachedRun, FileCache};
+use crate::parallel;
+use crate::profile::{self, EventKind, PluginScope, Profiler};
+use crate::schedule::PlannedPhase;
+use crate::{
+    Diagnostic, DirNode, FileContext, FileReplacement, Interest, LazyValue, Plugin, PluginInfo,
+    Replacement, Schedule, SeedLock, Severity, TextRange, WorkspaceContext, WorkspaceManifest,
+};
 use rand::{rngs::StdRng, SeedableRng};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{Map, Value};
-use std::collections::HashMap;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
 
 const START_MARKER_PREFIX: &str = "/*#start:";
 const END_MARKER_PREFIX: &str = "/*#end:";
 const MARKER_SUFFIX: &str = "*/";
+/// Key of the per-file states kept by [`PluginRuntime::for_each_file`] inside
+/// a plugin's [`PluginState`].
+const FILE_STATES_KEY: &str = "forgen:files";
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct PluginState {
     values: Map<String, Value>,
 }
@@ -90,6 +106,75 @@ impl PluginState {
 pub struct SuiteRuntime {
     suite_seed: u64,
     plugin_states: HashMap<String, PluginState>,
+    /// [`Plugin::state_version`] of the plugin that last wrote each state.
+    #[serde(default)]
+    state_versions: HashMap<String, u32>,
+    /// Pinned per-file seeds; `None` unless lock mode is enabled.
+    #[serde(default)]
+    seed_lock: Option<SeedLock>,
+    /// Index of the [`Schedule`] phase that has not converged yet.
+    #[serde(default)]
+    schedule_phase: usize,
+    /// Diagnostics of every plugin run since the last
+    /// [`SuiteRuntime::take_diagnostics`], keyed by plugin id.
+    #[serde(default)]
+    diagnostics: HashMap<String, Vec<Diagnostic>>,
+    /// Plugin runs that panicked since the last [`SuiteRuntime::take_panics`].
+    #[serde(default)]
+    panics: Vec<PluginPanic>,
+    /// Why the last [`SuiteRuntime::run_schedule`] ran nothing, until
+    /// [`SuiteRuntime::take_schedule_error`].
+    #[serde(default)]
+    schedule_error: Option<String>,
+    /// Problems the runtime recovered from since the last
+    /// [`SuiteRuntime::take_warnings`], such as discarded plugin state.
+    #[serde(default)]
+    warnings: Vec<String>,
+    /// Worker threads for concurrent plugins and per-file runs; `0` picks
+    /// [`parallel::default_jobs`].
+    #[serde(default)]
+    jobs: usize,
+    /// Receives timings of plugin runs, queries and lazy fields when set.
+    #[serde(skip)]
+    profiler: Option<Arc<Profiler>>,
+    #[serde(skip)]
+    filter: PluginFilter,
+    /// Plugins `filter` kept from running since the last
+    /// [`SuiteRuntime::take_skipped`].
+    #[serde(skip)]
+    skipped: BTreeSet<String>,
+    /// Per-file outputs of `run_file`; `None` disables caching.
+    #[serde(skip)]
+    file_cache: Option<Arc<FileCache>>,
+    /// Plugins seen by [`SuiteRuntime::discover_plugins`]; while `Some`,
+    /// plugins are recorded here instead of running.
+    #[serde(skip)]
+    discovered: Option<Vec<PluginInfo>>,
+}
+
+/// Which plugins a [`SuiteRuntime`] runs.
+///
+/// A plugin runs when `only` is empty or lists it, and `skip` does not list
+/// it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PluginFilter {
+    pub only: BTreeSet<String>,
+    pub skip: BTreeSet<String>,
+}
+
+impl PluginFilter {
+    pub fn allows(&self, plugin_id: &str) -> bool {
+        (self.only.is_empty() || self.only.contains(plugin_id)) && !self.skip.contains(plugin_id)
+    }
+}
+
+/// A [`Plugin::run`] call that panicked. Its output, state changes, new seed
+/// pins and diagnostics were discarded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginPanic {
+    pub plugin_id: String,
+    /// The panic message, or a placeholder for non-string payloads.
+    pub message: String,
 }
 
 impl Default for SuiteRuntime {
@@ -107,6 +192,19 @@ impl SuiteRuntime {
         Self {
             suite_seed: seed,
             plugin_states: HashMap::new(),
+            state_versions: HashMap::new(),
+            seed_lock: None,
+            schedule_phase: 0,
+            diagnostics: HashMap::new(),
+            panics: Vec::new(),
+            schedule_error: None,
+            warnings: Vec::new(),
+            jobs: 0,
+            profiler: None,
+            filter: PluginFilter::default(),
+            skipped: BTreeSet::new(),
+            file_cache: Some(Arc::default()),
+            discovered: None,
         }
     }
 
@@ -114,6 +212,85 @@ impl SuiteRuntime {
         self.suite_seed
     }
 
+    /// Limit the worker threads used for concurrent plugins and per-file
+    /// runs. `1` runs everything on the calling thread; `0` restores the
+    /// default of one thread per CPU.
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = jobs;
+    }
+
+    pub fn jobs(&self) -> usize {
+        match self.jobs {
+            0 => parallel::default_jobs(),
+            jobs => jobs,
+        }
+    }
+
+    /// Record plugin timings into `profiler`, or stop recording with `None`.
+    /// Clones of this runtime share the profiler.
+    pub fn set_profiler(&mut self, profiler: Option<Arc<Profiler>>) {
+        self.profiler = profiler;
+    }
+
+    pub fn profiler(&self) -> Option<&Arc<Profiler>> {
+        self.profiler.as_ref()
+    }
+
+    /// Only run the plugins `filter` allows. Other plugins are treated as if
+    /// they returned nothing and are reported by
+    /// [`SuiteRuntime::take_skipped`].
+    pub fn set_plugin_filter(&mut self, filter: PluginFilter) {
+        self.filter = filter;
+    }
+
+    pub fn plugin_filter(&self) -> &PluginFilter {
+        &self.filter
+    }
+
+    /// Ids of the plugins skipped by the [`PluginFilter`] since the last call.
+    pub fn take_skipped(&mut self) -> BTreeSet<String> {
+        std::mem::take(&mut self.skipped)
+    }
+
+    /// Turn the per-file output cache of [`PluginRuntime::for_each_file`] on
+    /// or off. It is on by default; turning it off drops cached results.
+    pub fn set_file_cache(&mut self, enabled: bool) {
+        match (enabled, &self.file_cache) {
+            (true, None) => self.file_cache = Some(Arc::default()),
+            (false, Some(_)) => self.file_cache = None,
+            _ => {}
+        }
+    }
+
+    /// Forget every cached per-file result, e.g. after the suite changed.
+    pub fn clear_file_cache(&self) {
+        if let Some(cache) = &self.file_cache {
+            cache.clear();
+        }
+    }
+
+    /// Cache hits and misses since the last call.
+    pub fn take_file_cache_stats(&self) -> CacheStats {
+        self.file_cache
+            .as_ref()
+            .map(|cache| cache.take_stats())
+            .unwrap_or_default()
+    }
+
+    /// Enable lock mode: seeds handed out by [`PluginRuntime::rng_for_file`]
+    /// are taken from `lock` when pinned there, and pinned into it otherwise.
+    pub fn enable_seed_lock(&mut self, lock: SeedLock) {
+        self.seed_lock = Some(lock);
+    }
+
+    pub fn seed_lock(&self) -> Option<&SeedLock> {
+        self.seed_lock.as_ref()
+    }
+
+    pub fn seed_lock_mut(&mut self) -> Option<&mut SeedLock> {
+        self.seed_lock.as_mut()
+    }
+
     pub fn plugin_state(&self, plugin_id: &str) -> Option<&PluginState> {
         self.plugin_states.get(plugin_id)
     }
@@ -122,37 +299,428 @@ impl SuiteRuntime {
         self.plugin_states.entry(plugin_id.to_owned()).or_default()
     }
 
+    /// All plugin states, with the [`Plugin::state_version`] that wrote them
+    /// (`None` if the state was never touched by a plugin run).
+    pub fn plugin_states(&self) -> impl Iterator<Item = (&str, Option<u32>, &PluginState)> {
+        self.plugin_states.iter().map(|(plugin_id, state)| {
+            (
+                plugin_id.as_str(),
+                self.state_versions.get(plugin_id).copied(),
+                state,
+            )
+        })
+    }
+
+    /// Install previously persisted state for `plugin_id`.
+    ///
+    /// If the plugin later reports a different [`Plugin::state_version`], the
+    /// restored state is discarded before it runs.
+    pub fn restore_plugin_state(&mut self, plugin_id: &str, version: u32, state: PluginState) {
+        self.plugin_states.insert(plugin_id.to_owned(), state);
+        self.state_versions.insert(plugin_id.to_owned(), version);
+    }
+
+    /// Drop the state of `plugin_id`. Returns `true` if there was any.
+    pub fn reset_plugin_state(&mut self, plugin_id: &str) -> bool {
+        self.state_versions.remove(plugin_id);
+        self.plugin_states.remove(plugin_id).is_some()
+    }
+
+    /// Drain the diagnostics reported since the last call.
+    ///
+    /// Every plugin that ran has an entry, even if it reported nothing, so the
+    /// caller can tell "no diagnostics any more" apart from "did not run".
+    /// Ranges refer to the snapshot the plugin last ran on.
+    pub fn take_diagnostics(&mut self) -> HashMap<String, Vec<Diagnostic>> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Drain the plugin panics recorded since the last call.
+    pub fn take_panics(&mut self) -> Vec<PluginPanic> {
+        std::mem::take(&mut self.panics)
+    }
+
+    /// Why [`SuiteRuntime::run_schedule`] rejected its schedule since the
+    /// last call, if it did. The CLI fails the run with it.
+    pub fn take_schedule_error(&mut self) -> Option<String> {
+        self.schedule_error.take()
+    }
+
+    /// Drain the warnings recorded since the last call. The CLI prints them,
+    /// so plugin code never has to write to stderr.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Call a suite function without running any plugin, and return the
+    /// plugins it passes to [`SuiteRuntime::run_schedule`] (every phase) and
+    /// [`SuiteRuntime::run_plugin`], in order and without duplicates.
+    ///
+    /// The suite function sees an empty workspace, so plugins it only runs
+    /// for some workspaces are missing from the list.
+    pub fn discover_plugins<F>(run: F) -> Vec<PluginInfo>
+    where
+        F: FnOnce(&WorkspaceContext, &mut SuiteRuntime) -> Vec<FileReplacement>,
+    {
+        let ctx = WorkspaceContext::new(
+            String::new(),
+            Vec::new(),
+            WorkspaceManifest {
+                members: Vec::new(),
+                workspace_root: String::new(),
+                target_directory: String::new(),
+                metadata: Value::Null,
+            },
+            LazyValue::from_value(DirNode {
+                name: String::new(),
+                path: String::new(),
+                entries: Vec::new(),
+            }),
+            None,
+        );
+        let mut runtime = SuiteRuntime::with_seed(0);
+        runtime.discovered = Some(Vec::new());
+        run(&ctx, &mut runtime);
+        runtime.discovered.unwrap_or_default()
+    }
+
+    /// Record `info` if discovery is on; returns whether it is.
+    fn discover(&mut self, info: impl FnOnce() -> PluginInfo) -> bool {
+        let Some(discovered) = &mut self.discovered else {
+            return false;
+        };
+        let info = info();
+        if !discovered.iter().any(|known| known.id == info.id) {
+            discovered.push(info);
+        }
+        true
+    }
+
+    /// Mark the start of a new fixed-point run. Resets [`Schedule`] progress
+    /// so the next [`SuiteRuntime::run_schedule`] starts at the first phase.
+    ///
+    /// The CLI calls this before the first pass of every run.
+    pub fn begin_run(&mut self) {
+        self.schedule_phase = 0;
+        if let Some(cache) = &self.file_cache {
+            cache.start_run();
+        }
+    }
+
+    /// Run one pass of `schedule`.
+    ///
+    /// Only the earliest phase that has not converged runs. When every plugin
+    /// of that phase returns no replacements, the phase is marked converged
+    /// and the next phase runs immediately on the same snapshot. Returns an
+    /// empty `Vec` once all phases have converged.
+    ///
+    /// An invalid schedule (unknown plugins or phases, cycles, …) runs no
+    /// plugins and is recorded for [`SuiteRuntime::take_schedule_error`].
+    pub fn run_schedule(
+        &mut self,
+        schedule: &Schedule<'_>,
+        ctx: &WorkspaceContext,
+    ) -> Vec<FileReplacement> {
+        if self.discovered.is_some() {
+            for (registration, phase) in schedule.registrations() {
+                self.discover(|| PluginInfo::of(registration.plugin, Some(phase)));
+            }
+            return Vec::new();
+        }
+
+        let plan = match schedule.plan() {
+            Ok(plan) => plan,
+            Err(err) => {
+                self.schedule_error = Some(err.to_string());
+                return Vec::new();
+            }
+        };
+
+        while let Some(phase) = plan.phases.get(self.schedule_phase) {
+            let out = self.run_phase(phase, ctx);
+            if !out.is_empty() {
+                return out;
+            }
+            self.schedule_phase += 1;
+        }
+
+        Vec::new()
+    }
+
+    /// Run one phase. Plugins of a phase all see the same snapshot, so they
+    /// run concurrently, except that an `after_converged` plugin waits for
+    /// the plugins it depends on. Output is concatenated in plan order.
+    fn run_phase(
+        &mut self,
+        phase: &PlannedPhase<'_, '_>,
+        ctx: &WorkspaceContext,
+    ) -> Vec<FileReplacement> {
+        // Wave of every registration: one after the latest wave of its
+        // `after_converged` dependencies in this phase.
+        let mut waves: Vec<usize> = Vec::with_capacity(phase.registrations.len());
+        for (index, registration) in phase.registrations.iter().enumerate() {
+            let wave = phase.registrations[..index]
+                .iter()
+                .zip(&waves)
+                .filter(|(earlier, _)| {
+                    registration
+                        .after_converged
+                        .iter()
+                        .any(|dependency| dependency == earlier.plugin.name())
+                })
+                .map(|(_, wave)| wave + 1)
+                .max()
+                .unwrap_or(0);
+            waves.push(wave);
+        }
+
+        let mut outputs: Vec<Vec<FileReplacement>> = vec![Vec::new(); phase.registrations.len()];
+        // Plugins that produced output or were held back in this pass.
+        let mut unsettled: HashSet<&str> = HashSet::new();
+
+        for wave in 0..=waves.iter().copied().max().unwrap_or(0) {
+            let mut prepared = Vec::new();
+            for (index, registration) in phase.registrations.iter().enumerate() {
+                if waves[index] != wave {
+                    continue;
+                }
+                if registration
+                    .after_converged
+                    .iter()
+                    .any(|dependency| unsettled.contains(dependency.as_str()))
+                {
+                    unsettled.insert(registration.plugin.name());
+                    continue;
+                }
+                if let Some(run) = self.prepare_run(registration.plugin) {
+                    prepared.push((index, run));
+                }
+            }
+
+            let this = &*self;
+            let outcomes = parallel::map_ordered(this.jobs(), prepared, |(index, run)| {
+                let plugin = phase.registrations[index].plugin;
+                (index, this.execute_run(plugin, ctx, run))
+            });
+
+            for (index, outcome) in outcomes {
+                let replacements = self.finish_run(outcome);
+                if replacements.iter().any(|fr| !fr.is_empty()) {
+                    unsettled.insert(phase.registrations[index].plugin.name());
+                    outputs[index] = replacements;
+                }
+            }
+        }
+
+        outputs.into_iter().flatten().collect()
+    }
+
+    /// Run `plugin` with its state, seeds and configuration and wrap its
+    /// output in generated-region markers.
+    ///
+    /// A panicking plugin contributes nothing: its output and state changes
+    /// are discarded and the panic is recorded for
+    /// [`SuiteRuntime::take_panics`], so the remaining plugins keep running.
     pub fn run_plugin<P>(&mut self, plugin: &P, ctx: &WorkspaceContext) -> Vec<FileReplacement>
     where
-        P: Plugin,
+        P: Plugin + ?Sized,
+    {
+        if self.discover(|| PluginInfo::of(plugin, None)) {
+            return Vec::new();
+        }
+        let Some(prepared) = self.prepare_run(plugin) else {
+            return Vec::new();
+        };
+        let outcome = self.execute_run(plugin, ctx, prepared);
+        self.finish_run(outcome)
+    }
+
+    /// Validate the plugin id, apply [`Plugin::state_version`] changes and
+    /// take the plugin's state out of the runtime for [`Self::execute_run`].
+    fn prepare_run<P>(&mut self, plugin: &P) -> Option<PreparedRun>
+    where
+        P: Plugin + ?Sized,
     {
         let plugin_id = plugin.name().to_owned();
         if !is_valid_plugin_id(&plugin_id) {
-            eprintln!(
-                "[forgen] plugin id `{plugin_id}` is invalid; Plugin::name() must contain only ASCII letters, digits, '_' or '-'. Skipping plugin output.",
-            );
-            return Vec::new();
+            self.warnings.push(format!(
+                "plugin id `{plugin_id}` is invalid; Plugin::name() must contain only ASCII letters, digits, '_' or '-'. Skipping plugin output."
+            ));
+            return None;
+        }
+        if !self.filter.allows(&plugin_id) {
+            self.skipped.insert(plugin_id);
+            return None;
         }
 
-        let raw = {
-            let state = self.plugin_states.entry(plugin_id.clone()).or_default();
-            let mut runtime = PluginRuntime {
-                plugin_id: &plugin_id,
-                suite_seed: self.suite_seed,
-                state,
-            };
-            plugin.run(ctx, &mut runtime)
+        let version = plugin.state_version();
+        if let Some(previous) = self.state_versions.insert(plugin_id.clone(), version) {
+            if previous != version {
+                self.warnings.push(format!(
+                    "plugin `{plugin_id}` state version changed from {previous} to {version}; discarding its stored state"
+                ));
+                self.plugin_states.remove(&plugin_id);
+            }
+        }
+
+        let state = self.plugin_states.remove(&plugin_id).unwrap_or_default();
+        Some(PreparedRun { plugin_id, state })
+    }
+
+    /// Call [`Plugin::run`]. Only reads `self`, so several plugins can execute
+    /// on different threads.
+    fn execute_run<P>(
+        &self,
+        plugin: &P,
+        ctx: &WorkspaceContext,
+        prepared: PreparedRun,
+    ) -> RunOutcome
+    where
+        P: Plugin + ?Sized,
+    {
+        let PreparedRun {
+            plugin_id,
+            mut state,
+        } = prepared;
+        let state_before = state.clone();
+        let interest = plugin.interest();
+        let file_cache = match (&self.file_cache, interest.cached) {
+            (Some(cache), true) => {
+                let salt = file_cache::run_salt(
+                    &plugin_id,
+                    plugin.state_version(),
+                    self.suite_seed,
+                    &ctx.manifest,
+                );
+                Some((&**cache, salt))
+            }
+            _ => None,
         };
+        let mut runtime = PluginRuntime {
+            plugin_id: &plugin_id,
+            suite_seed: self.suite_seed,
+            state: &mut state,
+            manifest: &ctx.manifest,
+            seed_lock: self.seed_lock.as_ref(),
+            new_pins: RefCell::new(Vec::new()),
+            diagnostics: Vec::new(),
+            jobs: self.jobs(),
+            interest,
+            file_cache,
+        };
+
+        // A panic must not unwind through `forgen_run` (which would abort the
+        // CLI) or take the other plugins down with it. The state is rolled
+        // back so a half-finished run does not leak into the next pass.
+        let scope = self
+            .profiler
+            .as_ref()
+            .map(|profiler| PluginScope::enter(profiler, &plugin_id));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| plugin.run(ctx, &mut runtime)));
+        if let Some(scope) = scope {
+            let replacements = result.as_ref().map_or(0, |replacements| {
+                replacements.iter().map(|fr| fr.replacements.len()).sum()
+            });
+            scope.finish(EventKind::Run, &plugin_id, replacements);
+        }
+        match result {
+            Ok(replacements) => {
+                let output = RunOutput {
+                    replacements,
+                    new_pins: runtime.new_pins.into_inner(),
+                    diagnostics: runtime.diagnostics,
+                };
+                RunOutcome {
+                    plugin_id,
+                    state,
+                    result: Ok(output),
+                }
+            }
+            Err(payload) => RunOutcome {
+                plugin_id,
+                state: state_before,
+                result: Err(panic_message(payload.as_ref())),
+            },
+        }
+    }
+
+    /// Store what [`Self::execute_run`] produced and return the wrapped
+    /// replacements.
+    fn finish_run(&mut self, outcome: RunOutcome) -> Vec<FileReplacement> {
+        let RunOutcome {
+            plugin_id,
+            state,
+            result,
+        } = outcome;
+        self.plugin_states.insert(plugin_id.clone(), state);
+
+        let RunOutput {
+            replacements,
+            new_pins,
+            diagnostics,
+        } = match result {
+            Ok(output) => output,
+            // The CLI reports it from `take_panics`.
+            Err(message) => {
+                self.panics.push(PluginPanic {
+                    plugin_id: plugin_id.clone(),
+                    message,
+                });
+                RunOutput {
+                    replacements: Vec::new(),
+                    new_pins: Vec::new(),
+                    diagnostics: Vec::new(),
+                }
+            }
+        };
+
+        self.diagnostics.insert(plugin_id.clone(), diagnostics);
+
+        if let Some(lock) = &mut self.seed_lock {
+            for (file_path, seed) in new_pins {
+                lock.pin(&plugin_id, &file_path, seed);
+            }
+        }
 
-        wrap_file_replacements(&plugin_id, raw)
+        wrap_file_replacements(&plugin_id, replacements)
     }
 }
 
+/// A plugin run whose state has been taken out of the [`SuiteRuntime`].
+struct PreparedRun {
+    plugin_id: String,
+    state: PluginState,
+}
+
+/// The result of [`SuiteRuntime::execute_run`]: the state to put back, and
+/// either the plugin's output or its panic message.
+struct RunOutcome {
+    plugin_id: String,
+    state: PluginState,
+    result: Result<RunOutput, String>,
+}
+
+struct RunOutput {
+    replacements: Vec<FileReplacement>,
+    new_pins: Vec<(String, u64)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
 #[derive(Debug)]
 pub struct PluginRuntime<'a> {
     plugin_id: &'a str,
     suite_seed: u64,
     state: &'a mut PluginState,
+    manifest: &'a WorkspaceManifest,
+    seed_lock: Option<&'a SeedLock>,
+    /// Seeds derived during this run that are not pinned in `seed_lock` yet.
+    new_pins: RefCell<Vec<(String, u64)>>,
+    diagnostics: Vec<Diagnostic>,
+    jobs: usize,
+    /// [`Plugin::interest`] of the running plugin, used by `for_each_file`.
+    interest: Interest,
+    /// Cache for `for_each_file` and the hash of this run's shared inputs.
+    file_cache: Option<(&'a FileCache, u64)>,
 }
 
 impl<'a> PluginRuntime<'a> {
@@ -164,8 +732,202 @@ impl<'a> PluginRuntime<'a> {
         self.state
     }
 
+    /// Report a warning about `range` in `file` (a workspace-relative path).
+    ///
+    /// Warnings are printed by the CLI and surface as compiler warnings when
+    /// `forgen::file` expands the file.
+    pub fn warn(&mut self, file: &str, range: TextRange, message: impl Into<String>) {
+        self.report(Severity::Warning, file, range, message.into());
+    }
+
+    /// Report an error about `range` in `file` (a workspace-relative path).
+    ///
+    /// Errors make `cargo forgen` exit with a non-zero status and become
+    /// compile errors when `forgen::file` expands the file.
+    pub fn error(&mut self, file: &str, range: TextRange, message: impl Into<String>) {
+        self.report(Severity::Error, file, range, message.into());
+    }
+
+    fn report(&mut self, severity: Severity, file: &str, range: TextRange, message: String) {
+        self.diagnostics.push(Diagnostic {
+            plugin_id: self.plugin_id.to_owned(),
+            severity,
+            file: file.to_owned(),
+            range,
+            message,
+        });
+    }
+
+    /// Deserialize this plugin's `[workspace.metadata.forgen.plugins.<id>]`
+    /// table.
+    ///
+    /// A missing table deserializes from an empty one, so a config type whose
+    /// fields all have `#[serde(default)]` needs no configuration at all. The
+    /// error names the plugin, the table and the offending key.
+    pub fn config<T>(&self) -> Result<T, ConfigError>
+    where
+        T: DeserializeOwned,
+    {
+        config::load(self.manifest, self.plugin_id, None)
+    }
+
+    /// Like [`PluginRuntime::config`], with the
+    /// `[package.metadata.forgen.plugins.<id>]` table of the package that owns
+    /// `file_path` merged over the workspace table.
+    pub fn config_for_file<T>(&self, file_path: &str) -> Result<T, ConfigError>
+    where
+        T: DeserializeOwned,
+    {
+        config::load(self.manifest, self.plugin_id, Some(file_path))
+    }
+
+    /// Run `run_file` for every file in `ctx` on the suite's worker threads
+    /// and collect the output in file order. This is what the default
+    /// [`Plugin::run`] does with [`Plugin::run_file`].
+    ///
+    /// Each call gets its own runtime whose [`PluginRuntime::state`] is the
+    /// plugin's state for that file only, so the result never depends on
+    /// which thread handled which file. Per-file states are stored inside the
+    /// plugin's state and persisted with it. `rng_for_file`, seed locks,
+    /// configuration and diagnostics work as usual.
+    ///
+    /// Files outside the plugin's [`Plugin::interest`] are skipped. When the
+    /// interest is `cached`, a file whose source, per-file state, pinned
+    /// seed, suite seed and manifest match an earlier call reuses that call's
+    /// output instead of running `run_file` again.
+    pub fn for_each_file<F>(&mut self, ctx: &WorkspaceContext, run_file: F) -> Vec<FileReplacement>
+    where
+        F: Fn(&FileContext, &mut PluginRuntime<'_>) -> Vec<Replacement> + Sync,
+    {
+        let mut file_states = match self.state.remove_value(FILE_STATES_KEY) {
+            Some(Value::Object(states)) => states,
+            _ => Map::new(),
+        };
+        let work: Vec<(&FileContext, PluginState)> = ctx
+            .files
+            .iter()
+            .map(|file| {
+                let state = match file_states.remove(&file.path) {
+                    Some(Value::Object(values)) => PluginState { values },
+                    _ => PluginState::new(),
+                };
+                (file, state)
+            })
+            .collect();
+
+        let (plugin_id, suite_seed, manifest, seed_lock, interest, file_cache) = (
+            self.plugin_id,
+            self.suite_seed,
+            self.manifest,
+            self.seed_lock,
+            &self.interest,
+            self.file_cache,
+        );
+        let profiler = profile::current_profiler();
+        let results = parallel::map_ordered(self.jobs, work, |(file, mut state)| {
+            if !interest.matches(file) {
+                let skipped = CachedRun {
+                    replacements: Vec::new(),
+                    state,
+                    new_pins: Vec::new(),
+                    diagnostics: Vec::new(),
+                };
+                return (file, skipped);
+            }
+
+            let key = file_cache.map(|(cache, salt)| {
+                let pinned_seed = seed_lock.and_then(|lock| lock.get(plugin_id, &file.path));
+                let key = CacheKey::new(
+                    plugin_id,
+                    &file.path,
+                    salt,
+                    file.source(),
+                    &state,
+                    pinned_seed,
+                );
+                (cache, key)
+            });
+            if let Some(cached) = key.as_ref().and_then(|(cache, key)| cache.get(key)) {
+                return (file, cached);
+            }
+
+            let scope = profiler
+                .as_ref()
+                .map(|profiler| PluginScope::enter(profiler, plugin_id));
+            let mut runtime = PluginRuntime {
+                plugin_id,
+                suite_seed,
+                state: &mut state,
+                manifest,
+                seed_lock,
+                new_pins: RefCell::new(Vec::new()),
+                diagnostics: Vec::new(),
+                jobs: 1,
+                interest: Interest::default(),
+                file_cache: None,
+            };
+            let replacements = run_file(file, &mut runtime);
+            if let Some(scope) = scope {
+                scope.finish(EventKind::File, &file.path, replacements.len());
+            }
+            let output = CachedRun {
+                replacements,
+                new_pins: runtime.new_pins.into_inner(),
+                diagnostics: runtime.diagnostics,
+                state,
+            };
+            if let Some((cache, key)) = key {
+                cache.insert(key, output.clone());
+            }
+            (file, output)
+        });
+
+        let mut out = Vec::new();
+        for (file, output) in results {
+            if !output.state.is_empty() {
+                file_states.insert(file.path.clone(), Value::Object(output.state.values));
+            }
+            self.new_pins.get_mut().extend(output.new_pins);
+            self.diagnostics.extend(output.diagnostics);
+            if !output.replacements.is_empty() {
+                out.push(FileReplacement::new(file.path.clone(), output.replacements));
+            }
+        }
+        if !file_states.is_empty() {
+            self.state
+                .set_value(FILE_STATES_KEY, Value::Object(file_states));
+        }
+
+        out
+    }
+
+    /// Deterministic RNG for `file_path`.
+    ///
+    /// The seed is derived from the suite seed, plugin id and file path. When
+    /// the suite runs with a [`SeedLock`], a seed pinned for this plugin and
+    /// file takes precedence, and newly derived seeds are pinned after the
+    /// plugin returns.
     pub fn rng_for_file(&self, file_path: &str) -> StdRng {
-        StdRng::seed_from_u64(derive_file_seed(self.suite_seed, self.plugin_id, file_path))
+        StdRng::seed_from_u64(self.file_seed(file_path))
+    }
+
+    fn file_seed(&self, file_path: &str) -> u64 {
+        let Some(lock) = self.seed_lock else {
+            return derive_file_seed(self.suite_seed, self.plugin_id, file_path);
+        };
+
+        if let Some(seed) = lock.get(self.plugin_id, file_path) {
+            return seed;
+        }
+
+        let mut new_pins = self.new_pins.borrow_mut();
+        if let Some((_, seed)) = new_pins.iter().find(|(path, _)| path == file_path) {
+            return *seed;
+        }
+
+        let seed = derive_file_seed(self.suite_seed, self.plugin_id, file_path);
+        new_pins.push((file_path.to_owned(), seed));
+        seed
     }
 }
 
@@ -179,6 +941,17 @@ pub struct GeneratedRegion {
     pub end_marker_range: TextRange,
 }
 
+/// Best-effort text of a panic payload (`panic!` produces `&str` or `String`).
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "<non-string panic payload>".to_owned()
+    }
+}
+
 pub fn is_valid_plugin_id(plugin_id: &str) -> bool {
     !plugin_id.is_empty()
         && plugin_id
@@ -276,8 +1049,13 @@ fn derive_file_seed(suite_seed: u64, plugin_id: &str, file_path: &str) -> u64 {
     fnv1a64(&bytes)
 }
 
+pub(crate) const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
 const fn fnv1a64(bytes: &[u8]) -> u64 {
-    let mut hash: u64 = 0xcbf29ce484222325;
+    fnv1a64_extend(FNV_OFFSET, bytes)
+}
+
+pub(crate) const fn fnv1a64_extend(mut hash: u64, bytes: &[u8]) -> u64 {
     let mut index = 0usize;
 
     while index < bytes.len() {
@@ -334,7 +1112,7 @@ fn parse_marker_at(source: &str, start: usize) -> Option<ParsedMarker> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{DirNode, LazyValue, WorkspaceManifest};
+    use crate::{DirNode, FileContext, LazyValue, Registration, WorkspaceManifest};
 
     struct CountingPlugin;
 
@@ -434,4 +1212,489 @@ mod tests {
         let state = runtime.plugin_state("counting-plugin").unwrap();
         assert_eq!(state.get::<u32>("count"), Some(2));
     }
+
+    #[test]
+    fn restored_state_is_discarded_on_version_mismatch() {
+        let ctx = empty_workspace();
+        let mut persisted = PluginState::new();
+        persisted.set("count", 40u32).unwrap();
+
+        let mut runtime = SuiteRuntime::with_seed(7);
+        runtime.restore_plugin_state("counting-plugin", 0, persisted.clone());
+        runtime.run_plugin(&CountingPlugin, &ctx);
+        let state = runtime.plugin_state("counting-plugin").unwrap();
+        assert_eq!(state.get::<u32>("count"), Some(41));
+        assert!(runtime.take_warnings().is_empty());
+
+        let mut runtime = SuiteRuntime::with_seed(7);
+        runtime.restore_plugin_state("counting-plugin", 3, persisted);
+        runtime.run_plugin(&CountingPlugin, &ctx);
+        let state = runtime.plugin_state("counting-plugin").unwrap();
+        assert_eq!(state.get::<u32>("count"), Some(1));
+        assert_eq!(
+            runtime.take_warnings(),
+            ["plugin `counting-plugin` state version changed from 3 to 0; discarding its stored state"]
+        );
+        assert!(runtime.take_warnings().is_empty());
+    }
+
+    struct RollingPlugin;
+
+    impl Plugin for RollingPlugin {
+        fn name(&self) -> &str {
+            "rolling-plugin"
+        }
+
+        fn run(
+            &self,
+            _ctx: &WorkspaceContext,
+            runtime: &mut PluginRuntime<'_>,
+        ) -> Vec<FileReplacement> {
+            use rand::Rng;
+
+            let roll: u64 = runtime.rng_for_file("src/lib.rs").gen();
+            runtime.state().set("roll", roll).unwrap();
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn seed_lock_pins_values_across_suite_seeds() {
+        let ctx = empty_workspace();
+
+        let mut first = SuiteRuntime::with_seed(1);
+        first.enable_seed_lock(SeedLock::new());
+        first.run_plugin(&RollingPlugin, &ctx);
+        let lock = first.seed_lock().unwrap().clone();
+        assert!(lock.get("rolling-plugin", "src/lib.rs").is_some());
+
+        let mut second = SuiteRuntime::with_seed(2);
+        second.enable_seed_lock(lock);
+        second.run_plugin(&RollingPlugin, &ctx);
+
+        let roll = |runtime: &SuiteRuntime| {
+            runtime
+                .plugin_state("rolling-plugin")
+                .unwrap()
+                .get::<u64>("roll")
+        };
+        assert_eq!(roll(&first), roll(&second));
+    }
+
+    #[test]
+    fn new_pins_follow_a_file_moved_after_the_first_run() {
+        let source = |path: &str| format!("#![forgen::file(\"{path}\")]\nfn a() {{}}\n");
+        let mut lock = SeedLock::new();
+        lock.sync_files(&HashMap::from([(
+            "src/lib.rs".to_owned(),
+            source("src/lib.rs"),
+        )]));
+        let mut runtime = SuiteRuntime::with_seed(1);
+        runtime.enable_seed_lock(lock);
+        runtime.run_plugin(&RollingPlugin, &empty_workspace());
+        let pinned = runtime
+            .seed_lock()
+            .unwrap()
+            .get("rolling-plugin", "src/lib.rs");
+        assert!(pinned.is_some());
+
+        let saved = serde_json::to_string(runtime.seed_lock().unwrap()).unwrap();
+        let mut lock: SeedLock = serde_json::from_str(&saved).unwrap();
+        let moved = lock.sync_files(&HashMap::from([(
+            "src/main.rs".to_owned(),
+            source("src/main.rs"),
+        )]));
+        assert_eq!(
+            moved,
+            vec![("src/lib.rs".to_owned(), "src/main.rs".to_owned())]
+        );
+        assert_eq!(lock.get("rolling-plugin", "src/main.rs"), pinned);
+    }
+
+    /// Inserts a marker comment until it sees one, then reports convergence.
+    struct MarkerPlugin(&'static str);
+
+    impl Plugin for MarkerPlugin {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn run(
+            &self,
+            ctx: &WorkspaceContext,
+            runtime: &mut PluginRuntime<'_>,
+        ) -> Vec<FileReplacement> {
+            let runs = runtime.state().get::<u32>("runs").unwrap_or(0) + 1;
+            runtime.state().set("runs", runs).unwrap();
+            if ctx.workspace_root.contains(self.0) {
+                return Vec::new();
+            }
+            vec![FileReplacement::new(
+                "src/lib.rs",
+                vec![Replacement::insert(0, format!("/* {} */", self.0))],
+            )]
+        }
+    }
+
+    #[test]
+    fn schedule_runs_phases_to_their_own_fixed_point() {
+        let (expand, lint) = (MarkerPlugin("expand"), MarkerPlugin("lint"));
+        let schedule = Schedule::new()
+            .phases(["expand", "lint"])
+            .register(Registration::new(&lint).phase("lint"))
+            .register(Registration::new(&expand).phase("expand"));
+
+        let mut runtime = SuiteRuntime::with_seed(7);
+        runtime.begin_run();
+        let mut ctx = empty_workspace();
+
+        let first = runtime.run_schedule(&schedule, &ctx);
+        assert!(first[0].replacements[0].text.contains("/* expand */"));
+        assert!(runtime.plugin_state("lint").is_none());
+
+        // Simulate the CLI applying the output: `expand` converges and `lint`
+        // runs in the same pass.
+        ctx.workspace_root.push_str("/expand");
+        let second = runtime.run_schedule(&schedule, &ctx);
+        assert!(second[0].replacements[0].text.contains("/* lint */"));
+
+        ctx.workspace_root.push_str("/lint");
+        assert!(runtime.run_schedule(&schedule, &ctx).is_empty());
+        let runs = |id| runtime.plugin_state(id).unwrap().get::<u32>("runs");
+        assert_eq!((runs("expand"), runs("lint")), (Some(2), Some(2)));
+    }
+
+    #[test]
+    fn invalid_schedules_are_recorded_instead_of_run() {
+        let (expand, lint) = (MarkerPlugin("expand"), MarkerPlugin("lint"));
+        let schedule = Schedule::new()
+            .register(Registration::new(&expand).after("lint"))
+            .register(Registration::new(&lint).after("expand"));
+
+        let mut runtime = SuiteRuntime::with_seed(7);
+        runtime.begin_run();
+        assert!(runtime
+            .run_schedule(&schedule, &empty_workspace())
+            .is_empty());
+        assert!(runtime.plugin_state("expand").is_none());
+        let error = runtime.take_schedule_error().unwrap();
+        assert!(error.contains("expand"), "{error}");
+        assert_eq!(runtime.take_schedule_error(), None);
+    }
+
+    #[test]
+    fn discovery_lists_plugins_without_running_them() {
+        let (expand, lint) = (MarkerPlugin("expand"), MarkerPlugin("lint"));
+        let plugins = SuiteRuntime::discover_plugins(|ctx, runtime| {
+            let schedule = Schedule::new()
+                .phases(["expand", "lint"])
+                .register(Registration::new(&expand))
+                .register(Registration::new(&lint).phase("lint"));
+            let mut out = runtime.run_schedule(&schedule, ctx);
+            out.extend(runtime.run_plugin(&lint, ctx));
+            out.extend(runtime.run_plugin(&CountingPlugin, ctx));
+            assert!(out.is_empty());
+            assert!(runtime.plugin_states().next().is_none());
+            out
+        });
+
+        let listed: Vec<_> = plugins
+            .iter()
+            .map(|plugin| (plugin.id.as_str(), plugin.phase.as_deref()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("expand", Some("expand")),
+                ("lint", Some("lint")),
+                ("counting-plugin", None)
+            ]
+        );
+    }
+
+    /// Warns once, then goes quiet.
+    struct WarningPlugin;
+
+    impl Plugin for WarningPlugin {
+        fn name(&self) -> &str {
+            "warning-plugin"
+        }
+
+        fn run(
+            &self,
+            _ctx: &WorkspaceContext,
+            runtime: &mut PluginRuntime<'_>,
+        ) -> Vec<FileReplacement> {
+            if !runtime.state().contains("warned") {
+                runtime.state().set("warned", true).unwrap();
+                runtime.warn("src/lib.rs", TextRange::new(3, 7), "suspicious");
+                runtime.error("src/lib.rs", TextRange::new(8, 9), "broken");
+            }
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn diagnostics_are_replaced_on_every_run() {
+        let ctx = empty_workspace();
+        let mut runtime = SuiteRuntime::with_seed(7);
+
+        runtime.run_plugin(&WarningPlugin, &ctx);
+        let reported = runtime.take_diagnostics();
+        let diagnostics = &reported["warning-plugin"];
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].range, TextRange::new(3, 7));
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+        assert_eq!(diagnostics[1].message, "broken");
+        assert!(runtime.take_diagnostics().is_empty());
+
+        runtime.run_plugin(&WarningPlugin, &ctx);
+        let reported = runtime.take_diagnostics();
+        assert_eq!(reported.get("warning-plugin").map(Vec::len), Some(0));
+    }
+
+    struct PanickingPlugin;
+
+    impl Plugin for PanickingPlugin {
+        fn name(&self) -> &str {
+            "panicking-plugin"
+        }
+
+        fn run(
+            &self,
+            _ctx: &WorkspaceContext,
+            runtime: &mut PluginRuntime<'_>,
+        ) -> Vec<FileReplacement> {
+            runtime.state().set("half-done", true).unwrap();
+            runtime.warn("src/lib.rs", TextRange::new(0, 1), "never reported");
+            panic!("boom in {}", runtime.plugin_id());
+        }
+    }
+
+    #[test]
+    fn panicking_plugins_are_isolated() {
+        let ctx = empty_workspace();
+        let mut runtime = SuiteRuntime::with_seed(7);
+
+        assert!(runtime.run_plugin(&PanickingPlugin, &ctx).is_empty());
+        assert!(runtime.run_plugin(&CountingPlugin, &ctx).is_empty());
+
+        assert_eq!(
+            runtime.take_panics(),
+            vec![PluginPanic {
+                plugin_id: "panicking-plugin".to_owned(),
+                message: "boom in panicking-plugin".to_owned(),
+            }]
+        );
+        assert!(!runtime
+            .plugin_state("panicking-plugin")
+            .unwrap()
+            .contains("half-done"));
+        assert_eq!(
+            runtime
+                .take_diagnostics()
+                .get("panicking-plugin")
+                .map(Vec::len),
+            Some(0)
+        );
+        let counted = runtime.plugin_state("counting-plugin").unwrap();
+        assert_eq!(counted.get::<u32>("count"), Some(1));
+    }
+
+    /// Numbers every file with a random value once and keeps it in the
+    /// per-file state.
+    struct PerFilePlugin;
+
+    impl Plugin for PerFilePlugin {
+        fn name(&self) -> &str {
+            "per-file"
+        }
+
+        fn run_file(
+            &self,
+            file: &FileContext,
+            _ctx: &WorkspaceContext,
+            runtime: &mut PluginRuntime<'_>,
+        ) -> Vec<Replacement> {
+            use rand::Rng;
+
+            let value = match runtime.state().get::<u32>("value") {
+                Some(value) => value,
+                None => {
+                    let value = runtime.rng_for_file(&file.path).gen_range(0..1000);
+                    runtime.state().set("value", value).unwrap();
+                    value
+                }
+            };
+            vec![Replacement::insert(0, format!("/* {value} */"))]
+        }
+    }
+
+    fn workspace_with_files(count: usize) -> WorkspaceContext {
+        let mut ctx = empty_workspace();
+        ctx.files = (0..count)
+            .map(|index| {
+                FileContext::new(
+                    format!("src/file_{index}.rs"),
+                    LazyValue::from_value(String::new()),
+                    LazyValue::from_value(Vec::new()),
+                    LazyValue::new(|| unreachable!("tree is not used")),
+                    LazyValue::from_value(Vec::new()),
+                    LazyValue::from_value(Vec::new()),
+                    LazyValue::from_value(Vec::new()),
+                    LazyValue::from_value(Vec::new()),
+                    LazyValue::from_value(Vec::new()),
+                    None,
+                )
+            })
+            .collect();
+        ctx
+    }
+
+    #[test]
+    fn filtered_plugins_are_skipped_and_reported() {
+        let ctx = empty_workspace();
+        let mut runtime = SuiteRuntime::with_seed(7);
+        runtime.set_plugin_filter(PluginFilter {
+            skip: BTreeSet::from(["counting-plugin".to_owned()]),
+            ..PluginFilter::default()
+        });
+
+        runtime.run_plugin(&CountingPlugin, &ctx);
+        runtime.run_plugin(&CountingPlugin, &ctx);
+        assert!(runtime.plugin_state("counting-plugin").is_none());
+        assert_eq!(
+            runtime.take_skipped(),
+            BTreeSet::from(["counting-plugin".to_owned()])
+        );
+
+        runtime.set_plugin_filter(PluginFilter {
+            only: BTreeSet::from(["counting-plugin".to_owned()]),
+            ..PluginFilter::default()
+        });
+        runtime.run_plugin(&CountingPlugin, &ctx);
+        assert!(runtime.take_skipped().is_empty());
+        let state = runtime.plugin_state("counting-plugin").unwrap();
+        assert_eq!(state.get::<u32>("count"), Some(1));
+    }
+
+    #[test]
+    fn per_file_runs_are_deterministic_across_thread_counts() {
+        let ctx = workspace_with_files(40);
+        let run = |jobs| {
+            let mut runtime = SuiteRuntime::with_seed(11);
+            runtime.set_jobs(jobs);
+            let out: Vec<(String, String)> = runtime
+                .run_plugin(&PerFilePlugin, &ctx)
+                .into_iter()
+                .map(|fr| (fr.path, fr.replacements[0].text.clone()))
+                .collect();
+            let state = runtime.plugin_state("per-file").unwrap().clone();
+            (out, serde_json::to_value(state).unwrap())
+        };
+
+        let (sequential, state) = run(1);
+        assert_eq!(sequential.len(), 40);
+        assert_eq!(sequential[3].0, "src/file_3.rs");
+        assert_eq!(state[FILE_STATES_KEY].as_object().unwrap().len(), 40);
+        assert_eq!(run(8), (sequential, state));
+    }
+
+    struct InterestedPlugin {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    impl Plugin for InterestedPlugin {
+        fn name(&self) -> &str {
+            "interested"
+        }
+
+        fn interest(&self) -> Interest {
+            Interest::paths(["src/file_1*.rs"]).cached()
+        }
+
+        fn run_file(
+            &self,
+            file: &FileContext,
+            _ctx: &WorkspaceContext,
+            runtime: &mut PluginRuntime<'_>,
+        ) -> Vec<Replacement> {
+            self.calls
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            runtime.warn(&file.path, TextRange::new(0, 0), "seen");
+            vec![Replacement::insert(0, "/* seen */".to_owned())]
+        }
+    }
+
+    /// Reads `ctx`, so it keeps the default uncached interest.
+    struct FileCountPlugin;
+
+    impl Plugin for FileCountPlugin {
+        fn name(&self) -> &str {
+            "file-count"
+        }
+
+        fn run_file(
+            &self,
+            _file: &FileContext,
+            ctx: &WorkspaceContext,
+            _runtime: &mut PluginRuntime<'_>,
+        ) -> Vec<Replacement> {
+            vec![Replacement::insert(
+                0,
+                format!("/* {} files */", ctx.files.len()),
+            )]
+        }
+    }
+
+    #[test]
+    fn uncached_plugins_rerun_when_another_file_changes() {
+        let mut runtime = SuiteRuntime::with_seed(3);
+        let first_file = |ctx: &WorkspaceContext, runtime: &mut SuiteRuntime| {
+            runtime.begin_run();
+            let output = runtime.run_plugin(&FileCountPlugin, ctx);
+            assert_eq!(output[0].path, "src/file_0.rs");
+            output[0].replacements[0].text.clone()
+        };
+
+        assert!(first_file(&workspace_with_files(2), &mut runtime).contains("/* 2 files */"));
+        // `src/file_0.rs` itself is unchanged.
+        assert!(first_file(&workspace_with_files(3), &mut runtime).contains("/* 3 files */"));
+        assert_eq!(runtime.take_file_cache_stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn per_file_output_is_cached_for_interesting_files_only() {
+        let ctx = workspace_with_files(20);
+        let plugin = InterestedPlugin {
+            calls: Default::default(),
+        };
+        let mut runtime = SuiteRuntime::with_seed(3);
+
+        runtime.begin_run();
+        let first = runtime.run_plugin(&plugin, &ctx);
+        let paths: Vec<_> = first.iter().map(|fr| fr.path.as_str()).collect();
+        assert_eq!(paths.len(), 11);
+        assert!(paths.iter().all(|path| path.starts_with("src/file_1")));
+        assert_eq!(runtime.take_diagnostics()["interested"].len(), 11);
+        assert_eq!(
+            runtime.take_file_cache_stats(),
+            CacheStats {
+                hits: 0,
+                misses: 11
+            }
+        );
+
+        runtime.begin_run();
+        let second = runtime.run_plugin(&plugin, &ctx);
+        assert_eq!(plugin.calls.load(std::sync::atomic::Ordering::Relaxed), 11);
+        assert_eq!(second.len(), first.len());
+        assert_eq!(runtime.take_diagnostics()["interested"].len(), 11);
+        assert_eq!(runtime.take_file_cache_stats().hits, 11);
+
+        runtime.set_file_cache(false);
+        runtime.run_plugin(&plugin, &ctx);
+        assert_eq!(plugin.calls.load(std::sync::atomic::Ordering::Relaxed), 22);
+    }
 }
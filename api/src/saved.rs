@@ -0,0 +1,240 @@
+//! The saved replacement format in `target/.forgen/` and how it is applied.
+//!
+//! `cargo forgen` writes one `<mirrored-path>.json` per changed file and the
+//! `forgen::file` macro applies it when the crate is compiled. Both, and
+//! `cargo forgen expand`, resolve the replacements with the functions here so
+//! they always agree on the rewritten source.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Replacement format written to `target/.forgen/<mirrored-path>.json`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SavedReplacement {
+    /// The nth occurrence of `old_text` in the original source file.
+    ///
+    /// This is zero-based and counts *overlapping* matches.
+    pub index: usize,
+    /// The original text to replace.
+    pub old_text: String,
+    /// The replacement text.
+    pub new_text: String,
+}
+
+/// A [`SavedReplacement`] located in a concrete source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedReplacement {
+    pub start: usize,
+    pub end: usize,
+    pub new_text: String,
+}
+
+/// `<workspace_root>/target/.forgen/<rel_path>.json`.
+pub fn replacement_path(workspace_root: &Path, rel_path: &str) -> PathBuf {
+    workspace_root
+        .join("target")
+        .join(".forgen")
+        .join(format!("{}.json", normalize_rel_path(rel_path)))
+}
+
+/// `<workspace_root>/target/.forgen/<rel_path>.diagnostics.json`, the plugin
+/// diagnostics `forgen::file` re-emits for the file.
+pub fn diagnostics_path(workspace_root: &Path, rel_path: &str) -> PathBuf {
+    workspace_root
+        .join("target")
+        .join(".forgen")
+        .join(format!("{}.diagnostics.json", normalize_rel_path(rel_path)))
+}
+
+/// Find every saved replacement in `source`, ordered by position. Fails when
+/// an occurrence is missing or two replacements overlap.
+pub fn resolve_saved_replacements(
+    source: &str,
+    saved: &[SavedReplacement],
+) -> Result<Vec<ResolvedReplacement>, String> {
+    let mut out = Vec::with_capacity(saved.len());
+
+    for rep in saved {
+        let start = find_occurrence_start(source, &rep.old_text, rep.index).ok_or_else(|| {
+            format!(
+                "could not find occurrence {} of `{}` in source",
+                rep.index, rep.old_text
+            )
+        })?;
+
+        let end = start + rep.old_text.len();
+
+        if !source.is_char_boundary(start) || !source.is_char_boundary(end) {
+            return Err(format!(
+                "resolved a replacement to non-character boundaries: [{}..{}]",
+                start, end
+            ));
+        }
+
+        if source[start..end] != rep.old_text {
+            return Err(format!(
+                "resolved replacement text mismatch at [{}..{}]",
+                start, end
+            ));
+        }
+
+        out.push(ResolvedReplacement {
+            start,
+            end,
+            new_text: rep.new_text.clone(),
+        });
+    }
+
+    out.sort_by_key(|r| (r.start, r.end));
+
+    for pair in out.windows(2) {
+        let a = &pair[0];
+        let b = &pair[1];
+        if a.end > b.start {
+            return Err(format!(
+                "found overlapping saved replacements: [{}..{}) overlaps [{}..{})",
+                a.start, a.end, b.start, b.end
+            ));
+        }
+    }
+
+    Ok(out)
+}
+
+/// Apply replacements returned by [`resolve_saved_replacements`] for the same
+/// `source`.
+pub fn apply_resolved_replacements(
+    source: &str,
+    replacements: &[ResolvedReplacement],
+) -> Result<String, String> {
+    let mut out = source.to_string();
+
+    for rep in replacements.iter().rev() {
+        if rep.start > rep.end || rep.end > out.len() {
+            return Err(format!(
+                "found out-of-bounds replacement range [{}..{})",
+                rep.start, rep.end
+            ));
+        }
+        if !out.is_char_boundary(rep.start) || !out.is_char_boundary(rep.end) {
+            return Err(format!(
+                "found non-character-boundary replacement range [{}..{})",
+                rep.start, rep.end
+            ));
+        }
+
+        out.replace_range(rep.start..rep.end, &rep.new_text);
+    }
+
+    Ok(out)
+}
+
+fn find_occurrence_start(source: &str, needle: &str, target_index: usize) -> Option<usize> {
+    if needle.is_empty() {
+        let mut seen = 0usize;
+        for i in 0..=source.len() {
+            if !source.is_char_boundary(i) {
+                continue;
+            }
+            if seen == target_index {
+                return Some(i);
+            }
+            seen += 1;
+        }
+        return None;
+    }
+
+    let mut seen = 0usize;
+    for i in 0..source.len() {
+        if !source.is_char_boundary(i) {
+            continue;
+        }
+        if source[i..].starts_with(needle) {
+            if seen == target_index {
+                return Some(i);
+            }
+            seen += 1;
+        }
+    }
+
+    None
+}
+
+/// Remove the line holding `#![forgen::file("<declared_rel_path>")]`, which
+/// the macro drops from the source it compiles.
+pub fn remove_forgen_attr_line(source: &str, declared_rel_path: &str) -> Option<String> {
+    let normalized = normalize_rel_path(declared_rel_path);
+
+    let candidates = [
+        format!("#![forgen::file(\"{}\")]", declared_rel_path),
+        format!("#![forgen::file(\"{}\")]", normalized),
+    ];
+
+    for candidate in candidates {
+        if let Some(s) = remove_line_containing(source, &candidate) {
+            return Some(s);
+        }
+    }
+
+    None
+}
+
+fn remove_line_containing(source: &str, needle: &str) -> Option<String> {
+    let idx = source.find(needle)?;
+    let line_start = source[..idx].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = source[idx..]
+        .find('\n')
+        .map(|off| idx + off + 1)
+        .unwrap_or(source.len());
+
+    let mut out = String::with_capacity(source.len().saturating_sub(line_end - line_start));
+    out.push_str(&source[..line_start]);
+    out.push_str(&source[line_end..]);
+    Some(out)
+}
+
+pub fn normalize_rel_path(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved(index: usize, old_text: &str, new_text: &str) -> SavedReplacement {
+        SavedReplacement {
+            index,
+            old_text: old_text.to_owned(),
+            new_text: new_text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn resolves_occurrences_and_applies_them() {
+        let source = "#![forgen::file(\"src/lib.rs\")]\nlet a = 1;\nlet b = 1;\n";
+        let resolved =
+            resolve_saved_replacements(source, &[saved(1, "1", "2"), saved(0, "", "// top\n")])
+                .unwrap();
+        assert_eq!(resolved[0].start, 0);
+
+        let rewritten = apply_resolved_replacements(source, &resolved).unwrap();
+        assert_eq!(
+            rewritten,
+            "// top\n#![forgen::file(\"src/lib.rs\")]\nlet a = 1;\nlet b = 2;\n"
+        );
+        assert_eq!(
+            remove_forgen_attr_line(&rewritten, "src/lib.rs").unwrap(),
+            "// top\nlet a = 1;\nlet b = 2;\n"
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_overlapping_replacements() {
+        let err = resolve_saved_replacements("abc", &[saved(0, "x", "")]).unwrap_err();
+        assert!(err.contains("could not find occurrence 0 of `x`"));
+
+        let err = resolve_saved_replacements("abc", &[saved(0, "ab", ""), saved(0, "bc", "")])
+            .unwrap_err();
+        assert!(err.contains("overlapping"));
+    }
+}
@@ -0,0 +1,484 @@
+use crate::Plugin;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Name of the implicit phase used when a [`Schedule`] declares none.
+pub const DEFAULT_PHASE: &str = "default";
+
+/// A plugin plus the constraints that decide when it runs.
+///
+/// ```rust,ignore
+/// Registration::new(&SeededBindingPlugin)
+///     .phase("instrument")
+///     .after_converged("f64-logger")
+/// ```
+pub struct Registration<'a> {
+    pub(crate) plugin: &'a dyn Plugin,
+    pub(crate) phase: Option<String>,
+    pub(crate) before: Vec<String>,
+    pub(crate) after: Vec<String>,
+    pub(crate) after_converged: Vec<String>,
+}
+
+impl<'a> Registration<'a> {
+    pub fn new(plugin: &'a dyn Plugin) -> Self {
+        Self {
+            plugin,
+            phase: None,
+            before: Vec::new(),
+            after: Vec::new(),
+            after_converged: Vec::new(),
+        }
+    }
+
+    /// Run in `phase` instead of the schedule's first phase.
+    pub fn phase(mut self, phase: impl Into<String>) -> Self {
+        self.phase = Some(phase.into());
+        self
+    }
+
+    /// Run before `plugin_id` within every pass. Satisfied automatically when
+    /// `plugin_id` belongs to a later phase.
+    pub fn before(mut self, plugin_id: impl Into<String>) -> Self {
+        self.before.push(plugin_id.into());
+        self
+    }
+
+    /// Run after `plugin_id` within every pass. Satisfied automatically when
+    /// `plugin_id` belongs to an earlier phase.
+    pub fn after(mut self, plugin_id: impl Into<String>) -> Self {
+        self.after.push(plugin_id.into());
+        self
+    }
+
+    /// Run after `plugin_id`, and only in passes where `plugin_id` produced
+    /// no replacements — i.e. once it has converged on the current snapshot.
+    pub fn after_converged(mut self, plugin_id: impl Into<String>) -> Self {
+        self.after_converged.push(plugin_id.into());
+        self
+    }
+}
+
+impl fmt::Debug for Registration<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registration")
+            .field("plugin", &self.plugin.name())
+            .field("phase", &self.phase)
+            .field("before", &self.before)
+            .field("after", &self.after)
+            .field("after_converged", &self.after_converged)
+            .finish()
+    }
+}
+
+/// Declarative plugin registration for [`SuiteRuntime::run_schedule`].
+///
+/// Plugins are grouped into ordered phases. Each phase runs to its own fixed
+/// point — the CLI keeps re-running it until none of its plugins produce
+/// replacements — before the next phase starts. Inside a phase, plugins run
+/// in an order that satisfies every `before` / `after` constraint, with ties
+/// broken by registration order.
+///
+/// ```rust,ignore
+/// fn run(ctx: &WorkspaceContext, runtime: &mut SuiteRuntime) -> Vec<FileReplacement> {
+///     let schedule = Schedule::new()
+///         .phases(["expand", "instrument", "lint"])
+///         .register(Registration::new(&ExpandPlugin).phase("expand"))
+///         .register(Registration::new(&LoggerPlugin).phase("instrument"))
+///         .register(Registration::new(&LintPlugin).phase("lint"));
+///     runtime.run_schedule(&schedule, ctx)
+/// }
+/// ```
+///
+/// [`SuiteRuntime::run_schedule`]: crate::SuiteRuntime::run_schedule
+#[derive(Debug, Default)]
+pub struct Schedule<'a> {
+    phases: Vec<String>,
+    registrations: Vec<Registration<'a>>,
+}
+
+impl<'a> Schedule<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare the phases in execution order. Without this call the schedule
+    /// has a single phase named [`DEFAULT_PHASE`].
+    pub fn phases<I, S>(mut self, phases: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.phases = phases.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn register(mut self, registration: Registration<'a>) -> Self {
+        self.registrations.push(registration);
+        self
+    }
+
+    /// Phase names in execution order.
+    pub fn phase_names(&self) -> Vec<&str> {
+        if self.phases.is_empty() {
+            vec![DEFAULT_PHASE]
+        } else {
+            self.phases.iter().map(String::as_str).collect()
+        }
+    }
+
+    /// Every registration with the phase it runs in, in registration order.
+    pub(crate) fn registrations(&self) -> impl Iterator<Item = (&Registration<'a>, &str)> {
+        let first_phase = self.phase_names()[0];
+        self.registrations.iter().map(move |registration| {
+            let phase = registration.phase.as_deref().unwrap_or(first_phase);
+            (registration, phase)
+        })
+    }
+
+    /// Check every constraint and compute the execution order of each phase.
+    pub fn plan(&self) -> Result<Plan<'_, 'a>, ScheduleError> {
+        let phase_names = self.phase_names();
+
+        let mut seen_phases = HashSet::new();
+        for phase in &phase_names {
+            if !seen_phases.insert(*phase) {
+                return Err(ScheduleError::DuplicatePhase {
+                    phase: (*phase).to_owned(),
+                });
+            }
+        }
+
+        // Resolve every registration to a phase index.
+        let mut phase_of: HashMap<&str, usize> = HashMap::new();
+        for registration in &self.registrations {
+            let id = registration.plugin.name();
+            let phase = match &registration.phase {
+                None => 0,
+                Some(name) => phase_names
+                    .iter()
+                    .position(|phase| phase == name)
+                    .ok_or_else(|| ScheduleError::UnknownPhase {
+                        plugin: id.to_owned(),
+                        phase: name.clone(),
+                    })?,
+            };
+            if phase_of.insert(id, phase).is_some() {
+                return Err(ScheduleError::DuplicatePlugin {
+                    plugin: id.to_owned(),
+                });
+            }
+        }
+
+        // Edges `from -> to` mean `from` must run before `to` in the same phase.
+        let mut edges: Vec<(usize, usize)> = Vec::new();
+        let index_of = |id: &str| {
+            self.registrations
+                .iter()
+                .position(|registration| registration.plugin.name() == id)
+        };
+
+        for (index, registration) in self.registrations.iter().enumerate() {
+            let id = registration.plugin.name();
+            let phase = phase_of[id];
+
+            let constraints = registration
+                .before
+                .iter()
+                .map(|other| (other, true))
+                .chain(registration.after.iter().map(|other| (other, false)))
+                .chain(
+                    registration
+                        .after_converged
+                        .iter()
+                        .map(|other| (other, false)),
+                );
+
+            for (other, runs_before_other) in constraints {
+                let other_index = index_of(other).ok_or_else(|| ScheduleError::UnknownPlugin {
+                    plugin: id.to_owned(),
+                    dependency: other.clone(),
+                })?;
+                let other_phase = phase_of[other.as_str()];
+
+                let satisfied_by_phases = if runs_before_other {
+                    phase < other_phase
+                } else {
+                    phase > other_phase
+                };
+                if satisfied_by_phases {
+                    continue;
+                }
+                if phase != other_phase {
+                    return Err(ScheduleError::PhaseConflict {
+                        plugin: id.to_owned(),
+                        phase: phase_names[phase].to_owned(),
+                        dependency: other.clone(),
+                        dependency_phase: phase_names[other_phase].to_owned(),
+                    });
+                }
+
+                edges.push(if runs_before_other {
+                    (index, other_index)
+                } else {
+                    (other_index, index)
+                });
+            }
+        }
+
+        let mut phases = Vec::with_capacity(phase_names.len());
+        for (phase, name) in phase_names.iter().enumerate() {
+            let members: Vec<usize> = (0..self.registrations.len())
+                .filter(|&index| phase_of[self.registrations[index].plugin.name()] == phase)
+                .collect();
+            let order =
+                topological_order(&members, &edges).map_err(|cycle| ScheduleError::Cycle {
+                    phase: (*name).to_owned(),
+                    plugins: cycle
+                        .into_iter()
+                        .map(|index| self.registrations[index].plugin.name().to_owned())
+                        .collect(),
+                })?;
+            phases.push(PlannedPhase {
+                name,
+                registrations: order
+                    .into_iter()
+                    .map(|index| &self.registrations[index])
+                    .collect(),
+            });
+        }
+
+        Ok(Plan { phases })
+    }
+}
+
+/// The validated execution order produced by [`Schedule::plan`].
+#[derive(Debug)]
+pub struct Plan<'s, 'a> {
+    pub(crate) phases: Vec<PlannedPhase<'s, 'a>>,
+}
+
+impl Plan<'_, '_> {
+    /// Phase names with their plugin ids in execution order.
+    pub fn phases(&self) -> impl Iterator<Item = (&str, Vec<&str>)> {
+        self.phases.iter().map(|phase| {
+            (
+                phase.name,
+                phase
+                    .registrations
+                    .iter()
+                    .map(|registration| registration.plugin.name())
+                    .collect(),
+            )
+        })
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct PlannedPhase<'s, 'a> {
+    pub(crate) name: &'s str,
+    pub(crate) registrations: Vec<&'s Registration<'a>>,
+}
+
+/// Kahn's algorithm over `members`, picking the lowest registration index
+/// whenever several plugins are ready. On a cycle, returns the plugins that
+/// form one.
+fn topological_order(
+    members: &[usize],
+    edges: &[(usize, usize)],
+) -> Result<Vec<usize>, Vec<usize>> {
+    let member_set: HashSet<usize> = members.iter().copied().collect();
+    let edges: Vec<(usize, usize)> = edges
+        .iter()
+        .copied()
+        .filter(|(from, to)| member_set.contains(from) && member_set.contains(to))
+        .collect();
+
+    let mut in_degree: HashMap<usize, usize> = members.iter().map(|&m| (m, 0)).collect();
+    for (_, to) in &edges {
+        *in_degree.get_mut(to).unwrap() += 1;
+    }
+
+    let mut order = Vec::with_capacity(members.len());
+    let mut done = HashSet::new();
+    while order.len() < members.len() {
+        let Some(next) = members
+            .iter()
+            .copied()
+            .find(|m| !done.contains(m) && in_degree[m] == 0)
+        else {
+            let remaining: Vec<usize> = members
+                .iter()
+                .copied()
+                .filter(|m| !done.contains(m))
+                .collect();
+            return Err(find_cycle(&remaining, &edges));
+        };
+
+        done.insert(next);
+        order.push(next);
+        for (_, to) in edges.iter().filter(|(from, _)| *from == next) {
+            *in_degree.get_mut(to).unwrap() -= 1;
+        }
+    }
+
+    Ok(order)
+}
+
+/// Every node left over by Kahn's algorithm has a predecessor that is also
+/// left over, so walking predecessors must eventually revisit a node.
+fn find_cycle(remaining: &[usize], edges: &[(usize, usize)]) -> Vec<usize> {
+    let remaining: HashSet<usize> = remaining.iter().copied().collect();
+    let mut path = Vec::new();
+    let mut current = *remaining.iter().min().unwrap();
+
+    loop {
+        if let Some(start) = path.iter().position(|&node| node == current) {
+            let mut cycle: Vec<usize> = path[start..].to_vec();
+            cycle.reverse();
+            // Start at the earliest registration so the report is stable.
+            let first = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+            cycle.rotate_left(first);
+            return cycle;
+        }
+        path.push(current);
+        current = edges
+            .iter()
+            .find(|(from, to)| *to == current && remaining.contains(from))
+            .map(|(from, _)| *from)
+            .unwrap();
+    }
+}
+
+/// Error returned by [`Schedule::plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    DuplicatePhase {
+        phase: String,
+    },
+    DuplicatePlugin {
+        plugin: String,
+    },
+    UnknownPhase {
+        plugin: String,
+        phase: String,
+    },
+    UnknownPlugin {
+        plugin: String,
+        dependency: String,
+    },
+    /// A `before` / `after` constraint points the wrong way across phases.
+    PhaseConflict {
+        plugin: String,
+        phase: String,
+        dependency: String,
+        dependency_phase: String,
+    },
+    /// Ordering constraints inside `phase` form a cycle through `plugins`.
+    Cycle {
+        phase: String,
+        plugins: Vec<String>,
+    },
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::DuplicatePhase { phase } => {
+                write!(f, "phase `{phase}` is declared more than once")
+            }
+            ScheduleError::DuplicatePlugin { plugin } => {
+                write!(f, "plugin `{plugin}` is registered more than once")
+            }
+            ScheduleError::UnknownPhase { plugin, phase } => {
+                write!(f, "plugin `{plugin}` is registered in undeclared phase `{phase}`")
+            }
+            ScheduleError::UnknownPlugin { plugin, dependency } => write!(
+                f,
+                "plugin `{plugin}` is ordered relative to `{dependency}`, which is not registered"
+            ),
+            ScheduleError::PhaseConflict {
+                plugin,
+                phase,
+                dependency,
+                dependency_phase,
+            } => write!(
+                f,
+                "plugin `{plugin}` (phase `{phase}`) cannot be ordered that way relative to `{dependency}` (phase `{dependency_phase}`)"
+            ),
+            ScheduleError::Cycle { phase, plugins } => {
+                write!(f, "plugin ordering cycle in phase `{phase}`: ")?;
+                for plugin in plugins {
+                    write!(f, "{plugin} -> ")?;
+                }
+                write!(f, "{}", plugins[0])
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileReplacement, PluginRuntime, WorkspaceContext};
+
+    struct Named(&'static str);
+
+    impl Plugin for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn run(
+            &self,
+            _ctx: &WorkspaceContext,
+            _runtime: &mut PluginRuntime<'_>,
+        ) -> Vec<FileReplacement> {
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn plan_orders_plugins_by_phase_and_constraints() {
+        let (a, b, c, d) = (Named("a"), Named("b"), Named("c"), Named("d"));
+        let schedule = Schedule::new()
+            .phases(["expand", "lint"])
+            .register(Registration::new(&a).phase("lint"))
+            .register(Registration::new(&b).after("c"))
+            .register(Registration::new(&c).before("a"))
+            .register(Registration::new(&d).phase("lint").before("a"));
+
+        let plan = schedule.plan().unwrap();
+        let phases: Vec<_> = plan.phases().collect();
+        assert_eq!(
+            phases,
+            vec![("expand", vec!["c", "b"]), ("lint", vec!["d", "a"])]
+        );
+    }
+
+    #[test]
+    fn plan_reports_cycles_and_phase_conflicts() {
+        let (a, b, c) = (Named("a"), Named("b"), Named("c"));
+        let cycle = Schedule::new()
+            .register(Registration::new(&a).after("c"))
+            .register(Registration::new(&b).after("a"))
+            .register(Registration::new(&c).after_converged("b"));
+        let err = cycle.plan().unwrap_err();
+        assert!(matches!(err, ScheduleError::Cycle { ref plugins, .. } if plugins.len() == 3));
+        assert_eq!(
+            err.to_string(),
+            "plugin ordering cycle in phase `default`: a -> b -> c -> a"
+        );
+
+        let conflict = Schedule::new()
+            .phases(["first", "second"])
+            .register(Registration::new(&a).phase("first").after("b"))
+            .register(Registration::new(&b).phase("second"));
+        assert!(matches!(
+            conflict.plan(),
+            Err(ScheduleError::PhaseConflict { .. })
+        ));
+    }
+}
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Pinned per-plugin, per-file RNG seeds.
+///
+/// Without a lock, [`PluginRuntime::rng_for_file`] derives its seed from the
+/// suite seed, the plugin id and the file path, so changing the suite seed or
+/// moving a file changes every generated value. With a lock enabled on the
+/// [`SuiteRuntime`], the first seed handed out for a `(plugin, file)` pair is
+/// recorded here and reused on every later run until it is explicitly
+/// regenerated.
+///
+/// Each locked file also stores a content fingerprint so the pins can follow
+/// the file when it is moved or renamed (see [`SeedLock::sync_files`]).
+///
+/// [`PluginRuntime::rng_for_file`]: crate::PluginRuntime::rng_for_file
+/// [`SuiteRuntime`]: crate::SuiteRuntime
+#[derive(Debug, Clone, Default, Eq, Serialize, Deserialize)]
+pub struct SeedLock {
+    #[serde(default)]
+    files: BTreeMap<String, LockedFile>,
+    /// Fingerprints of the sources passed to the last [`SeedLock::sync_files`],
+    /// so files pinned for the first time get one right away.
+    #[serde(skip)]
+    source_fingerprints: HashMap<String, String>,
+}
+
+impl PartialEq for SeedLock {
+    fn eq(&self, other: &Self) -> bool {
+        self.files == other.files
+    }
+}
+
+/// Seeds pinned for one workspace-relative file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedFile {
+    /// Fingerprint of the file's original source, see [`SeedLock::fingerprint`].
+    #[serde(default)]
+    pub fingerprint: String,
+    /// Pinned seed per plugin id.
+    #[serde(default)]
+    pub seeds: BTreeMap<String, u64>,
+}
+
+impl SeedLock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (&str, &LockedFile)> {
+        self.files.iter().map(|(path, file)| (path.as_str(), file))
+    }
+
+    /// The pinned seed for `plugin_id` in `file_path`, if any.
+    pub fn get(&self, plugin_id: &str, file_path: &str) -> Option<u64> {
+        self.files.get(file_path)?.seeds.get(plugin_id).copied()
+    }
+
+    /// Pin `seed` for `plugin_id` in `file_path`, replacing any previous pin.
+    ///
+    /// A file without a fingerprint gets the one of its source from the last
+    /// [`SeedLock::sync_files`], so the pin follows the file even if it moves
+    /// before the lock is synced again.
+    pub fn pin(&mut self, plugin_id: &str, file_path: &str, seed: u64) {
+        let file = self.files.entry(file_path.to_owned()).or_default();
+        if file.fingerprint.is_empty() {
+            if let Some(fingerprint) = self.source_fingerprints.get(file_path) {
+                file.fingerprint = fingerprint.clone();
+            }
+        }
+        file.seeds.insert(plugin_id.to_owned(), seed);
+    }
+
+    /// Replace pinned seeds with fresh random ones.
+    ///
+    /// `None` filters match everything, so `regenerate(None, None)` re-rolls
+    /// the whole lock. Returns the number of seeds that changed.
+    pub fn regenerate(&mut self, plugin_id: Option<&str>, file_path: Option<&str>) -> usize {
+        let mut regenerated = 0usize;
+
+        for (path, file) in &mut self.files {
+            if file_path.is_some_and(|wanted| wanted != path) {
+                continue;
+            }
+            for (id, seed) in &mut file.seeds {
+                if plugin_id.is_some_and(|wanted| wanted != id) {
+                    continue;
+                }
+                *seed = rand::random();
+                regenerated += 1;
+            }
+        }
+
+        regenerated
+    }
+
+    /// Reconcile the lock with the current workspace sources
+    /// (workspace-relative path → original source text).
+    ///
+    /// A locked path that no longer exists is moved to an unlocked path whose
+    /// source has the same fingerprint, so a renamed file keeps its values.
+    /// Afterwards the fingerprint of every locked file that still exists is
+    /// refreshed. Pins for files that vanished without a match are kept, in
+    /// case the file comes back.
+    ///
+    /// Returns the `(from, to)` pairs of moved entries.
+    pub fn sync_files(&mut self, sources: &HashMap<String, String>) -> Vec<(String, String)> {
+        self.source_fingerprints = sources
+            .iter()
+            .map(|(path, source)| (path.clone(), Self::fingerprint(source)))
+            .collect();
+
+        let mut by_fingerprint: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (path, fingerprint) in &self.source_fingerprints {
+            if !self.files.contains_key(path) {
+                by_fingerprint.entry(fingerprint).or_default().push(path);
+            }
+        }
+
+        let missing: Vec<String> = self
+            .files
+            .keys()
+            .filter(|path| !sources.contains_key(*path))
+            .cloned()
+            .collect();
+
+        let mut moved = Vec::new();
+        for from in missing {
+            let fingerprint = self.files[&from].fingerprint.as_str();
+            let Some(candidates) = by_fingerprint.get_mut(fingerprint) else {
+                continue;
+            };
+            // Ambiguous copies are left alone rather than guessed.
+            if candidates.len() != 1 {
+                continue;
+            }
+            let to = candidates.pop().unwrap().to_owned();
+            let file = self.files.remove(&from).unwrap();
+            self.files.insert(to.clone(), file);
+            moved.push((from, to));
+        }
+
+        for (path, file) in &mut self.files {
+            if let Some(fingerprint) = self.source_fingerprints.get(path) {
+                file.fingerprint = fingerprint.clone();
+            }
+        }
+
+        moved.sort();
+        moved
+    }
+
+    /// Content fingerprint used to recognise moved files.
+    ///
+    /// `#![forgen::file("...")]` lines are ignored because they spell out the
+    /// file's own path and therefore always change when a file moves.
+    pub fn fingerprint(source: &str) -> String {
+        let mut hash = crate::runtime::FNV_OFFSET;
+        for line in source.lines() {
+            if line.trim_start().starts_with("#![forgen::file(") {
+                continue;
+            }
+            hash = crate::runtime::fnv1a64_extend(hash, line.as_bytes());
+            hash = crate::runtime::fnv1a64_extend(hash, b"\n");
+        }
+        format!("{hash:016x}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_follow_moved_files() {
+        let mut lock = SeedLock::new();
+        lock.pin("demo", "src/old.rs", 42);
+        lock.sync_files(&HashMap::from([(
+            "src/old.rs".to_owned(),
+            "#![forgen::file(\"src/old.rs\")]\nfn a() {}\n".to_owned(),
+        )]));
+
+        let moved = lock.sync_files(&HashMap::from([
+            (
+                "src/new.rs".to_owned(),
+                "#![forgen::file(\"src/new.rs\")]\nfn a() {}\n".to_owned(),
+            ),
+            ("src/other.rs".to_owned(), "fn b() {}\n".to_owned()),
+        ]));
+
+        assert_eq!(
+            moved,
+            vec![("src/old.rs".to_owned(), "src/new.rs".to_owned())]
+        );
+        assert_eq!(lock.get("demo", "src/new.rs"), Some(42));
+        assert_eq!(lock.get("demo", "src/old.rs"), None);
+    }
+}
@@ -0,0 +1,809 @@
+//! Code templates for building Rust source text inside plugins.
+//!
+//! `format!` is a poor fit for generating Rust: every literal `{` and `}` has
+//! to be escaped, and nothing checks that the result is valid code. A
+//! [`Template`] instead uses `$name` placeholders, validates every
+//! interpolated value against its declared kind, and parses the rendered text
+//! as the expected [`Fragment`] before it can become a [`Replacement`].
+//!
+//! # Syntax
+//!
+//! | Form               | Meaning                                                  |
+//! |--------------------|----------------------------------------------------------|
+//! | `$name`            | Interpolate the value bound to `name`                    |
+//! | `$$`               | A literal `$`                                            |
+//! | `$( ... )*`        | Repeat the body once per element of the lists used in it |
+//! | `$( ... ),*`       | Same, with a separator (one or two characters) and space |
+//!
+//! Placeholders are substituted textually, so they also work inside string
+//! literals (`"$name: {}"`). Inside a repetition, list bindings advance once
+//! per iteration while single bindings repeat unchanged; every list used in
+//! one repetition must have the same length.
+//!
+//! # Example
+//!
+//! ```rust
+//! use forgen_api::Template;
+//!
+//! let code = Template::stmt(r#"println!("$name: {}", $name);"#)
+//!     .ident("name", "distance")
+//!     .render()
+//!     .unwrap();
+//! assert_eq!(code, r#"println!("distance: {}", distance);"#);
+//!
+//! let item = Template::item("struct $name { $(pub $field: $ty),* }")
+//!     .ident("name", "Point")
+//!     .idents("field", ["x", "y"])
+//!     .tys("ty", ["f64", "f64"])
+//!     .render()
+//!     .unwrap();
+//! assert_eq!(item, "struct Point { pub x: f64, pub y: f64 }");
+//! ```
+
+use crate::{Replacement, TextRange};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The kind of Rust syntax a [`Template`] must render to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fragment {
+    /// A single item (`fn`, `struct`, `impl`, `use`, …).
+    Item,
+    /// Zero or more items.
+    Items,
+    /// A single statement (`let x = 1;`, `foo();`, an item, …).
+    Stmt,
+    /// Zero or more statements, as they would appear inside a block.
+    Stmts,
+    /// A single expression.
+    Expr,
+    /// A single type.
+    Type,
+}
+
+impl fmt::Display for Fragment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Fragment::Item => "item",
+            Fragment::Items => "items",
+            Fragment::Stmt => "statement",
+            Fragment::Stmts => "statements",
+            Fragment::Expr => "expression",
+            Fragment::Type => "type",
+        })
+    }
+}
+
+/// How an interpolated value is validated before it is spliced in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    /// An identifier such as `counter` or `r#type`.
+    Ident,
+    /// A type such as `Vec<String>`.
+    Type,
+    /// An expression. Non-atomic expressions are parenthesized on insertion
+    /// so operator precedence in the template is preserved.
+    Expr,
+    /// Arbitrary text, inserted verbatim and only checked as part of the
+    /// rendered fragment.
+    Raw,
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValueKind::Ident => "identifier",
+            ValueKind::Type => "type",
+            ValueKind::Expr => "expression",
+            ValueKind::Raw => "raw text",
+        })
+    }
+}
+
+/// Error returned by [`Template::render`] and friends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// The template text itself is malformed.
+    Syntax { offset: usize, message: String },
+    /// The template uses `$name` but nothing is bound to `name`.
+    Unbound { name: String },
+    /// A bound value is not a valid instance of its declared kind.
+    InvalidValue {
+        name: String,
+        kind: ValueKind,
+        value: String,
+        message: String,
+    },
+    /// A list binding was used outside of `$( ... )*`.
+    ListOutsideRepetition { name: String },
+    /// A repetition does not use any list binding, so its length is unknown.
+    RepetitionWithoutList,
+    /// Lists used in the same repetition have different lengths.
+    RepetitionLengthMismatch {
+        first: String,
+        first_len: usize,
+        second: String,
+        second_len: usize,
+    },
+    /// The rendered text does not parse as the expected fragment.
+    Parse {
+        fragment: Fragment,
+        message: String,
+        rendered: String,
+    },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::Syntax { offset, message } => {
+                write!(f, "malformed template at byte {offset}: {message}")
+            }
+            TemplateError::Unbound { name } => {
+                write!(f, "template placeholder `${name}` has no bound value")
+            }
+            TemplateError::InvalidValue {
+                name,
+                kind,
+                value,
+                message,
+            } => write!(
+                f,
+                "value `{value}` bound to `${name}` is not a valid {kind}: {message}"
+            ),
+            TemplateError::ListOutsideRepetition { name } => write!(
+                f,
+                "`${name}` is bound to a list and can only be used inside `$( ... )*`"
+            ),
+            TemplateError::RepetitionWithoutList => {
+                f.write_str("`$( ... )*` repetition does not use any list binding")
+            }
+            TemplateError::RepetitionLengthMismatch {
+                first,
+                first_len,
+                second,
+                second_len,
+            } => write!(
+                f,
+                "lists in the same repetition differ in length: `${first}` has {first_len}, `${second}` has {second_len}"
+            ),
+            TemplateError::Parse {
+                fragment,
+                message,
+                rendered,
+            } => write!(
+                f,
+                "rendered template is not a valid {fragment}: {message}\n{rendered}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Debug, Clone)]
+enum Binding {
+    One(ValueKind, String),
+    Many(ValueKind, Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Var(String),
+    Repeat {
+        body: Vec<Node>,
+        separator: Option<String>,
+    },
+}
+
+/// A Rust code template with typed interpolation.
+///
+/// Build one with the constructor matching the expected [`Fragment`], bind
+/// values with the typed builder methods, then call [`Template::render`] or
+/// turn it straight into a [`Replacement`] with [`Template::insert_at`] /
+/// [`Template::replace`]. See the [module docs](self) for the syntax.
+#[derive(Debug, Clone)]
+pub struct Template {
+    fragment: Fragment,
+    source: String,
+    bindings: HashMap<String, Binding>,
+}
+
+impl Template {
+    /// Create a template that must render to `fragment`. [`Template::item`],
+    /// [`Template::items`], [`Template::stmt`] and [`Template::stmts`] are
+    /// shorthands for the common cases.
+    pub fn new(fragment: Fragment, source: impl Into<String>) -> Self {
+        Self {
+            fragment,
+            source: source.into(),
+            bindings: HashMap::new(),
+        }
+    }
+
+    pub fn item(source: impl Into<String>) -> Self {
+        Self::new(Fragment::Item, source)
+    }
+
+    pub fn items(source: impl Into<String>) -> Self {
+        Self::new(Fragment::Items, source)
+    }
+
+    pub fn stmt(source: impl Into<String>) -> Self {
+        Self::new(Fragment::Stmt, source)
+    }
+
+    pub fn stmts(source: impl Into<String>) -> Self {
+        Self::new(Fragment::Stmts, source)
+    }
+
+    pub fn fragment(&self) -> Fragment {
+        self.fragment
+    }
+
+    /// Bind `$name` to an identifier.
+    pub fn ident(self, name: impl Into<String>, value: impl AsRef<str>) -> Self {
+        self.bind_one(name, ValueKind::Ident, value)
+    }
+
+    /// Bind `$name` to a type.
+    pub fn ty(self, name: impl Into<String>, value: impl AsRef<str>) -> Self {
+        self.bind_one(name, ValueKind::Type, value)
+    }
+
+    /// Bind `$name` to an expression.
+    pub fn expr(self, name: impl Into<String>, value: impl AsRef<str>) -> Self {
+        self.bind_one(name, ValueKind::Expr, value)
+    }
+
+    /// Bind `$name` to raw text (for example the contents of a string
+    /// literal, or an already rendered template).
+    pub fn raw(self, name: impl Into<String>, value: impl AsRef<str>) -> Self {
+        self.bind_one(name, ValueKind::Raw, value)
+    }
+
+    /// Bind `$name` to a list of identifiers for use in `$( ... )*`.
+    pub fn idents<I, S>(self, name: impl Into<String>, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.bind_many(name, ValueKind::Ident, values)
+    }
+
+    /// Bind `$name` to a list of types for use in `$( ... )*`.
+    pub fn tys<I, S>(self, name: impl Into<String>, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.bind_many(name, ValueKind::Type, values)
+    }
+
+    /// Bind `$name` to a list of expressions for use in `$( ... )*`.
+    pub fn exprs<I, S>(self, name: impl Into<String>, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.bind_many(name, ValueKind::Expr, values)
+    }
+
+    /// Bind `$name` to a list of raw text snippets for use in `$( ... )*`.
+    pub fn raws<I, S>(self, name: impl Into<String>, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.bind_many(name, ValueKind::Raw, values)
+    }
+
+    /// Render the template and check that the result parses as the expected
+    /// [`Fragment`].
+    pub fn render(&self) -> Result<String, TemplateError> {
+        for (name, binding) in &self.bindings {
+            match binding {
+                Binding::One(kind, value) => validate_value(name, *kind, value)?,
+                Binding::Many(kind, values) => {
+                    for value in values {
+                        validate_value(name, *kind, value)?;
+                    }
+                }
+            }
+        }
+
+        let nodes = TemplateParser::new(&self.source).parse()?;
+        let mut rendered = String::with_capacity(self.source.len());
+        self.render_nodes(&nodes, None, &mut rendered)?;
+
+        check_fragment(self.fragment, &rendered)?;
+        Ok(rendered)
+    }
+
+    /// Render the template into a zero-width insertion at `offset`.
+    pub fn insert_at(&self, offset: u32) -> Result<Replacement, TemplateError> {
+        Ok(Replacement::insert(offset, self.render()?))
+    }
+
+    /// Render the template into a replacement of `range`.
+    pub fn replace(&self, range: TextRange) -> Result<Replacement, TemplateError> {
+        Ok(Replacement::replace(range.start, range.end, self.render()?))
+    }
+
+    fn bind_one(
+        mut self,
+        name: impl Into<String>,
+        kind: ValueKind,
+        value: impl AsRef<str>,
+    ) -> Self {
+        self.bindings
+            .insert(name.into(), Binding::One(kind, value.as_ref().to_owned()));
+        self
+    }
+
+    fn bind_many<I, S>(mut self, name: impl Into<String>, kind: ValueKind, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let values = values
+            .into_iter()
+            .map(|value| value.as_ref().to_owned())
+            .collect();
+        self.bindings
+            .insert(name.into(), Binding::Many(kind, values));
+        self
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        iteration: Option<usize>,
+        out: &mut String,
+    ) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Var(name) => {
+                    let (kind, value) = match self.bindings.get(name) {
+                        None => return Err(TemplateError::Unbound { name: name.clone() }),
+                        Some(Binding::One(kind, value)) => (*kind, value),
+                        Some(Binding::Many(kind, values)) => match iteration {
+                            Some(index) => (*kind, &values[index]),
+                            None => {
+                                return Err(TemplateError::ListOutsideRepetition {
+                                    name: name.clone(),
+                                })
+                            }
+                        },
+                    };
+                    push_value(out, kind, value);
+                }
+                Node::Repeat { body, separator } => {
+                    let len = self.repetition_len(body)?;
+                    for index in 0..len {
+                        if index > 0 {
+                            if let Some(separator) = separator {
+                                out.push_str(separator);
+                                out.push(' ');
+                            }
+                        }
+                        self.render_nodes(body, Some(index), out)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn repetition_len(&self, body: &[Node]) -> Result<usize, TemplateError> {
+        let mut found: Option<(&str, usize)> = None;
+
+        for node in body {
+            let Node::Var(name) = node else {
+                continue;
+            };
+            let Some(Binding::Many(_, values)) = self.bindings.get(name) else {
+                continue;
+            };
+
+            match found {
+                None => found = Some((name, values.len())),
+                Some((first, first_len)) if first_len != values.len() => {
+                    return Err(TemplateError::RepetitionLengthMismatch {
+                        first: first.to_owned(),
+                        first_len,
+                        second: name.clone(),
+                        second_len: values.len(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        found
+            .map(|(_, len)| len)
+            .ok_or(TemplateError::RepetitionWithoutList)
+    }
+}
+
+fn push_value(out: &mut String, kind: ValueKind, value: &str) {
+    let value = value.trim();
+    if kind == ValueKind::Expr && !is_atomic_expr(value) {
+        out.push('(');
+        out.push_str(value);
+        out.push(')');
+    } else {
+        out.push_str(value);
+    }
+}
+
+fn validate_value(name: &str, kind: ValueKind, value: &str) -> Result<(), TemplateError> {
+    let result = match kind {
+        ValueKind::Ident => syn::parse_str::<syn::Ident>(value).map(drop),
+        ValueKind::Type => syn::parse_str::<syn::Type>(value).map(drop),
+        ValueKind::Expr => syn::parse_str::<syn::Expr>(value).map(drop),
+        ValueKind::Raw => Ok(()),
+    };
+
+    result.map_err(|err| TemplateError::InvalidValue {
+        name: name.to_owned(),
+        kind,
+        value: value.to_owned(),
+        message: err.to_string(),
+    })
+}
+
+/// Expressions that never need parentheses when spliced into a larger one.
+fn is_atomic_expr(value: &str) -> bool {
+    matches!(
+        syn::parse_str::<syn::Expr>(value),
+        Ok(syn::Expr::Array(_)
+            | syn::Expr::Block(_)
+            | syn::Expr::Call(_)
+            | syn::Expr::Field(_)
+            | syn::Expr::Index(_)
+            | syn::Expr::Lit(_)
+            | syn::Expr::Macro(_)
+            | syn::Expr::MethodCall(_)
+            | syn::Expr::Paren(_)
+            | syn::Expr::Path(_)
+            | syn::Expr::Struct(_)
+            | syn::Expr::Tuple(_))
+    )
+}
+
+fn check_fragment(fragment: Fragment, rendered: &str) -> Result<(), TemplateError> {
+    use syn::parse::Parser;
+
+    let result = match fragment {
+        Fragment::Item => syn::parse_str::<syn::Item>(rendered).map(drop),
+        Fragment::Items => syn::parse_str::<syn::File>(rendered).map(drop),
+        Fragment::Stmt => syn::parse_str::<syn::Stmt>(rendered).map(drop),
+        Fragment::Stmts => syn::Block::parse_within.parse_str(rendered).map(drop),
+        Fragment::Expr => syn::parse_str::<syn::Expr>(rendered).map(drop),
+        Fragment::Type => syn::parse_str::<syn::Type>(rendered).map(drop),
+    };
+
+    result.map_err(|err| TemplateError::Parse {
+        fragment,
+        message: err.to_string(),
+        rendered: rendered.to_owned(),
+    })
+}
+
+/// The kind of literal `parse_seq` is inside of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Literal {
+    None,
+    Str,
+    /// A raw string closed by `"` and this many `#`s.
+    RawStr(usize),
+}
+
+struct TemplateParser<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> TemplateParser<'a> {
+    fn new(source: &'a str) -> Self {
+        Self { source, pos: 0 }
+    }
+
+    fn parse(mut self) -> Result<Vec<Node>, TemplateError> {
+        let nodes = self.parse_seq(false)?;
+        debug_assert_eq!(self.pos, self.source.len());
+        Ok(nodes)
+    }
+
+    /// Parse until the end of input, or (inside a repetition) until the `)`
+    /// that closes it. The closing `)` is left for the caller to consume.
+    ///
+    /// Parentheses and quotes inside string, raw string and char literals do
+    /// not count towards nesting, but placeholders inside string literals are
+    /// still substituted.
+    fn parse_seq(&mut self, in_repeat: bool) -> Result<Vec<Node>, TemplateError> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        let mut depth = 0usize;
+        let mut literal = Literal::None;
+
+        while let Some(ch) = self.peek() {
+            match ch {
+                '$' => {
+                    if !text.is_empty() {
+                        nodes.push(Node::Text(std::mem::take(&mut text)));
+                    }
+                    match self.parse_dollar(in_repeat)? {
+                        Some(node) => nodes.push(node),
+                        None => text.push('$'),
+                    }
+                    continue;
+                }
+                '\\' if literal == Literal::Str => {
+                    text.push(ch);
+                    self.pos += 1;
+                    if let Some(escaped) = self.peek() {
+                        text.push(escaped);
+                        self.pos += escaped.len_utf8();
+                    }
+                    continue;
+                }
+                '"' => match literal {
+                    Literal::None => literal = Literal::Str,
+                    Literal::Str => literal = Literal::None,
+                    Literal::RawStr(hashes) => {
+                        let end = self.pos + 1 + hashes;
+                        if self.source[self.pos + 1..].starts_with(&"#".repeat(hashes)) {
+                            text.push_str(&self.source[self.pos..end]);
+                            self.pos = end;
+                            literal = Literal::None;
+                            continue;
+                        }
+                    }
+                },
+                'r' if literal == Literal::None => {
+                    if let Some(hashes) = self.raw_string_start() {
+                        let end = self.pos + hashes + 2;
+                        text.push_str(&self.source[self.pos..end]);
+                        self.pos = end;
+                        literal = Literal::RawStr(hashes);
+                        continue;
+                    }
+                }
+                '\'' if literal == Literal::None => {
+                    if let Some(len) = self.char_literal_len() {
+                        text.push_str(&self.source[self.pos..self.pos + len]);
+                        self.pos += len;
+                        continue;
+                    }
+                }
+                '(' if literal == Literal::None => depth += 1,
+                ')' if literal == Literal::None && in_repeat && depth == 0 => break,
+                ')' if literal == Literal::None => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+
+            text.push(ch);
+            self.pos += ch.len_utf8();
+        }
+
+        if in_repeat && self.peek() != Some(')') {
+            return Err(self.error("unterminated `$(` repetition"));
+        }
+
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+
+        Ok(nodes)
+    }
+
+    /// Parse the construct starting at a `$`. Returns `None` for `$$`.
+    fn parse_dollar(&mut self, in_repeat: bool) -> Result<Option<Node>, TemplateError> {
+        let dollar = self.pos;
+        self.pos += 1;
+
+        match self.peek() {
+            Some('$') => {
+                self.pos += 1;
+                Ok(None)
+            }
+            Some('(') => {
+                if in_repeat {
+                    return Err(TemplateError::Syntax {
+                        offset: dollar,
+                        message: "nested `$( ... )*` repetitions are not supported".to_owned(),
+                    });
+                }
+
+                self.pos += 1;
+                let body = self.parse_seq(true)?;
+                self.pos += 1; // closing `)`
+
+                let rest = &self.source[self.pos..];
+                let Some(star) = rest.find('*').filter(|&star| star <= 2) else {
+                    return Err(
+                        self.error("expected `*` or a separator followed by `*` after `$( ... )`")
+                    );
+                };
+                let separator = &rest[..star];
+                if separator.chars().any(char::is_whitespace) {
+                    return Err(self.error("repetition separators cannot contain whitespace"));
+                }
+
+                let separator = (!separator.is_empty()).then(|| separator.to_owned());
+                self.pos += star + 1;
+                Ok(Some(Node::Repeat { body, separator }))
+            }
+            Some(ch) if ch == '_' || ch.is_ascii_alphabetic() => {
+                let start = self.pos;
+                while let Some(ch) = self.peek() {
+                    if ch == '_' || ch.is_ascii_alphanumeric() {
+                        self.pos += 1;
+                    } else {
+                        break;
+                    }
+                }
+                Ok(Some(Node::Var(self.source[start..self.pos].to_owned())))
+            }
+            _ => Err(TemplateError::Syntax {
+                offset: dollar,
+                message: "expected a placeholder name, `$$`, or `$(` after `$`".to_owned(),
+            }),
+        }
+    }
+
+    /// Number of `#`s if a raw string literal (`r"`, `r#"`, `br"`, ...)
+    /// starts at the `r` at the current position.
+    fn raw_string_start(&self) -> Option<usize> {
+        let before = &self.source[..self.pos];
+        let prefix = &before[before
+            .trim_end_matches(|ch: char| ch == '_' || ch.is_alphanumeric())
+            .len()..];
+        if !matches!(prefix, "" | "b" | "c") {
+            return None;
+        }
+        let rest = &self.source[self.pos + 1..];
+        let hashes = rest.len() - rest.trim_start_matches('#').len();
+        rest[hashes..].starts_with('"').then_some(hashes)
+    }
+
+    /// Length of the char or byte literal whose opening `'` is at the current
+    /// position, or `None` if the `'` starts a lifetime or label.
+    fn char_literal_len(&self) -> Option<usize> {
+        let rest = &self.source[self.pos + 1..];
+        let mut chars = rest.char_indices();
+        let (_, first) = chars.next()?;
+        let close = if first == '\\' {
+            // `'\''`, `'\n'`, `'\u{29}'`: the literal ends at the first `'`
+            // after the escaped character.
+            let (escaped_at, escaped) = chars.next()?;
+            let after = escaped_at + escaped.len_utf8();
+            after + rest[after..].find('\'')?
+        } else {
+            let (after, _) = chars.next()?;
+            rest[after..].starts_with('\'').then_some(after)?
+        };
+        Some(close + 2)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    fn error(&self, message: &str) -> TemplateError {
+        TemplateError::Syntax {
+            offset: self.pos,
+            message: message.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitutes_placeholders_inside_literals() {
+        let rendered = Template::stmt(r#"println!("$name: {}", $name);"#)
+            .ident("name", "total")
+            .render()
+            .unwrap();
+        assert_eq!(rendered, r#"println!("total: {}", total);"#);
+
+        let rendered = Template::item("macro_rules! $name { ($$x:expr) => { $$x }; }")
+            .ident("name", "id")
+            .render()
+            .unwrap();
+        assert_eq!(rendered, "macro_rules! id { ($x:expr) => { $x }; }");
+    }
+
+    #[test]
+    fn parenthesizes_compound_expressions() {
+        let rendered = Template::new(Fragment::Expr, "$a * $b")
+            .expr("a", "x + 1")
+            .expr("b", "y.len()")
+            .render()
+            .unwrap();
+        assert_eq!(rendered, "(x + 1) * y.len()");
+    }
+
+    #[test]
+    fn expands_repetitions() {
+        let rendered = Template::item("fn $name($($arg: $ty),*) {}")
+            .ident("name", "f")
+            .idents("arg", ["a", "b"])
+            .tys("ty", ["u8", "Vec<(i32, i32)>"])
+            .render()
+            .unwrap();
+        assert_eq!(rendered, "fn f(a: u8, b: Vec<(i32, i32)>) {}");
+
+        let err = Template::item("fn f($( $arg: $ty ),*) {}")
+            .idents("arg", ["a", "b"])
+            .tys("ty", ["u8"])
+            .render()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            TemplateError::RepetitionLengthMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn skips_char_and_raw_string_literals() {
+        let push = |template: &str| {
+            Template::stmts(template)
+                .idents("arg", ["a", "b"])
+                .render()
+                .unwrap()
+        };
+        assert_eq!(push("$(f('(', $arg);)*"), "f('(', a);f('(', b);");
+        assert_eq!(push("$(f(')', $arg);)*"), "f(')', a);f(')', b);");
+        assert_eq!(push(r#"$(f('"', $arg);)*"#), r#"f('"', a);f('"', b);"#);
+        assert_eq!(
+            push(r"$(f('\'', b'\\', '\u{29}', $arg);)*"),
+            r"f('\'', b'\\', '\u{29}', a);f('\'', b'\\', '\u{29}', b);"
+        );
+        assert_eq!(
+            push(r##"$(f(r#"(")"#, br")", $arg);)*"##),
+            r##"f(r#"(")"#, br")", a);f(r#"(")"#, br")", b);"##
+        );
+        assert_eq!(push(r##"$(f(r"$arg)");)*"##), r##"f(r"a)");f(r"b)");"##);
+
+        let rendered = Template::items("$(fn $arg<'a>(x: &'a str) -> &'a str { x })*")
+            .idents("arg", ["f", "g"])
+            .render()
+            .unwrap();
+        assert_eq!(
+            rendered,
+            "fn f<'a>(x: &'a str) -> &'a str { x }fn g<'a>(x: &'a str) -> &'a str { x }"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values_and_output() {
+        let err = Template::stmt("let $name = 1;")
+            .ident("name", "not an ident")
+            .render()
+            .unwrap_err();
+        assert!(matches!(err, TemplateError::InvalidValue { .. }));
+
+        let err = Template::stmt("let $name = 1;").render().unwrap_err();
+        assert_eq!(
+            err,
+            TemplateError::Unbound {
+                name: "name".to_owned()
+            }
+        );
+
+        let err = Template::item("fn $name(")
+            .ident("name", "f")
+            .render()
+            .unwrap_err();
+        assert!(matches!(err, TemplateError::Parse { .. }));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::output::notice;
+use crate::replacements::{ChangedFile, SavedReplacement};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How the saved replacements of one file differ from a fresh run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Mismatch {
+    /// The run produced replacements, but none are saved.
+    Missing { path: String, expected: usize },
+    /// Replacements are saved for a file the run leaves unchanged.
+    Unexpected { path: String, saved: usize },
+    /// Both exist but differ.
+    Stale {
+        path: String,
+        expected: usize,
+        saved: usize,
+    },
+}
+
+impl Mismatch {
+    pub fn path(&self) -> &str {
+        match self {
+            Mismatch::Missing { path, .. }
+            | Mismatch::Unexpected { path, .. }
+            | Mismatch::Stale { path, .. } => path,
+        }
+    }
+}
+
+/// Compare the output of a run with what is saved in `target/.forgen/`,
+/// ordered by path.
+pub fn compare_saved(
+    expected: &[ChangedFile],
+    saved: &BTreeMap<String, Vec<SavedReplacement>>,
+) -> Vec<Mismatch> {
+    let expected: BTreeMap<&str, &[SavedReplacement]> = expected
+        .iter()
+        .filter(|file| !file.replacements.is_empty())
+        .map(|file| (file.path.as_str(), file.replacements.as_slice()))
+        .collect();
+    let paths: BTreeSet<&str> = expected
+        .keys()
+        .copied()
+        .chain(saved.keys().map(String::as_str))
+        .collect();
+
+    paths
+        .into_iter()
+        .filter_map(|path| match (expected.get(path), saved.get(path)) {
+            (Some(expected), Some(saved)) if *expected == saved.as_slice() => None,
+            (Some(expected), Some(saved)) => Some(Mismatch::Stale {
+                path: path.to_owned(),
+                expected: expected.len(),
+                saved: saved.len(),
+            }),
+            (Some(expected), None) => Some(Mismatch::Missing {
+                path: path.to_owned(),
+                expected: expected.len(),
+            }),
+            (None, Some(saved)) => Some(Mismatch::Unexpected {
+                path: path.to_owned(),
+                saved: saved.len(),
+            }),
+            (None, None) => None,
+        })
+        .collect()
+}
+
+/// Files whose output differs between two runs on the same sources.
+pub fn nondeterministic_paths(first: &[ChangedFile], second: &[ChangedFile]) -> Vec<String> {
+    let first: BTreeMap<_, _> = first
+        .iter()
+        .map(|file| (file.path.clone(), file.replacements.clone()))
+        .collect();
+    compare_saved(second, &first)
+        .into_iter()
+        .map(|mismatch| mismatch.path().to_owned())
+        .collect()
+}
+
+pub fn print_mismatches(mismatches: &[Mismatch]) {
+    for mismatch in mismatches {
+        match mismatch {
+            Mismatch::Missing { path, expected } => {
+                notice!("   missing     {path} ({expected} replacement(s) not saved)")
+            }
+            Mismatch::Unexpected { path, saved } => {
+                notice!("   unexpected  {path} ({saved} saved replacement(s) no longer generated)")
+            }
+            Mismatch::Stale {
+                path,
+                expected,
+                saved,
+            } => notice!(
+                "   stale       {path} ({saved} saved replacement(s), {expected} generated, contents differ)"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replacement(new_text: &str) -> SavedReplacement {
+        SavedReplacement {
+            index: 0,
+            old_text: "x".to_owned(),
+            new_text: new_text.to_owned(),
+        }
+    }
+
+    fn changed(path: &str, new_text: &str) -> ChangedFile {
+        ChangedFile {
+            path: path.to_owned(),
+            replacements: vec![replacement(new_text)],
+        }
+    }
+
+    #[test]
+    fn reports_missing_unexpected_and_stale_files() {
+        let expected = [
+            changed("src/a.rs", "1"),
+            changed("src/b.rs", "2"),
+            changed("src/c.rs", "3"),
+        ];
+        let saved = BTreeMap::from([
+            ("src/a.rs".to_owned(), vec![replacement("1")]),
+            ("src/c.rs".to_owned(), vec![replacement("old")]),
+            ("src/d.rs".to_owned(), vec![replacement("4")]),
+        ]);
+
+        assert_eq!(
+            compare_saved(&expected, &saved),
+            [
+                Mismatch::Missing {
+                    path: "src/b.rs".to_owned(),
+                    expected: 1
+                },
+                Mismatch::Stale {
+                    path: "src/c.rs".to_owned(),
+                    expected: 1,
+                    saved: 1
+                },
+                Mismatch::Unexpected {
+                    path: "src/d.rs".to_owned(),
+                    saved: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_files_that_differ_between_runs() {
+        let first = [changed("src/a.rs", "1"), changed("src/b.rs", "2")];
+        let second = [changed("src/a.rs", "1"), changed("src/b.rs", "other")];
+        assert_eq!(nondeterministic_paths(&first, &second), ["src/b.rs"]);
+        assert!(nondeterministic_paths(&first, &first).is_empty());
+    }
+}
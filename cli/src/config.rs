@@ -0,0 +1,100 @@
+use anyhow::{bail, Result};
+use forgen_api::{is_valid_plugin_id, PluginFilter};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// The `[workspace.metadata.forgen]` table, if present.
+pub fn forgen_metadata(meta: &cargo_metadata::Metadata) -> Option<&Value> {
+    meta.workspace_metadata.get("forgen")
+}
+
+/// Resolve a file path that can be given on the command line or under
+/// `key` in `[workspace.metadata.forgen]`.
+///
+/// An explicit path (relative to the current directory) wins over the
+/// metadata value (relative to the workspace root). Returns `None` when
+/// neither is set.
+pub fn configured_path(
+    explicit: Option<&Path>,
+    meta: &cargo_metadata::Metadata,
+    key: &str,
+) -> Option<PathBuf> {
+    if let Some(path) = explicit {
+        return Some(path.to_path_buf());
+    }
+
+    forgen_metadata(meta)
+        .and_then(|f| f.get(key))
+        .and_then(|v| v.as_str())
+        .map(|path| meta.workspace_root.as_std_path().join(path))
+}
+
+/// Read an unsigned integer from `[workspace.metadata.forgen]`.
+///
+/// Strings are accepted too, since TOML integers cannot represent the upper
+/// half of the `u64` range.
+pub fn metadata_u64(meta: &cargo_metadata::Metadata, key: &str) -> Result<Option<u64>> {
+    let Some(value) = forgen_metadata(meta).and_then(|f| f.get(key)) else {
+        return Ok(None);
+    };
+
+    match value {
+        Value::Number(n) => match n.as_u64() {
+            Some(n) => Ok(Some(n)),
+            None => bail!("`[workspace.metadata.forgen] {key}` must be a non-negative integer"),
+        },
+        Value::String(s) => match s.parse() {
+            Ok(n) => Ok(Some(n)),
+            Err(_) => bail!("`[workspace.metadata.forgen] {key} = \"{s}\"` is not a valid u64"),
+        },
+        _ => bail!("`[workspace.metadata.forgen] {key}` must be an integer"),
+    }
+}
+
+/// Read a list of strings from `[workspace.metadata.forgen]`.
+pub fn metadata_strings(meta: &cargo_metadata::Metadata, key: &str) -> Result<Vec<String>> {
+    let Some(value) = forgen_metadata(meta).and_then(|f| f.get(key)) else {
+        return Ok(Vec::new());
+    };
+
+    let Value::Array(items) = value else {
+        bail!("`[workspace.metadata.forgen] {key}` must be an array of strings");
+    };
+    items
+        .iter()
+        .map(|item| match item {
+            Value::String(s) => Ok(s.clone()),
+            other => {
+                bail!("`[workspace.metadata.forgen] {key}` contains {other}, expected a string")
+            }
+        })
+        .collect()
+}
+
+/// Build the plugin filter from `--only` / `--skip` and the `disabled` list
+/// in `[workspace.metadata.forgen]`.
+///
+/// `--only` wins over `disabled`, so a disabled plugin can still be run on
+/// its own while debugging it.
+pub fn plugin_filter(
+    only: &[String],
+    skip: &[String],
+    meta: &cargo_metadata::Metadata,
+) -> Result<PluginFilter> {
+    let only: BTreeSet<String> = only.iter().cloned().collect();
+    let mut skip: BTreeSet<String> = skip.iter().cloned().collect();
+    skip.extend(
+        metadata_strings(meta, "disabled")?
+            .into_iter()
+            .filter(|plugin_id| !only.contains(plugin_id)),
+    );
+
+    for plugin_id in only.iter().chain(&skip) {
+        if !is_valid_plugin_id(plugin_id) {
+            bail!("`{plugin_id}` is not a valid plugin id");
+        }
+    }
+
+    Ok(PluginFilter { only, skip })
+}
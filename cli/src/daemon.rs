@@ -0,0 +1,256 @@
+use crate::diagnostics::SavedDiagnostic;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+#[cfg(unix)]
+use {
+    anyhow::{bail, Context, Result},
+    std::fs,
+    std::io::{BufRead, BufReader, Write},
+    std::os::unix::net::{UnixListener, UnixStream},
+    std::path::Path,
+    std::sync::mpsc::Sender,
+    std::thread,
+};
+
+/// One line a client sends. Clients write one JSON request per line to the
+/// daemon's socket and read one [`Response`] line for each, in order; requests
+/// from all connections are handled one at a time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub enum Request {
+    /// Bring `target/.forgen/` up to date and answer when it is.
+    Run {
+        /// Run even if no source changed since the last successful run.
+        #[serde(default)]
+        force: bool,
+    },
+    Status,
+    /// The diagnostics of the last run.
+    Diagnostics,
+    Shutdown,
+}
+
+/// One line the daemon answers with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum Response {
+    Run {
+        /// `false` when the output was already fresh; the other fields then
+        /// describe the last run.
+        ran: bool,
+        /// Workspace-relative paths of the files the plugins changed.
+        changed_files: Vec<String>,
+        /// Changed files without a matching `#![forgen::file(...)]`.
+        unadopted: Vec<String>,
+        /// Error diagnostics plus plugins that panicked.
+        errors: usize,
+        elapsed_ms: f64,
+    },
+    Status {
+        pid: u32,
+        workspace_root: String,
+        /// Plugin runs since the daemon started, including the first one.
+        runs: usize,
+        /// The last run succeeded and no source changed since.
+        fresh: bool,
+        /// Why the last run failed, if it did.
+        last_error: Option<String>,
+    },
+    Diagnostics {
+        diagnostics: Vec<SavedDiagnostic>,
+    },
+    ShuttingDown,
+    Error {
+        message: String,
+    },
+}
+
+/// Where the daemon of the workspace described by `meta` listens by default.
+pub fn default_socket_path(meta: &cargo_metadata::Metadata) -> PathBuf {
+    meta.target_directory
+        .as_std_path()
+        .join("forgen-daemon.sock")
+}
+
+// ---------------------------------------------------------------------------
+// Server
+// ---------------------------------------------------------------------------
+
+/// Where the answer to a request goes.
+#[cfg(unix)]
+pub struct Reply {
+    stream: UnixStream,
+}
+
+#[cfg(unix)]
+impl Reply {
+    /// Write `response` as one line. A client that hung up is not an error.
+    pub fn send(mut self, response: &Response) {
+        let line = serde_json::to_string(response).expect("responses serialize to JSON");
+        let _ = writeln!(self.stream, "{line}");
+        let _ = self.stream.flush();
+    }
+}
+
+/// A bound socket that hands requests from every connection to one thread.
+/// The socket file is removed on drop.
+#[cfg(unix)]
+pub struct Server {
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl Server {
+    /// Listen on `path` and send every request, with where to answer it, to
+    /// `requests`. Replaces a stale socket left by a daemon that did not shut
+    /// down cleanly.
+    pub fn bind<M>(path: &Path, requests: Sender<M>) -> Result<Self>
+    where
+        M: From<(Request, Reply)> + Send + 'static,
+    {
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                bail!("A forgen daemon is already listening on {}", path.display());
+            }
+            fs::remove_file(path)
+                .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("Failed to listen on {}", path.display()))?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let requests = requests.clone();
+                thread::spawn(move || read_requests(stream, &requests));
+            }
+        });
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Forward the requests of one connection in the order they were sent.
+#[cfg(unix)]
+fn read_requests<M: From<(Request, Reply)>>(stream: UnixStream, requests: &Sender<M>) {
+    let Ok(reader) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Ok(writer) = stream.try_clone() else {
+            return;
+        };
+        let reply = Reply { stream: writer };
+        match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                if requests.send((request, reply).into()).is_err() {
+                    return;
+                }
+            }
+            Err(e) => reply.send(&Response::Error {
+                message: format!("Invalid request: {e}"),
+            }),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Client
+// ---------------------------------------------------------------------------
+
+/// Send `request` to the daemon on `socket` and return its answer, both as
+/// the raw JSON line and parsed.
+#[cfg(unix)]
+pub fn request(socket: &Path, request: &Request) -> Result<(String, Response)> {
+    let mut stream = UnixStream::connect(socket).with_context(|| {
+        format!(
+            "No forgen daemon is listening on {}; start one with `cargo forgen daemon`",
+            socket.display()
+        )
+    })?;
+    let line = serde_json::to_string(request)?;
+    writeln!(stream, "{line}").context("Failed to send the request to the daemon")?;
+
+    let mut answer = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut answer)
+        .context("Failed to read the daemon's response")?;
+    if answer.is_empty() {
+        bail!("The daemon closed the connection without answering");
+    }
+    let response = serde_json::from_str(&answer)
+        .with_context(|| format!("Invalid response from the daemon: {}", answer.trim_end()))?;
+    Ok((answer.trim_end().to_owned(), response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_and_responses_use_the_documented_json() {
+        let parse = |line: &str| serde_json::from_str::<Request>(line).unwrap();
+        assert_eq!(parse(r#"{"request":"run"}"#), Request::Run { force: false });
+        assert_eq!(
+            parse(r#"{"request":"run","force":true}"#),
+            Request::Run { force: true }
+        );
+        assert_eq!(parse(r#"{"request":"status"}"#), Request::Status);
+        assert_eq!(parse(r#"{"request":"shutdown"}"#), Request::Shutdown);
+
+        assert_eq!(
+            serde_json::to_value(Response::ShuttingDown).unwrap(),
+            serde_json::json!({ "response": "shutting_down" })
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn answers_requests_over_the_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("daemon.sock");
+        let (tx, requests) = std::sync::mpsc::channel::<(Request, Reply)>();
+        let server = Server::bind(&socket, tx.clone()).unwrap();
+        assert!(
+            Server::bind(&socket, tx).is_err(),
+            "second daemon on one socket"
+        );
+
+        let client = {
+            let socket = socket.clone();
+            thread::spawn(move || request(&socket, &Request::Status).unwrap())
+        };
+        let (request, reply) = requests.recv().unwrap();
+        assert_eq!(request, Request::Status);
+        reply.send(&Response::Error {
+            message: "not yet".to_owned(),
+        });
+        let (line, response) = client.join().unwrap();
+        assert_eq!(line, r#"{"response":"error","message":"not yet"}"#);
+        assert!(matches!(response, Response::Error { message } if message == "not yet"));
+
+        drop(server);
+        assert!(!socket.exists());
+    }
+}
@@ -0,0 +1,132 @@
+//! Runs rust-analyzer work on the thread that owns the database.
+//!
+//! The database is attached to the thread that runs `attach_db_allow_change`
+//! in `run_plugins`, and rust-analyzer queries must not run anywhere else.
+//! When the suite runs plugins on worker threads, lazy file data and semantic
+//! queries are therefore sent back to that thread, which executes them while
+//! it waits for the suite in [`DbThread::serve`].
+
+use forgen_api::LazyValue;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, ThreadId};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+enum Message {
+    Job(Job),
+    /// The work passed to [`DbThread::serve`] returned or panicked.
+    Done,
+}
+
+/// The receiving end, owned by the database thread.
+pub(crate) struct DbThread {
+    executor: DbExecutor,
+    jobs: Receiver<Message>,
+}
+
+/// Cloneable handle that runs closures on the database thread.
+#[derive(Clone)]
+pub(crate) struct DbExecutor {
+    owner: ThreadId,
+    jobs: Sender<Message>,
+}
+
+impl DbThread {
+    /// Make the current thread the database thread.
+    pub(crate) fn new() -> Self {
+        let (sender, jobs) = mpsc::channel();
+        Self {
+            executor: DbExecutor {
+                owner: thread::current().id(),
+                jobs: sender,
+            },
+            jobs,
+        }
+    }
+
+    pub(crate) fn executor(&self) -> DbExecutor {
+        self.executor.clone()
+    }
+
+    /// Run `work` on a helper thread and execute database jobs on the current
+    /// thread until it finishes. Panics in `work` are resumed here.
+    pub(crate) fn serve<T, F>(&self, work: F) -> T
+    where
+        T: Send,
+        F: FnOnce() -> T + Send,
+    {
+        assert_eq!(
+            thread::current().id(),
+            self.executor.owner,
+            "DbThread::serve must run on the database thread"
+        );
+
+        thread::scope(|scope| {
+            let done = DoneGuard(self.executor.jobs.clone());
+            let handle = scope.spawn(move || {
+                let _done = done;
+                work()
+            });
+
+            while let Ok(Message::Job(job)) = self.jobs.recv() {
+                job();
+            }
+
+            match handle.join() {
+                Ok(value) => value,
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        })
+    }
+}
+
+impl DbExecutor {
+    /// Run `job` on the database thread and wait for its result. Runs inline
+    /// when called on the database thread itself.
+    pub(crate) fn run<T, F>(&self, job: F) -> T
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        if thread::current().id() == self.owner {
+            return job();
+        }
+
+        let (reply, result) = mpsc::sync_channel(1);
+        let job: Job = Box::new(move || {
+            let _ = reply.send(panic::catch_unwind(AssertUnwindSafe(job)));
+        });
+        self.jobs
+            .send(Message::Job(job))
+            .expect("rust-analyzer database thread is gone");
+
+        match result
+            .recv()
+            .expect("rust-analyzer database thread dropped a job")
+        {
+            Ok(value) => value,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// A lazy value whose initializer runs on the database thread.
+    pub(crate) fn lazy<T, F>(&self, init: F) -> LazyValue<T>
+    where
+        T: Send + 'static,
+        F: Fn() -> T + Clone + Send + Sync + 'static,
+    {
+        let executor = self.clone();
+        LazyValue::new(move || executor.run(init.clone()))
+    }
+}
+
+/// Tells [`DbThread::serve`] to stop once the helper thread is finished,
+/// including when it unwinds.
+struct DoneGuard(Sender<Message>);
+
+impl Drop for DoneGuard {
+    fn drop(&mut self) {
+        let _ = self.0.send(Message::Done);
+    }
+}
@@ -0,0 +1,401 @@
+use crate::output::{self, info, notice, Event};
+use crate::replacements::FileModel;
+use anyhow::{Context, Result};
+use forgen_api::{saved, Diagnostic, LineIndex, Severity};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A plugin diagnostic mapped back to the original on-disk source.
+///
+/// This is the format of `--diagnostics-json` and of the per-file
+/// `target/.forgen/<mirrored-path>.diagnostics.json` that `forgen::file`
+/// re-emits during `cargo build`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SavedDiagnostic {
+    pub plugin_id: String,
+    pub severity: Severity,
+    pub file: String,
+    /// Byte range in the original source.
+    pub start: usize,
+    pub end: usize,
+    /// 1-based line of `start`.
+    pub line: usize,
+    /// 1-based column of `start`, counted in characters.
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub message: String,
+    /// The plugin pointed into text generated earlier in the run; the location
+    /// is where that text is inserted.
+    pub generated: bool,
+}
+
+/// Where to export the diagnostics of a run in addition to printing them.
+#[derive(Debug, Clone, Default)]
+pub struct Exports {
+    pub json: Option<PathBuf>,
+    pub sarif: Option<PathBuf>,
+}
+
+/// The latest diagnostics of every plugin during one `cargo forgen` run.
+///
+/// A plugin's diagnostics from one pass replace those of its earlier passes,
+/// so only what it reported on the converged sources is kept.
+#[derive(Debug, Default)]
+pub struct Collector {
+    by_plugin: BTreeMap<String, Vec<SavedDiagnostic>>,
+}
+
+impl Collector {
+    /// Record what plugins reported in one pass. `file_models` must still
+    /// describe the sources that pass ran on, i.e. this has to be called
+    /// before the pass's replacements are applied to them.
+    pub fn record(
+        &mut self,
+        reported: HashMap<String, Vec<Diagnostic>>,
+        file_models: &HashMap<String, FileModel>,
+    ) {
+        let mut line_indexes: HashMap<String, LineIndex> = HashMap::new();
+
+        for (plugin_id, diagnostics) in reported {
+            let mut mapped = Vec::with_capacity(diagnostics.len());
+            for diagnostic in diagnostics {
+                let Some(model) = file_models.get(&diagnostic.file) else {
+                    output::warn(format_args!(
+                        "plugin `{plugin_id}` reported a diagnostic for unknown file `{}`; ignoring it",
+                        diagnostic.file
+                    ));
+                    continue;
+                };
+                let index = line_indexes
+                    .entry(diagnostic.file.clone())
+                    .or_insert_with(|| LineIndex::new(model.original()));
+                mapped.push(map_diagnostic(diagnostic, model, index));
+            }
+            self.by_plugin.insert(plugin_id, mapped);
+        }
+    }
+
+    /// All diagnostics ordered by file and position.
+    pub fn diagnostics(&self) -> Vec<&SavedDiagnostic> {
+        let mut all: Vec<_> = self.by_plugin.values().flatten().collect();
+        all.sort_by(|a, b| {
+            (&a.file, a.start, a.end, &a.plugin_id).cmp(&(&b.file, b.start, b.end, &b.plugin_id))
+        });
+        all
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.by_plugin
+            .values()
+            .flatten()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+
+    /// Print and export the collected diagnostics, and save them to
+    /// `target/.forgen/` of `saved_to` if given. Saving must happen after the
+    /// final replacement JSON is written, which clears `target/.forgen/`.
+    /// Returns the number of errors.
+    pub fn finish(
+        &self,
+        saved_to: Option<&Path>,
+        exports: &Exports,
+        original_sources: &HashMap<String, String>,
+    ) -> Result<usize> {
+        self.print(original_sources);
+        if let Some(workspace_root) = saved_to {
+            self.save(workspace_root)?;
+        }
+
+        if let Some(path) = &exports.json {
+            write_file(path, &serde_json::to_string_pretty(&self.diagnostics())?)?;
+            info!("📝 Wrote diagnostics JSON to {}", path.display());
+        }
+        if let Some(path) = &exports.sarif {
+            let sarif = self.to_sarif(original_sources);
+            write_file(path, &serde_json::to_string_pretty(&sarif)?)?;
+            info!("📝 Wrote SARIF diagnostics to {}", path.display());
+        }
+
+        Ok(self.count(Severity::Error))
+    }
+
+    fn print(&self, original_sources: &HashMap<String, String>) {
+        let diagnostics = self.diagnostics();
+        for diagnostic in &diagnostics {
+            output::emit(&Event::Diagnostic { diagnostic });
+        }
+        if diagnostics.is_empty() {
+            return;
+        }
+
+        notice!();
+        for diagnostic in &diagnostics {
+            let icon = match diagnostic.severity {
+                Severity::Warning => "⚠️ ",
+                Severity::Error => "❌",
+            };
+            notice!(
+                "{icon} {}[{}]: {}",
+                diagnostic.severity,
+                diagnostic.plugin_id,
+                diagnostic.message
+            );
+            notice!(
+                "   --> {}:{}:{}{}",
+                diagnostic.file,
+                diagnostic.line,
+                diagnostic.column,
+                if diagnostic.generated {
+                    " (in generated code)"
+                } else {
+                    ""
+                }
+            );
+
+            let source_line = original_sources
+                .get(&diagnostic.file)
+                .and_then(|source| source.lines().nth(diagnostic.line - 1));
+            if let Some(source_line) = source_line {
+                let width = if diagnostic.end_line == diagnostic.line {
+                    diagnostic
+                        .end_column
+                        .saturating_sub(diagnostic.column)
+                        .max(1)
+                } else {
+                    1
+                };
+                notice!("    |");
+                notice!("    | {source_line}");
+                notice!(
+                    "    | {}{}",
+                    " ".repeat(diagnostic.column - 1),
+                    "^".repeat(width)
+                );
+            }
+        }
+
+        notice!(
+            "\n🩺 Plugins reported {} error(s) and {} warning(s)",
+            self.count(Severity::Error),
+            self.count(Severity::Warning)
+        );
+    }
+
+    /// Write `target/.forgen/<mirrored-path>.diagnostics.json` for every file
+    /// with diagnostics.
+    fn save(&self, workspace_root: &Path) -> Result<()> {
+        let mut by_file: BTreeMap<&str, Vec<&SavedDiagnostic>> = BTreeMap::new();
+        for diagnostic in self.diagnostics() {
+            by_file
+                .entry(diagnostic.file.as_str())
+                .or_default()
+                .push(diagnostic);
+        }
+
+        for (file, diagnostics) in by_file {
+            let path = saved::diagnostics_path(workspace_root, file);
+            write_file(&path, &serde_json::to_string_pretty(&diagnostics)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// SARIF 2.1.0 log with one rule per plugin. Columns are UTF-16 code
+    /// units, the SARIF default.
+    fn to_sarif(&self, original_sources: &HashMap<String, String>) -> serde_json::Value {
+        let diagnostics = self.diagnostics();
+        let line_indexes: HashMap<&str, LineIndex> = diagnostics
+            .iter()
+            .filter_map(|diagnostic| {
+                let source = original_sources.get(&diagnostic.file)?;
+                Some((diagnostic.file.as_str(), LineIndex::new(source)))
+            })
+            .collect();
+
+        let results: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let mut region = json!({
+                    "startLine": diagnostic.line,
+                    "startColumn": diagnostic.column,
+                    "endLine": diagnostic.end_line,
+                    "endColumn": diagnostic.end_column,
+                });
+                if let Some(index) = line_indexes.get(diagnostic.file.as_str()) {
+                    let start = index.line_col_utf16(diagnostic.start as u32);
+                    let end = index.line_col_utf16(diagnostic.end as u32);
+                    region["startColumn"] = json!(start.col + 1);
+                    region["endColumn"] = json!(end.col + 1);
+                }
+
+                json!({
+                    "ruleId": diagnostic.plugin_id,
+                    "level": diagnostic.severity.to_string(),
+                    "message": { "text": diagnostic.message },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": diagnostic.file },
+                            "region": region,
+                        }
+                    }],
+                })
+            })
+            .collect();
+
+        let rules: Vec<_> = self
+            .by_plugin
+            .keys()
+            .map(|plugin_id| json!({ "id": plugin_id }))
+            .collect();
+
+        json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "cargo-forgen",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules,
+                    }
+                },
+                "results": results,
+            }],
+        })
+    }
+}
+
+fn map_diagnostic(diagnostic: Diagnostic, model: &FileModel, index: &LineIndex) -> SavedDiagnostic {
+    let (start, start_generated) = model.original_offset(diagnostic.range.start as usize);
+    let (end, end_generated) = model.original_offset(diagnostic.range.end as usize);
+    let end = end.max(start);
+
+    let (line, column) = char_line_col(model.original(), index, start);
+    let (end_line, end_column) = char_line_col(model.original(), index, end);
+
+    SavedDiagnostic {
+        plugin_id: diagnostic.plugin_id,
+        severity: diagnostic.severity,
+        file: diagnostic.file,
+        start,
+        end,
+        line,
+        column,
+        end_line,
+        end_column,
+        message: diagnostic.message,
+        generated: start_generated || end_generated,
+    }
+}
+
+/// 1-based line and character column of `offset`, matching what
+/// `proc_macro::Span::line` / `column` report.
+fn char_line_col(source: &str, index: &LineIndex, offset: usize) -> (usize, usize) {
+    let line = index.line_of(offset as u32);
+    let line_start = index.line_start(line).unwrap_or(0) as usize;
+    let column = source
+        .get(line_start..offset)
+        .map_or(0, |prefix| prefix.chars().count());
+    (line as usize + 1, column + 1)
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replacements;
+    use forgen_api::{Replacement, TextRange};
+
+    fn diagnostic(file: &str, start: u32, end: u32) -> Diagnostic {
+        Diagnostic {
+            plugin_id: "demo".to_owned(),
+            severity: Severity::Warning,
+            file: file.to_owned(),
+            range: TextRange::new(start, end),
+            message: "look here".to_owned(),
+        }
+    }
+
+    #[test]
+    fn maps_ranges_in_generated_text_to_the_insertion_point() {
+        let source = "fn main() {\n    run();\n}\n";
+        let sources = HashMap::from([("src/main.rs".to_owned(), source.to_owned())]);
+        let mut models = replacements::build_file_models(&sources);
+        let model = models.get_mut("src/main.rs").unwrap();
+        let generated = "\n    let x = 1;";
+        replacements::apply_replacements_to_file_model(
+            model,
+            source,
+            &[Replacement::insert(11, generated.to_owned())],
+        )
+        .unwrap();
+        let index = LineIndex::new(source);
+
+        // `x` in the inserted `let`.
+        let mapped = map_diagnostic(diagnostic("src/main.rs", 20, 21), model, &index);
+        assert_eq!((mapped.start, mapped.end), (11, 11));
+        assert_eq!((mapped.line, mapped.column), (1, 12));
+        assert!(mapped.generated);
+
+        // `run` moved down by the inserted text.
+        let run = 16 + generated.len() as u32;
+        let mapped = map_diagnostic(diagnostic("src/main.rs", run, run + 3), model, &index);
+        assert_eq!((mapped.start, mapped.end), (16, 19));
+        assert_eq!((mapped.line, mapped.column), (2, 5));
+        assert_eq!((mapped.end_line, mapped.end_column), (2, 8));
+        assert!(!mapped.generated);
+    }
+
+    #[test]
+    fn columns_count_characters_not_bytes() {
+        let source = "let a = 1;\nlet é = \"ü\";\n";
+        let index = LineIndex::new(source);
+        let equals = source.find('=').unwrap();
+        assert_eq!(char_line_col(source, &index, equals), (1, 7));
+
+        let equals = source.rfind('=').unwrap();
+        assert_eq!(char_line_col(source, &index, equals), (2, 7));
+        let end = source.rfind(';').unwrap();
+        assert_eq!(char_line_col(source, &index, end), (2, 12));
+    }
+
+    #[test]
+    fn sarif_columns_are_utf16_code_units() {
+        let source = "let s = \"😀\"; bad();\n";
+        let sources = HashMap::from([("src/lib.rs".to_owned(), source.to_owned())]);
+        let models = replacements::build_file_models(&sources);
+        let start = source.find("bad").unwrap() as u32;
+
+        let mut collector = Collector::default();
+        collector.record(
+            HashMap::from([(
+                "demo".to_owned(),
+                vec![diagnostic("src/lib.rs", start, start + 3)],
+            )]),
+            &models,
+        );
+        let saved = collector.diagnostics();
+        assert_eq!((saved[0].column, saved[0].end_column), (14, 17));
+
+        let sarif = collector.to_sarif(&sources);
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "demo");
+        assert_eq!(result["level"], "warning");
+        let region = &result["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(region["startLine"], 1);
+        assert_eq!(region["startColumn"], 15);
+        assert_eq!(region["endColumn"], 18);
+    }
+}
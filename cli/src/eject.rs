@@ -0,0 +1,212 @@
+use crate::setup::FORGEN_FEATURES;
+use crate::{expand, replacements, review};
+use anyhow::{bail, Context, Result};
+use forgen_api::parse_generated_regions;
+use forgen_api::saved::remove_forgen_attr_line;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Only eject the output of these plugins; the regions of other plugins
+    /// keep their markers and stay managed by forgen.
+    pub plugins: Vec<String>,
+    /// Also remove `#![forgen::file(...)]` and forgen's feature gates.
+    pub remove_attr: bool,
+    /// Overwrite files with uncommitted changes.
+    pub force: bool,
+}
+
+/// Write the converged source of `files` (every file with saved output when
+/// empty) back to disk and drop their saved replacements.
+pub fn run(workspace_root: &Path, files: &[String], options: &Options) -> Result<()> {
+    if options.remove_attr && !options.plugins.is_empty() {
+        bail!(
+            "--remove-attr cannot be combined with --plugin: the output of other plugins would \
+             lose `forgen::file`"
+        );
+    }
+
+    let files = if files.is_empty() {
+        replacements::load_saved_replacements(workspace_root)?
+            .into_keys()
+            .collect()
+    } else {
+        files.to_vec()
+    };
+
+    let mut ejected = Vec::new();
+    for rel_path in files {
+        let Some(converged) = expand::converged_source(workspace_root, &rel_path)? else {
+            println!("⏭  {rel_path}: no saved output");
+            continue;
+        };
+        let Some(source) = eject_source(&converged, &rel_path, options) else {
+            println!("⏭  {rel_path}: no output of the selected plugin(s)");
+            continue;
+        };
+        ejected.push((rel_path, source));
+    }
+
+    if ejected.is_empty() {
+        println!("Nothing to eject");
+        return Ok(());
+    }
+
+    if !options.force {
+        let paths: Vec<&str> = ejected.iter().map(|(path, _)| path.as_str()).collect();
+        let dirty = uncommitted_changes(workspace_root, &paths)?;
+        if !dirty.is_empty() {
+            bail!(
+                "refusing to overwrite files with uncommitted changes: {}\n\
+                 Commit or stash them first, or pass --force.",
+                dirty.join(", ")
+            );
+        }
+    }
+
+    let out_root = workspace_root.join("target").join(".forgen");
+    for (rel_path, source) in &ejected {
+        let path = workspace_root.join(rel_path);
+        fs::write(&path, source).with_context(|| format!("Failed to write {}", path.display()))?;
+
+        // The saved replacements describe the old source and would no longer
+        // apply to the ejected one.
+        for suffix in [".json", ".diagnostics.json"] {
+            let saved = replacements::mirrored_output_path(&out_root, rel_path, suffix)?;
+            if saved.exists() {
+                fs::remove_file(&saved)
+                    .with_context(|| format!("Failed to remove {}", saved.display()))?;
+            }
+        }
+        println!("📤 Ejected {rel_path}");
+    }
+
+    println!(
+        "\n✅ Ejected {} file(s). Run `cargo forgen` again to refresh target/.forgen/.",
+        ejected.len()
+    );
+    Ok(())
+}
+
+/// What to write for one file, or `None` when `--plugin` was given and none
+/// of those plugins generated anything in it.
+fn eject_source(converged: &str, rel_path: &str, options: &Options) -> Option<String> {
+    let selected = |plugin_id: &str| {
+        options.plugins.is_empty() || options.plugins.iter().any(|p| p == plugin_id)
+    };
+    if !options.plugins.is_empty()
+        && !parse_generated_regions(converged)
+            .iter()
+            .any(|region| selected(&region.plugin_id))
+    {
+        return None;
+    }
+
+    let source = review::strip_markers_where(converged, selected);
+    if !options.remove_attr {
+        return Some(source);
+    }
+    let source = remove_forgen_attr_line(&source, rel_path).unwrap_or(source);
+    Some(remove_forgen_features(&source))
+}
+
+/// Drop forgen's features from every `#![feature(...)]` line, and the line
+/// itself when nothing is left.
+fn remove_forgen_features(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    for line in source.split_inclusive('\n') {
+        let trimmed = line.trim();
+        let Some(list) = trimmed
+            .strip_prefix("#![feature(")
+            .and_then(|rest| rest.strip_suffix(")]"))
+        else {
+            out.push_str(line);
+            continue;
+        };
+
+        let kept: Vec<&str> = list
+            .split(',')
+            .map(str::trim)
+            .filter(|feature| !feature.is_empty() && !FORGEN_FEATURES.contains(feature))
+            .collect();
+        if kept.is_empty() {
+            continue;
+        }
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let newline = &line[line.trim_end().len()..];
+        out.push_str(&format!(
+            "{indent}#![feature({})]{newline}",
+            kept.join(", ")
+        ));
+    }
+    out
+}
+
+/// `git status --porcelain` entries for `paths`. Fails when git cannot tell,
+/// e.g. outside a repository.
+fn uncommitted_changes(workspace_root: &Path, paths: &[&str]) -> Result<Vec<String>> {
+    let output = Command::new("git")
+        .args(["status", "--porcelain", "--"])
+        .args(paths)
+        .current_dir(workspace_root)
+        .output()
+        .context("Failed to run `git status`; pass --force to eject anyway")?;
+    if !output.status.success() {
+        bail!(
+            "`git status` failed: {}Pass --force to eject anyway.",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.get(3..))
+        .map(str::to_owned)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONVERGED: &str = "#![feature(custom_inner_attributes, prelude_import, never_type)]\n\
+        #![forgen::file(\"src/lib.rs\")]\n\
+        fn a() { /*#start:one:1*/x();/*#end:one:1*/ /*#start:two:2*/y();/*#end:two:2*/ }\n";
+
+    #[test]
+    fn strips_markers_of_selected_plugins_only() {
+        let options = Options {
+            plugins: vec!["one".to_owned()],
+            ..Options::default()
+        };
+        let source = eject_source(CONVERGED, "src/lib.rs", &options).unwrap();
+        assert!(source.contains("fn a() { x(); /*#start:two:2*/y();/*#end:two:2*/ }"));
+        assert!(source.contains("#![forgen::file"));
+
+        let options = Options {
+            plugins: vec!["three".to_owned()],
+            ..Options::default()
+        };
+        assert_eq!(eject_source(CONVERGED, "src/lib.rs", &options), None);
+    }
+
+    #[test]
+    fn removes_attribute_and_feature_gates() {
+        let options = Options {
+            remove_attr: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            eject_source(CONVERGED, "src/lib.rs", &options).unwrap(),
+            "#![feature(never_type)]\nfn a() { x(); y(); }\n"
+        );
+        assert_eq!(
+            remove_forgen_features(
+                "#![feature(custom_inner_attributes, prelude_import)]\nfn a() {}\n"
+            ),
+            "fn a() {}\n"
+        );
+    }
+}
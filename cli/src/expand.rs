@@ -0,0 +1,310 @@
+use crate::output;
+use crate::review::strip_generated_markers;
+use anyhow::{anyhow, Context, Result};
+use forgen_api::saved::{
+    apply_resolved_replacements, remove_forgen_attr_line, replacement_path,
+    resolve_saved_replacements, SavedReplacement,
+};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Color {
+    /// Highlight when stdout is a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    fn enabled(self) -> bool {
+        match self {
+            Color::Auto => {
+                std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    pub strip_markers: bool,
+    pub color: Color,
+}
+
+/// The source `forgen::file` compiles for `rel_path`: [`converged_source`]
+/// with the `#![forgen::file(...)]` line removed.
+pub fn expanded_source(workspace_root: &Path, rel_path: &str) -> Result<Option<String>> {
+    let Some(rewritten) = converged_source(workspace_root, rel_path)? else {
+        return Ok(None);
+    };
+    let expanded = remove_forgen_attr_line(&rewritten, rel_path).ok_or_else(|| {
+        anyhow!("`{rel_path}` has no `#![forgen::file(\"{rel_path}\")]` line after rewriting")
+    })?;
+    Ok(Some(expanded))
+}
+
+/// The file on disk with its saved replacements from `target/.forgen/`
+/// applied. `None` when nothing is saved for it.
+pub fn converged_source(workspace_root: &Path, rel_path: &str) -> Result<Option<String>> {
+    let source_path = workspace_root.join(rel_path);
+    let source = fs::read_to_string(&source_path)
+        .with_context(|| format!("Failed to read {}", source_path.display()))?;
+
+    let json_path = replacement_path(workspace_root, rel_path);
+    if !json_path.exists() {
+        return Ok(None);
+    }
+    let json = fs::read_to_string(&json_path)
+        .with_context(|| format!("Failed to read {}", json_path.display()))?;
+    let saved: Vec<SavedReplacement> = serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse {}", json_path.display()))?;
+    if saved.is_empty() {
+        return Ok(None);
+    }
+
+    let resolved = resolve_saved_replacements(&source, &saved)
+        .map_err(|e| anyhow!("{}: {e}", json_path.display()))?;
+    let rewritten = apply_resolved_replacements(&source, &resolved)
+        .map_err(|e| anyhow!("{}: {e}", json_path.display()))?;
+    Ok(Some(rewritten))
+}
+
+/// Print the expansion of `rel_path`, or the file itself when nothing is
+/// saved for it.
+pub fn print(workspace_root: &Path, rel_path: &str, options: Options) -> Result<()> {
+    let source = match expanded_source(workspace_root, rel_path)? {
+        Some(expanded) => expanded,
+        None => {
+            output::warn(format_args!(
+                "no saved replacements for `{rel_path}`; printing it unchanged (run `cargo forgen` first?)"
+            ));
+            let path = workspace_root.join(rel_path);
+            fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?
+        }
+    };
+
+    let source = if options.strip_markers {
+        strip_generated_markers(&source)
+    } else {
+        source
+    };
+
+    if options.color.enabled() {
+        print!("{}", highlight(&source));
+    } else {
+        print!("{source}");
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Syntax highlighting
+// ---------------------------------------------------------------------------
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+const RESET: &str = "\x1b[0m";
+const KEYWORD: &str = "\x1b[35m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const COMMENT: &str = "\x1b[90m";
+const MARKER: &str = "\x1b[2;36m";
+const LIFETIME: &str = "\x1b[36m";
+
+/// ANSI-colour keywords, literals, comments and generated-region markers.
+/// A small lexer is enough here: the output only has to look right.
+pub fn highlight(source: &str) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let mut out = String::with_capacity(source.len() * 2);
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (end, color) = if c == '/' && next == Some('/') {
+            (line_end(&chars, i), Some(COMMENT))
+        } else if c == '/' && next == Some('*') {
+            let end = block_comment_end(&chars, i);
+            let marker = chars.get(i + 2) == Some(&'#');
+            (end, Some(if marker { MARKER } else { COMMENT }))
+        } else if c == '"' || (c == 'b' && next == Some('"')) {
+            let open = if c == 'b' { i + 1 } else { i };
+            (string_end(&chars, open), Some(STRING))
+        } else if let Some(end) = raw_string_end(&chars, i) {
+            (end, Some(STRING))
+        } else if c == '\'' {
+            match char_literal_end(&chars, i) {
+                Some(end) => (end, Some(STRING)),
+                None => (ident_end(&chars, i + 1), Some(LIFETIME)),
+            }
+        } else if c.is_ascii_digit() {
+            (ident_end(&chars, i), Some(NUMBER))
+        } else if c.is_alphabetic() || c == '_' {
+            let end = ident_end(&chars, i);
+            let word: String = chars[i..end].iter().collect();
+            (end, KEYWORDS.contains(&word.as_str()).then_some(KEYWORD))
+        } else {
+            (i + 1, None)
+        };
+
+        let text: String = chars[i..end].iter().collect();
+        match color {
+            Some(color) => {
+                out.push_str(color);
+                out.push_str(&text);
+                out.push_str(RESET);
+            }
+            None => out.push_str(&text),
+        }
+        i = end;
+    }
+    out
+}
+
+fn line_end(chars: &[char], start: usize) -> usize {
+    chars[start..]
+        .iter()
+        .position(|&c| c == '\n')
+        .map_or(chars.len(), |offset| start + offset)
+}
+
+fn block_comment_end(chars: &[char], start: usize) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('/', Some('*')) => {
+                depth += 1;
+                i += 2;
+            }
+            ('*', Some('/')) => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+fn string_end(chars: &[char], open: usize) -> usize {
+    let mut i = open + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+/// `r"…"`, `r#"…"#`, `br"…"` and friends.
+fn raw_string_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut i = start;
+    if chars.get(i) == Some(&'b') {
+        i += 1;
+    }
+    if chars.get(i) != Some(&'r') || (start > 0 && is_ident_char(chars[start - 1])) {
+        return None;
+    }
+    i += 1;
+    let hashes = chars[i..].iter().take_while(|&&c| c == '#').count();
+    i += hashes;
+    if chars.get(i) != Some(&'"') {
+        return None;
+    }
+    i += 1;
+    while i < chars.len() {
+        if chars[i] == '"'
+            && chars[i + 1..]
+                .iter()
+                .take(hashes)
+                .filter(|&&c| c == '#')
+                .count()
+                == hashes
+        {
+            return Some(i + 1 + hashes);
+        }
+        i += 1;
+    }
+    Some(chars.len())
+}
+
+/// `'a'` or `'\n'`; `None` for a lifetime.
+fn char_literal_end(chars: &[char], start: usize) -> Option<usize> {
+    match chars.get(start + 1)? {
+        '\\' => {
+            let close = chars[start + 2..].iter().position(|&c| c == '\'')?;
+            Some(start + 2 + close + 1)
+        }
+        _ => (chars.get(start + 2) == Some(&'\'')).then_some(start + 3),
+    }
+}
+
+fn ident_end(chars: &[char], start: usize) -> usize {
+    chars[start..]
+        .iter()
+        .position(|&c| !is_ident_char(c))
+        .map_or(chars.len(), |offset| start + offset)
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_saved_replacements_like_the_macro() {
+        let root = tempfile::tempdir().unwrap();
+        let src = root.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(
+            src.join("lib.rs"),
+            "#![forgen::file(\"src/lib.rs\")]\nfn main() {}\n",
+        )
+        .unwrap();
+        assert_eq!(expanded_source(root.path(), "src/lib.rs").unwrap(), None);
+
+        let saved = [SavedReplacement {
+            index: 0,
+            old_text: "{}".to_owned(),
+            new_text: "{ /*#start:demo:1*/run();/*#end:demo:1*/ }".to_owned(),
+        }];
+        let json_path = replacement_path(root.path(), "src/lib.rs");
+        fs::create_dir_all(json_path.parent().unwrap()).unwrap();
+        fs::write(&json_path, serde_json::to_string(&saved).unwrap()).unwrap();
+
+        let expanded = expanded_source(root.path(), "src/lib.rs").unwrap().unwrap();
+        assert_eq!(
+            expanded,
+            "fn main() { /*#start:demo:1*/run();/*#end:demo:1*/ }\n"
+        );
+        assert_eq!(strip_generated_markers(&expanded), "fn main() { run(); }\n");
+    }
+
+    #[test]
+    fn highlights_keywords_literals_and_markers() {
+        let highlighted = highlight("let s = \"fn\"; /*#start:p:h*/'a'");
+        assert!(highlighted.starts_with(&format!("{KEYWORD}let{RESET} s")));
+        assert!(highlighted.contains(&format!("{STRING}\"fn\"{RESET}")));
+        assert!(highlighted.contains(&format!("{MARKER}/*#start:p:h*/{RESET}")));
+        assert!(highlighted.contains(&format!("{STRING}'a'{RESET}")));
+    }
+}
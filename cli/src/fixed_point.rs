@@ -0,0 +1,465 @@
+use crate::replacements;
+use anyhow::{bail, Context, Result};
+use forgen_api::{parse_generated_regions, Replacement};
+use similar::TextDiff;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Pass limit used when neither `--max-passes` nor `max-passes` metadata is
+/// set.
+pub const DEFAULT_MAX_PASSES: usize = 10;
+
+/// How many of the last passes a non-convergence error shows diffs for.
+const REPORTED_PASSES: usize = 3;
+
+/// Lines of a single file's diff shown in a non-convergence error.
+const MAX_DIFF_LINES: usize = 40;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub max_passes: usize,
+    /// Write the sources after every pass, and each pass's diff, here.
+    pub trace_dir: Option<PathBuf>,
+}
+
+/// Pick the pass limit: `--max-passes`, then `[workspace.metadata.forgen]
+/// max-passes`, then [`DEFAULT_MAX_PASSES`].
+pub fn resolve_max_passes(
+    explicit: Option<usize>,
+    meta: &cargo_metadata::Metadata,
+) -> Result<usize> {
+    let max_passes = match explicit {
+        Some(max_passes) => max_passes,
+        None => match crate::config::metadata_u64(meta, "max-passes")? {
+            Some(max_passes) => usize::try_from(max_passes)
+                .context("`[workspace.metadata.forgen] max-passes` is too large")?,
+            None => DEFAULT_MAX_PASSES,
+        },
+    };
+    if max_passes == 0 {
+        bail!("the pass limit must be at least 1");
+    }
+    Ok(max_passes)
+}
+
+#[derive(Debug)]
+pub struct PassOutput {
+    pub replacements_by_path: HashMap<String, Vec<Replacement>>,
+    pub generated_replacements: usize,
+}
+
+#[derive(Debug)]
+pub struct FixedPointResult {
+    pub total_generated_replacements: usize,
+}
+
+/// Run passes until one leaves every source unchanged.
+///
+/// Each pass sees the sources rewritten by the previous one. When the limit
+/// is reached the error explains how the output diverged: whether it cycles
+/// back to an earlier state or keeps growing, which plugins' regions changed,
+/// and the diffs of the last few passes.
+pub fn run_fixed_point_passes<F>(
+    initial_sources: HashMap<String, String>,
+    options: &Options,
+    mut run_pass: F,
+) -> Result<FixedPointResult>
+where
+    F: FnMut(usize, &HashMap<String, String>) -> Result<PassOutput>,
+{
+    let trace = options
+        .trace_dir
+        .as_deref()
+        .map(Trace::create)
+        .transpose()?;
+    if let Some(trace) = &trace {
+        trace.write_pass(0, &initial_sources, &[])?;
+    }
+
+    let mut current_sources = initial_sources;
+    let mut total_generated_replacements = 0usize;
+    // State after each pass, starting with the initial sources.
+    let mut fingerprints = vec![fingerprint(&current_sources)];
+    let mut sizes = vec![total_size(&current_sources)];
+    let mut history: Vec<Vec<FileChange>> = Vec::new();
+
+    for pass in 1..=options.max_passes {
+        let pass_output = run_pass(pass, &current_sources)?;
+        total_generated_replacements += pass_output.generated_replacements;
+
+        let changed_paths = replacements::changed_paths_from_replacements(
+            &current_sources,
+            &pass_output.replacements_by_path,
+        )?;
+
+        if changed_paths.is_empty() {
+            return Ok(FixedPointResult {
+                total_generated_replacements,
+            });
+        }
+
+        let mut changed_paths: Vec<_> = changed_paths.into_iter().collect();
+        changed_paths.sort();
+
+        let mut next_sources = current_sources.clone();
+        let mut changes = Vec::with_capacity(changed_paths.len());
+        for path in changed_paths {
+            let source = current_sources
+                .get(&path)
+                .ok_or_else(|| anyhow::anyhow!("Replacement references unknown file `{path}`"))?;
+            let replacements = pass_output
+                .replacements_by_path
+                .get(&path)
+                .ok_or_else(|| anyhow::anyhow!("Missing replacement set for `{path}`"))?;
+            let rewritten = replacements::apply_replacements_to_source(source, replacements)
+                .with_context(|| format!("Failed to rewrite `{path}` during pass {pass}"))?;
+            changes.push(FileChange::new(path.clone(), source, &rewritten));
+            next_sources.insert(path, rewritten);
+        }
+
+        if let Some(trace) = &trace {
+            trace.write_pass(pass, &next_sources, &changes)?;
+        }
+        fingerprints.push(fingerprint(&next_sources));
+        sizes.push(total_size(&next_sources));
+        history.push(changes);
+        current_sources = next_sources;
+    }
+
+    bail!(
+        "{}",
+        non_convergence_report(options.max_passes, &fingerprints, &sizes, &history)
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Divergence
+// ---------------------------------------------------------------------------
+
+/// How the sources behaved over the passes of a run that did not converge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// The sources after `pass` are the same as after `earlier` (`0` being
+    /// the original sources), so the output cycles with period
+    /// `pass - earlier`.
+    Oscillating { earlier: usize, pass: usize },
+    /// The sources got larger in every one of the last passes.
+    Growing { from: usize, to: usize },
+    /// Neither of the above.
+    Changing,
+}
+
+/// Classify the run from the fingerprints and sizes of the sources after
+/// every pass, index `0` being the original sources.
+pub fn classify(fingerprints: &[u64], sizes: &[usize]) -> Divergence {
+    if let Some((last, earlier)) = fingerprints.split_last() {
+        if let Some(earlier_pass) = earlier.iter().rposition(|f| f == last) {
+            return Divergence::Oscillating {
+                earlier: earlier_pass,
+                pass: earlier.len(),
+            };
+        }
+    }
+
+    let window = &sizes[sizes.len().saturating_sub(REPORTED_PASSES + 1)..];
+    if window.len() > 1 && window.windows(2).all(|pair| pair[1] > pair[0]) {
+        return Divergence::Growing {
+            from: window[0],
+            to: window[window.len() - 1],
+        };
+    }
+
+    Divergence::Changing
+}
+
+/// One file rewritten by a pass.
+#[derive(Debug, Clone)]
+struct FileChange {
+    path: String,
+    diff: String,
+    /// Plugins whose generated regions differ before and after the pass.
+    plugins: BTreeSet<String>,
+}
+
+impl FileChange {
+    fn new(path: String, before: &str, after: &str) -> Self {
+        let diff = TextDiff::from_lines(before, after)
+            .unified_diff()
+            .context_radius(2)
+            .header(&format!("a/{path}"), &format!("b/{path}"))
+            .to_string();
+        Self {
+            plugins: changed_plugins(before, after),
+            path,
+            diff,
+        }
+    }
+}
+
+/// Plugins whose generated regions were added, removed or rewritten.
+pub fn changed_plugins(before: &str, after: &str) -> BTreeSet<String> {
+    let before = region_texts(before);
+    let after = region_texts(after);
+    before
+        .keys()
+        .chain(after.keys())
+        .filter(|plugin_id| before.get(*plugin_id) != after.get(*plugin_id))
+        .map(|plugin_id| (*plugin_id).to_owned())
+        .collect()
+}
+
+fn region_texts(source: &str) -> BTreeMap<String, Vec<&str>> {
+    let mut texts: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for region in parse_generated_regions(source) {
+        let range = region.inner_range.start as usize..region.inner_range.end as usize;
+        texts
+            .entry(region.plugin_id)
+            .or_default()
+            .push(source.get(range).unwrap_or_default());
+    }
+    texts
+}
+
+fn non_convergence_report(
+    max_passes: usize,
+    fingerprints: &[u64],
+    sizes: &[usize],
+    history: &[Vec<FileChange>],
+) -> String {
+    let mut report = format!("Plugin execution did not converge after {max_passes} passes");
+    match classify(fingerprints, sizes) {
+        Divergence::Oscillating { earlier, pass } => {
+            let _ = write!(
+                report,
+                "; the output oscillates with period {} (pass {pass} reproduced {})",
+                pass - earlier,
+                if earlier == 0 {
+                    "the original sources".to_owned()
+                } else {
+                    format!("the sources after pass {earlier}")
+                }
+            );
+        }
+        Divergence::Growing { from, to } => {
+            let passes = sizes.len().min(REPORTED_PASSES + 1) - 1;
+            let _ = write!(
+                report,
+                "; the output grows without bound ({from} → {to} bytes over the last {passes} pass(es))"
+            );
+        }
+        Divergence::Changing => {}
+    }
+
+    let last_paths: Vec<&str> = history
+        .last()
+        .map(|changes| changes.iter().map(|change| change.path.as_str()).collect())
+        .unwrap_or_default();
+    let _ = write!(report, "\nfiles still changing: {}", last_paths.join(", "));
+
+    let first_reported = history.len().saturating_sub(REPORTED_PASSES);
+    for (index, changes) in history.iter().enumerate().skip(first_reported) {
+        let pass = index + 1;
+        let plugins: BTreeSet<&str> = changes
+            .iter()
+            .flat_map(|change| change.plugins.iter().map(String::as_str))
+            .collect();
+        let _ = write!(report, "\n\npass {pass} changed regions of: ");
+        if plugins.is_empty() {
+            report.push_str("(no generated regions)");
+        } else {
+            report.push_str(&plugins.into_iter().collect::<Vec<_>>().join(", "));
+        }
+        for change in changes {
+            report.push('\n');
+            push_truncated(&mut report, &change.diff);
+        }
+    }
+    report
+}
+
+fn push_truncated(report: &mut String, diff: &str) {
+    let lines: Vec<&str> = diff.lines().collect();
+    for line in lines.iter().take(MAX_DIFF_LINES) {
+        report.push_str(line);
+        report.push('\n');
+    }
+    if lines.len() > MAX_DIFF_LINES {
+        let _ = writeln!(report, "… {} more line(s)", lines.len() - MAX_DIFF_LINES);
+    }
+    if report.ends_with('\n') {
+        report.pop();
+    }
+}
+
+fn fingerprint(sources: &HashMap<String, String>) -> u64 {
+    let mut paths: Vec<_> = sources.keys().collect();
+    paths.sort();
+    let mut hasher = DefaultHasher::new();
+    for path in paths {
+        path.hash(&mut hasher);
+        sources[path].hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn total_size(sources: &HashMap<String, String>) -> usize {
+    sources.values().map(String::len).sum()
+}
+
+// ---------------------------------------------------------------------------
+// Trace directory
+// ---------------------------------------------------------------------------
+
+/// `pass-00/` holds the original sources and `pass-NN/` the sources after
+/// pass `NN`, mirrored at their workspace-relative paths; `pass-NN.diff` is
+/// what pass `NN` changed.
+struct Trace {
+    dir: PathBuf,
+}
+
+impl Trace {
+    /// Create `dir`, removing the snapshots of a previous run.
+    fn create(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if !entry.file_name().to_string_lossy().starts_with("pass-") {
+                continue;
+            }
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            }
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    fn write_pass(
+        &self,
+        pass: usize,
+        sources: &HashMap<String, String>,
+        changes: &[FileChange],
+    ) -> Result<()> {
+        let pass_dir = self.dir.join(format!("pass-{pass:02}"));
+        for (rel_path, source) in sources {
+            let path = replacements::mirrored_output_path(&pass_dir, rel_path, "")?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            fs::write(&path, source)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+
+        if !changes.is_empty() {
+            let diff: String = changes.iter().map(|change| change.diff.as_str()).collect();
+            let path = self.dir.join(format!("pass-{pass:02}.diff"));
+            fs::write(&path, diff)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(text: &str) -> HashMap<String, String> {
+        HashMap::from([("src/lib.rs".to_owned(), text.to_owned())])
+    }
+
+    fn options(max_passes: usize) -> Options {
+        Options {
+            max_passes,
+            trace_dir: None,
+        }
+    }
+
+    /// A pass that replaces the whole file with `next(source)`.
+    fn rewrite(
+        next: impl Fn(&str) -> String,
+    ) -> impl FnMut(usize, &HashMap<String, String>) -> Result<PassOutput> {
+        move |_, sources| {
+            let source = &sources["src/lib.rs"];
+            let replacement = Replacement::replace(0, source.len() as u32, next(source));
+            Ok(PassOutput {
+                replacements_by_path: HashMap::from([("src/lib.rs".to_owned(), vec![replacement])]),
+                generated_replacements: 1,
+            })
+        }
+    }
+
+    #[test]
+    fn converges_when_a_pass_changes_nothing() {
+        let result = run_fixed_point_passes(
+            sources("a"),
+            &options(3),
+            rewrite(|source| {
+                if source == "a" {
+                    "b".to_owned()
+                } else {
+                    source.to_owned()
+                }
+            }),
+        )
+        .unwrap();
+        assert_eq!(result.total_generated_replacements, 2);
+    }
+
+    #[test]
+    fn reports_oscillation_with_period_and_diff() {
+        let err = run_fixed_point_passes(
+            sources("fn a() {}\n"),
+            &options(4),
+            rewrite(|source| {
+                if source.contains("a()") {
+                    "fn b() {}\n".to_owned()
+                } else {
+                    "fn a() {}\n".to_owned()
+                }
+            }),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("did not converge after 4 passes"), "{err}");
+        assert!(err.contains("oscillates with period 2"), "{err}");
+        assert!(
+            err.contains("pass 4 reproduced the sources after pass 2"),
+            "{err}"
+        );
+        assert!(err.contains("-fn b() {}\n+fn a() {}"), "{err}");
+        assert!(!err.contains("pass 1 changed"), "{err}");
+    }
+
+    #[test]
+    fn reports_growth() {
+        let err = run_fixed_point_passes(
+            sources("x"),
+            &options(5),
+            rewrite(|source| format!("{source}x")),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("grows without bound (3 → 6 bytes"), "{err}");
+    }
+
+    #[test]
+    fn names_plugins_whose_regions_changed() {
+        let region =
+            |plugin: &str, body: &str| format!("/*#start:{plugin}:h*/{body}/*#end:{plugin}:h*/\n");
+        let before = format!("{}{}", region("a", "1"), region("b", "1"));
+        let after = format!("{}{}", region("a", "1"), region("b", "2"));
+        let plugins = changed_plugins(&before, &after);
+        assert_eq!(plugins.into_iter().collect::<Vec<_>>(), ["b"]);
+    }
+}
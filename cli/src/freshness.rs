@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use notify_debouncer_mini::notify::RecursiveMode;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Name of the file in `target/.forgen/` that records the inputs of the last
+/// saved run.
+const INPUTS_RECORD_FILE: &str = "inputs";
+
+/// Modification time and size of every file a run depends on: `.rs` files in
+/// the recursively watched directories and `Cargo.toml` in the others, as
+/// returned by `watch_targets`, plus files added with [`Sources::add`].
+#[derive(Debug, Default, PartialEq, Eq, Hash)]
+pub struct Sources {
+    files: BTreeMap<PathBuf, (Option<SystemTime>, u64)>,
+}
+
+impl Sources {
+    pub fn scan(targets: &BTreeMap<PathBuf, RecursiveMode>) -> Self {
+        let mut sources = Self::default();
+        for (dir, mode) in targets {
+            match mode {
+                RecursiveMode::Recursive => sources.scan_dir(dir),
+                RecursiveMode::NonRecursive => sources.add(&dir.join("Cargo.toml")),
+            }
+        }
+        sources
+    }
+
+    fn scan_dir(&mut self, dir: &Path) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                self.scan_dir(&path);
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                self.add(&path);
+            }
+        }
+    }
+
+    /// Include `path`; a missing file is left out.
+    pub fn add(&mut self, path: &Path) {
+        if let Ok(metadata) = fs::metadata(path) {
+            self.files.insert(
+                path.to_path_buf(),
+                (metadata.modified().ok(), metadata.len()),
+            );
+        }
+    }
+
+    /// Files created, deleted or modified since `earlier` was scanned.
+    pub fn changed_since(&self, earlier: &Sources) -> Vec<PathBuf> {
+        let modified = self
+            .files
+            .iter()
+            .filter(|(path, stamp)| earlier.files.get(*path) != Some(stamp))
+            .map(|(path, _)| path.clone());
+        let deleted = earlier
+            .files
+            .keys()
+            .filter(|path| !self.files.contains_key(*path))
+            .cloned();
+        modified.chain(deleted).collect()
+    }
+}
+
+/// Hash of `sources`, the options that change what a run writes, and the CLI
+/// version. Only compared with fingerprints of the same binary.
+pub fn fingerprint(sources: &Sources, settings: &impl Hash) -> String {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    sources.hash(&mut hasher);
+    settings.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Whether the output in `target/.forgen/` was saved from inputs with this
+/// fingerprint. Clearing the directory, which every run starts with, makes
+/// the output stale.
+pub fn is_fresh(workspace_root: &Path, fingerprint: &str) -> bool {
+    fs::read_to_string(record_path(workspace_root)).is_ok_and(|recorded| recorded == fingerprint)
+}
+
+/// Record that the output in `target/.forgen/` is up to date with inputs of
+/// this fingerprint.
+pub fn record(workspace_root: &Path, fingerprint: &str) -> Result<()> {
+    let path = record_path(workspace_root);
+    fs::write(&path, fingerprint).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn record_path(workspace_root: &Path) -> PathBuf {
+    workspace_root
+        .join("target")
+        .join(".forgen")
+        .join(INPUTS_RECORD_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_created_modified_and_deleted_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]\n").unwrap();
+        fs::write(src.join("lib.rs"), "mod nested;\n").unwrap();
+        fs::write(src.join("nested/mod.rs"), "").unwrap();
+        fs::write(src.join("notes.txt"), "").unwrap();
+        let targets = BTreeMap::from([
+            (dir.path().to_path_buf(), RecursiveMode::NonRecursive),
+            (src.clone(), RecursiveMode::Recursive),
+        ]);
+
+        let before = Sources::scan(&targets);
+        assert_eq!(before.files.len(), 3);
+        assert!(Sources::scan(&targets).changed_since(&before).is_empty());
+
+        fs::write(src.join("lib.rs"), "mod nested;\nmod added;\n").unwrap();
+        fs::write(src.join("added.rs"), "").unwrap();
+        fs::remove_file(src.join("nested/mod.rs")).unwrap();
+        let mut changed = Sources::scan(&targets).changed_since(&before);
+        changed.sort();
+        assert_eq!(
+            changed,
+            [
+                src.join("added.rs"),
+                src.join("lib.rs"),
+                src.join("nested/mod.rs")
+            ]
+        );
+    }
+
+    #[test]
+    fn output_is_fresh_for_the_recorded_fingerprint_only() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("target/.forgen")).unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("lib.rs"), "").unwrap();
+        let targets = BTreeMap::from([(src.clone(), RecursiveMode::Recursive)]);
+
+        let settings = (Some(1_u64), vec!["expand".to_owned()]);
+        let before = fingerprint(&Sources::scan(&targets), &settings);
+        assert!(!is_fresh(dir.path(), &before));
+        record(dir.path(), &before).unwrap();
+        assert!(is_fresh(dir.path(), &before));
+        assert!(!is_fresh(
+            dir.path(),
+            &fingerprint(&Sources::scan(&targets), &(Some(2_u64), vec!["expand"]))
+        ));
+
+        fs::write(src.join("lib.rs"), "fn main() {}\n").unwrap();
+        assert!(!is_fresh(
+            dir.path(),
+            &fingerprint(&Sources::scan(&targets), &settings)
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::output::{self, notice, Event};
+use forgen_api::{PluginFilter, PluginInfo};
+use std::collections::BTreeSet;
+
+/// Print the plugins of `suite_name` as a table, or as one `suite` event.
+pub fn print(suite_name: &str, plugins: &[PluginInfo]) {
+    output::emit(&Event::Suite {
+        name: suite_name,
+        plugins,
+    });
+    notice!(
+        "🧩 Plugin suite '{suite_name}' ({} plugin(s))",
+        plugins.len()
+    );
+    if plugins.is_empty() {
+        return;
+    }
+
+    let column = |value: Option<&str>| value.unwrap_or("-").to_owned();
+    let rows: Vec<[String; 4]> = plugins
+        .iter()
+        .map(|plugin| {
+            [
+                plugin.id.clone(),
+                column(plugin.version.as_deref()),
+                column(plugin.phase.as_deref()),
+                column(plugin.description.as_deref()),
+            ]
+        })
+        .collect();
+    let width = |index: usize, title: &str| {
+        rows.iter()
+            .map(|row| row[index].len())
+            .max()
+            .unwrap_or(0)
+            .max(title.len())
+    };
+    let (id_width, version_width, phase_width) =
+        (width(0, "plugin"), width(1, "version"), width(2, "phase"));
+
+    notice!(
+        "   {:<id_width$}  {:<version_width$}  {:<phase_width$}  description",
+        "plugin",
+        "version",
+        "phase"
+    );
+    for [id, version, phase, description] in &rows {
+        notice!(
+            "   {id:<id_width$}  {version:<version_width$}  {phase:<phase_width$}  {description}"
+        );
+    }
+}
+
+/// Ids in `--only`, `--skip` or `disabled` that no plugin of the suite has.
+pub fn unknown_filter_ids<'a>(filter: &'a PluginFilter, plugins: &[PluginInfo]) -> Vec<&'a str> {
+    let known: BTreeSet<&str> = plugins.iter().map(|plugin| plugin.id.as_str()).collect();
+    let named: BTreeSet<&str> = filter
+        .only
+        .iter()
+        .chain(&filter.skip)
+        .map(String::as_str)
+        .collect();
+    named.into_iter().filter(|id| !known.contains(id)).collect()
+}
+
+/// Warn about filter ids that name no plugin, usually typos. Only a warning,
+/// because a suite can register plugins that discovery does not see.
+pub fn warn_unknown_filter_ids(filter: &PluginFilter, plugins: &[PluginInfo]) {
+    let unknown = unknown_filter_ids(filter, plugins);
+    if unknown.is_empty() {
+        return;
+    }
+    let known: Vec<&str> = plugins.iter().map(|plugin| plugin.id.as_str()).collect();
+    output::warn(format_args!(
+        "No plugin named `{}` in the suite (it has: {}); check --only, --skip and `disabled`",
+        unknown.join("`, `"),
+        known.join(", ")
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(id: &str) -> PluginInfo {
+        PluginInfo {
+            id: id.to_owned(),
+            version: None,
+            description: None,
+            state_version: 0,
+            phase: None,
+        }
+    }
+
+    #[test]
+    fn reports_filter_ids_without_a_plugin() {
+        let filter = PluginFilter {
+            only: ["expand".to_owned(), "expnad".to_owned()].into(),
+            skip: ["lint".to_owned(), "zzz".to_owned()].into(),
+        };
+        let plugins = [info("expand"), info("lint")];
+        assert_eq!(unknown_filter_ids(&filter, &plugins), ["expnad", "zzz"]);
+        assert!(unknown_filter_ids(&PluginFilter::default(), &plugins).is_empty());
+    }
+}
@@ -1,8 +1,13 @@
+use crate::output::{self, info, Event};
 use anyhow::{Context, Result};
-use forgen_api::{FileReplacement, SuiteRuntime, WorkspaceContext, FORGEN_ABI_VERSION};
+use cargo_metadata::DependencyKind;
+use forgen_api::{FileReplacement, PluginInfo, SuiteRuntime, WorkspaceContext, FORGEN_ABI_VERSION};
 use libloading::{Library, Symbol};
+use std::collections::BTreeSet;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 
 // ---------------------------------------------------------------------------
@@ -17,6 +22,7 @@ type ForgenAbiVersionFn = unsafe extern "C" fn() -> u64;
 type ForgenRunFn =
     unsafe extern "C" fn(*const WorkspaceContext, *mut SuiteRuntime) -> *mut Vec<FileReplacement>;
 type ForgenFreeFn = unsafe extern "C" fn(*mut Vec<FileReplacement>);
+type ForgenPluginsFn = unsafe extern "C" fn() -> *mut Vec<PluginInfo>;
 
 // ---------------------------------------------------------------------------
 // PluginSuite
@@ -54,6 +60,12 @@ pub struct PluginSuite {
     /// impl uses `Box::from_raw` instead (same allocator on both sides).
     #[allow(dead_code)]
     free_fn: ForgenFreeFn,
+    /// Raw pointer to `forgen_plugins` — lists the plugins without running
+    /// them.
+    plugins_fn: ForgenPluginsFn,
+    /// Directory of the private copy of the dylib that [`load_suite`]
+    /// loaded; removed on drop.
+    copy_dir: Option<PathBuf>,
 }
 
 impl PluginSuite {
@@ -63,14 +75,14 @@ impl PluginSuite {
     ///
     /// Returns an error if:
     /// - The library cannot be opened.
-    /// - Any of the three required symbols are missing.
+    /// - Any of the four required symbols are missing.
     /// - The ABI version reported by the dylib does not match
     ///   [`FORGEN_ABI_VERSION`].
     ///
     /// # Safety
     ///
-    /// The library must export `forgen_abi_version`, `forgen_run`, and
-    /// `forgen_free` with the exact signatures generated by
+    /// The library must export `forgen_abi_version`, `forgen_run`,
+    /// `forgen_free` and `forgen_plugins` with the exact signatures generated by
     /// `forgen_api::plugin_suite!`.
     pub unsafe fn load(path: &Path) -> Result<Self> {
         let lib = Library::new(path)
@@ -130,6 +142,16 @@ impl PluginSuite {
             *sym
         };
 
+        let plugins_fn: ForgenPluginsFn = {
+            let sym: Symbol<ForgenPluginsFn> = lib.get(b"forgen_plugins\0").with_context(|| {
+                format!(
+                    "Plugin suite '{}' is missing `forgen_plugins`.",
+                    path.display()
+                )
+            })?;
+            *sym
+        };
+
         let name = path
             .file_stem()
             .and_then(|s| s.to_str())
@@ -141,6 +163,8 @@ impl PluginSuite {
             name,
             run_fn,
             free_fn,
+            plugins_fn,
+            copy_dir: None,
         })
     }
 }
@@ -150,7 +174,13 @@ impl PluginSuite {
         &self.name
     }
 
-    pub fn run(&self, ctx: &WorkspaceContext, runtime: &mut SuiteRuntime) -> Vec<FileReplacement> {
+    /// Run the suite once. Fails if the suite function panicked outside of a
+    /// plugin (panics inside plugins are isolated by `SuiteRuntime`).
+    pub fn run(
+        &self,
+        ctx: &WorkspaceContext,
+        runtime: &mut SuiteRuntime,
+    ) -> Result<Vec<FileReplacement>> {
         // Safety:
         // - `run_fn` is a valid function pointer; `_lib` is alive.
         // - `ctx` is a valid reference for the duration of this call.
@@ -163,14 +193,41 @@ impl PluginSuite {
             unsafe { (self.run_fn)(ctx as *const WorkspaceContext, runtime as *mut SuiteRuntime) };
 
         if ptr.is_null() {
-            return Vec::new();
+            anyhow::bail!(
+                "Plugin suite '{}' panicked outside of a plugin; see the message above",
+                self.name
+            );
         }
 
         // Move the `Vec<FileReplacement>` out of the Box.  The Box wrapper
         // is freed here (on the CLI side); the Vec contents (Strings, inner
         // Vecs) will be freed when `result` is eventually dropped.  Both
         // frees go through the system allocator, which is shared.
-        unsafe { *Box::from_raw(ptr) }
+        Ok(unsafe { *Box::from_raw(ptr) })
+    }
+
+    /// The plugins the suite runs, without running them.
+    pub fn plugins(&self) -> Result<Vec<PluginInfo>> {
+        // Safety: same as for `run`; the pointer comes from `Box::into_raw`
+        // in the dylib.
+        let ptr = unsafe { (self.plugins_fn)() };
+        if ptr.is_null() {
+            anyhow::bail!(
+                "Plugin suite '{}' panicked while listing its plugins; see the message above",
+                self.name
+            );
+        }
+        Ok(unsafe { *Box::from_raw(ptr) })
+    }
+}
+
+impl Drop for PluginSuite {
+    fn drop(&mut self) {
+        // Unix allows deleting a mapped file; elsewhere this fails while the
+        // library is loaded and the copy stays behind.
+        if let Some(dir) = &self.copy_dir {
+            let _ = fs::remove_dir_all(dir);
+        }
     }
 }
 
@@ -178,18 +235,12 @@ impl PluginSuite {
 // Plugin suite loader
 // ---------------------------------------------------------------------------
 
-/// Reads `[workspace.metadata.forgen] suite = "<name>"` from the
-/// cargo metadata, builds that crate with `cargo build`, and loads the
-/// resulting shared library as a [`PluginSuite`].
-///
-/// Returns `None` (without error) if the key is absent — meaning the user
-/// has not configured a plugin suite yet and only built-in plugins run.
-pub fn load_suite(meta: &cargo_metadata::Metadata, build: bool) -> Option<PluginSuite> {
+/// The package name of the workspace's plugin suite, if it has one.
+pub fn suite_name(meta: &cargo_metadata::Metadata) -> Option<String> {
     // Explicit config takes priority.  If absent, fall back to auto-discovery:
     // a workspace member named "plugins" is treated as the plugin suite by
     // convention (no Cargo.toml entry required).
-    let suite_name = meta
-        .workspace_metadata
+    meta.workspace_metadata
         .get("forgen")
         .and_then(|f| f.get("suite"))
         .and_then(|v| v.as_str())
@@ -200,13 +251,23 @@ pub fn load_suite(meta: &cargo_metadata::Metadata, build: bool) -> Option<Plugin
                 .iter()
                 .any(|p| p.name == "plugins")
                 .then(|| "plugins".to_owned())
-        })?; // return None if neither configured nor auto-discovered
+        })
+}
+
+/// Reads `[workspace.metadata.forgen] suite = "<name>"` from the
+/// cargo metadata, builds that crate with `cargo build`, and loads the
+/// resulting shared library as a [`PluginSuite`].
+///
+/// Returns `None` (without error) if the key is absent — meaning the user
+/// has not configured a plugin suite yet and only built-in plugins run.
+pub fn load_suite(meta: &cargo_metadata::Metadata, build: bool) -> Option<PluginSuite> {
+    let suite_name = suite_name(meta)?;
 
     let workspace_root: &Path = meta.workspace_root.as_std_path();
     let target_dir: &Path = meta.target_directory.as_std_path();
 
     if build {
-        println!("  🔨 Building plugin suite '{suite_name}'...");
+        info!("  🔨 Building plugin suite '{suite_name}'...");
         let build_start = Instant::now();
 
         // Build the plugin suite crate as a cdylib.
@@ -221,16 +282,16 @@ pub fn load_suite(meta: &cargo_metadata::Metadata, build: bool) -> Option<Plugin
 
         match silent_status {
             Ok(s) if s.success() => {
-                println!(
+                output::phase("suite_build", build_start);
+                info!(
                     "  ⏱ plugin suite build finished in {:.2?}",
                     build_start.elapsed()
                 );
             }
             Ok(_) => {
-                eprintln!(
-                    "  ⚠️  `cargo build --package {suite_name}` failed — \
-                 re-running with output:"
-                );
+                output::warn(format_args!(
+                    "`cargo build --package {suite_name}` failed — re-running with output:"
+                ));
                 let _ = Command::new("cargo")
                     .args(["build", "--release", "--package", &suite_name])
                     .current_dir(workspace_root)
@@ -238,7 +299,7 @@ pub fn load_suite(meta: &cargo_metadata::Metadata, build: bool) -> Option<Plugin
                 return None;
             }
             Err(e) => {
-                eprintln!("  ⚠️  Failed to invoke `cargo build`: {e}");
+                output::warn(format_args!("Failed to invoke `cargo build`: {e}"));
                 return None;
             }
         }
@@ -247,39 +308,165 @@ pub fn load_suite(meta: &cargo_metadata::Metadata, build: bool) -> Option<Plugin
     // Locate the compiled dylib in target/release/.
     let lib_stem = suite_name.replace('-', "_");
     let Some(dylib_path) = dylib_path_in_target(target_dir, &lib_stem) else {
-        eprintln!(
-            "  ⚠️  Build succeeded but no dylib found for '{suite_name}' in {}.\n  \
+        output::warn(format_args!(
+            "Build succeeded but no dylib found for '{suite_name}' in {}.\n  \
              Make sure your Cargo.toml has `crate-type = [\"cdylib\"]`.",
             target_dir.join("release").display()
-        );
+        ));
         return None;
     };
 
+    // Load a private copy: the dynamic loader may keep a library that was
+    // "closed" mapped and hand it out again for the same path, which would
+    // keep a rebuilt suite from replacing it, and Windows cannot rebuild a
+    // loaded DLL in place.
+    let copy_dir = match copy_for_loading(&dylib_path, target_dir) {
+        Ok(copy_dir) => copy_dir,
+        Err(e) => {
+            output::warn(format_args!("{e:#}"));
+            return None;
+        }
+    };
+    let file_name = dylib_path.file_name().expect("dylib path has a file name");
+
     let load_start = Instant::now();
-    match unsafe { PluginSuite::load(&dylib_path) } {
-        Ok(p) => {
-            println!(
+    match unsafe { PluginSuite::load(&copy_dir.join(file_name)) } {
+        Ok(mut p) => {
+            p.copy_dir = Some(copy_dir);
+            output::phase("suite_load", load_start);
+            info!(
                 "  ✅ Loaded plugin suite '{}' from {}",
                 p.name(),
                 dylib_path.display()
             );
-            println!(
+            info!(
                 "  ⏱ plugin suite dylib load took {:.2?}",
                 load_start.elapsed()
             );
             Some(p)
         }
         Err(e) => {
-            eprintln!("  ⚠️  {e}");
+            let _ = fs::remove_dir_all(&copy_dir);
+            output::warn(e);
             None
         }
     }
 }
 
+/// Replace the suite in `slot` with `new_suite` after a rebuild. Plugins the
+/// old build ran but the new one does not lose their state; the state of
+/// every other plugin is kept. Per-file results of the old code are dropped.
+pub fn replace_suite(
+    slot: &mut Option<PluginSuite>,
+    new_suite: PluginSuite,
+    runtime: &mut SuiteRuntime,
+) {
+    let Some(old_suite) = slot.replace(new_suite) else {
+        return;
+    };
+
+    let ids = |suite: &PluginSuite| -> Option<BTreeSet<String>> {
+        let plugins = suite.plugins().ok()?;
+        Some(plugins.into_iter().map(|plugin| plugin.id).collect())
+    };
+    let removed = forget_old_suite(ids(&old_suite), slot.as_ref().and_then(ids), runtime);
+
+    output::emit(&Event::Reloaded {
+        removed_plugins: &removed,
+    });
+    if removed.is_empty() {
+        info!("  🔁 Reloaded the plugin suite, keeping plugin state");
+    } else {
+        info!(
+            "  🔁 Reloaded the plugin suite; dropped the state of removed plugin(s): {}",
+            removed.join(", ")
+        );
+    }
+}
+
+/// Clear what `runtime` kept from the old suite: every cached per-file
+/// result, and the state of plugins in `old_ids` but not in `new_ids`. Keeps
+/// all state when either build could not list its plugins. Returns the ids of
+/// the removed plugins.
+fn forget_old_suite(
+    old_ids: Option<BTreeSet<String>>,
+    new_ids: Option<BTreeSet<String>>,
+    runtime: &mut SuiteRuntime,
+) -> Vec<String> {
+    runtime.clear_file_cache();
+    let removed: Vec<String> = match (old_ids, new_ids) {
+        (Some(old), Some(new)) => old.difference(&new).cloned().collect(),
+        _ => Vec::new(),
+    };
+    for plugin_id in &removed {
+        runtime.reset_plugin_state(plugin_id);
+    }
+    removed
+}
+
+/// The `src` directories of the suite crate and of the local path
+/// dependencies it is built from, directly or transitively.
+pub fn suite_source_dirs(meta: &cargo_metadata::Metadata) -> Vec<PathBuf> {
+    let Some(suite_name) = suite_name(meta) else {
+        return Vec::new();
+    };
+    let Some(suite) = meta
+        .workspace_packages()
+        .into_iter()
+        .find(|package| package.name == suite_name)
+    else {
+        return Vec::new();
+    };
+
+    let mut queue = vec![suite];
+    let mut dirs = BTreeSet::new();
+    while let Some(package) = queue.pop() {
+        let Some(package_dir) = package.manifest_path.parent() else {
+            continue;
+        };
+        if !dirs.insert(package_dir.join("src").into_std_path_buf()) {
+            continue;
+        }
+        for dependency in &package.dependencies {
+            let Some(path) = &dependency.path else {
+                continue;
+            };
+            if dependency.kind == DependencyKind::Development {
+                continue;
+            }
+            queue.extend(
+                meta.packages
+                    .iter()
+                    .filter(|candidate| candidate.manifest_path.parent() == Some(path.as_path())),
+            );
+        }
+    }
+    dirs.into_iter().collect()
+}
+
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
 
+/// Copy `dylib_path` into a new directory under
+/// `<target_dir>/forgen-suite/`, keeping its file name, and return that
+/// directory.
+fn copy_for_loading(dylib_path: &Path, target_dir: &Path) -> Result<PathBuf> {
+    static LOADS: AtomicUsize = AtomicUsize::new(0);
+
+    let copy_dir = target_dir.join("forgen-suite").join(format!(
+        "{}-{}",
+        std::process::id(),
+        LOADS.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&copy_dir)
+        .with_context(|| format!("Failed to create {}", copy_dir.display()))?;
+    let file_name = dylib_path.file_name().expect("dylib path has a file name");
+    fs::copy(dylib_path, copy_dir.join(file_name))
+        .with_context(|| format!("Failed to copy {}", dylib_path.display()))?;
+    Ok(copy_dir)
+}
+
 /// Returns the expected on-disk path of the `cdylib` output for `lib_stem`
 /// (underscores, not hyphens) inside `<target_dir>/release/`.
 ///
@@ -299,3 +486,161 @@ fn dylib_path_in_target(target_dir: &Path, lib_stem: &str) -> Option<PathBuf> {
     let path = release_dir.join(filename);
     path.exists().then_some(path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, package, path_dependency, registry_dependency};
+    use forgen_api::{
+        CacheStats, DirNode, FileContext, Interest, LazyValue, Plugin, PluginRuntime, PluginState,
+        Replacement, WorkspaceManifest,
+    };
+    use serde_json::json;
+
+    struct CachedPlugin;
+
+    impl Plugin for CachedPlugin {
+        fn name(&self) -> &str {
+            "cached"
+        }
+
+        fn interest(&self) -> Interest {
+            Interest::all_files().cached()
+        }
+
+        fn run_file(
+            &self,
+            _file: &FileContext,
+            _ctx: &WorkspaceContext,
+            _runtime: &mut PluginRuntime<'_>,
+        ) -> Vec<Replacement> {
+            Vec::new()
+        }
+    }
+
+    fn one_file_workspace() -> WorkspaceContext {
+        let file = FileContext::new(
+            "src/lib.rs".to_owned(),
+            LazyValue::from_value(String::new()),
+            LazyValue::from_value(Vec::new()),
+            LazyValue::new(|| unreachable!("tree is not used")),
+            LazyValue::from_value(Vec::new()),
+            LazyValue::from_value(Vec::new()),
+            LazyValue::from_value(Vec::new()),
+            LazyValue::from_value(Vec::new()),
+            LazyValue::from_value(Vec::new()),
+            None,
+        );
+        WorkspaceContext::new(
+            "/ws".to_owned(),
+            vec![file],
+            WorkspaceManifest {
+                members: Vec::new(),
+                workspace_root: "/ws".to_owned(),
+                target_directory: "/ws/target".to_owned(),
+                metadata: json!(null),
+            },
+            LazyValue::from_value(DirNode {
+                name: String::new(),
+                path: String::new(),
+                entries: Vec::new(),
+            }),
+            None,
+        )
+    }
+
+    fn ids(ids: &[&str]) -> Option<BTreeSet<String>> {
+        Some(ids.iter().map(|id| id.to_string()).collect())
+    }
+
+    fn runtime_with_state(plugin_ids: &[&str]) -> SuiteRuntime {
+        let mut runtime = SuiteRuntime::with_seed(1);
+        for plugin_id in plugin_ids {
+            let mut state = PluginState::new();
+            state.set("runs", 1u32).unwrap();
+            runtime.restore_plugin_state(plugin_id, 0, state);
+        }
+        runtime
+    }
+
+    #[test]
+    fn reload_keeps_the_state_of_plugins_that_still_exist() {
+        let mut runtime = runtime_with_state(&["kept", "removed"]);
+
+        let removed = forget_old_suite(
+            ids(&["kept", "removed"]),
+            ids(&["kept", "new"]),
+            &mut runtime,
+        );
+        assert_eq!(removed, ["removed"]);
+        assert!(runtime.plugin_state("kept").is_some());
+        assert!(runtime.plugin_state("removed").is_none());
+    }
+
+    #[test]
+    fn reload_keeps_all_state_when_a_build_cannot_list_its_plugins() {
+        let mut runtime = runtime_with_state(&["kept", "removed"]);
+
+        assert!(forget_old_suite(ids(&["kept", "removed"]), None, &mut runtime).is_empty());
+        assert!(runtime.plugin_state("removed").is_some());
+    }
+
+    #[test]
+    fn reload_clears_the_file_cache() {
+        let ctx = one_file_workspace();
+        let mut runtime = SuiteRuntime::with_seed(1);
+        runtime.run_plugin(&CachedPlugin, &ctx);
+        runtime.run_plugin(&CachedPlugin, &ctx);
+        assert_eq!(
+            runtime.take_file_cache_stats(),
+            CacheStats { hits: 1, misses: 1 }
+        );
+
+        forget_old_suite(ids(&["cached"]), ids(&["cached"]), &mut runtime);
+        runtime.run_plugin(&CachedPlugin, &ctx);
+        assert_eq!(
+            runtime.take_file_cache_stats(),
+            CacheStats { hits: 0, misses: 1 }
+        );
+    }
+
+    #[test]
+    fn suite_sources_include_transitive_path_dependencies_only() {
+        let meta = test_support::workspace(
+            json!({ "forgen": { "suite": "plugins" } }),
+            vec![
+                package(
+                    "plugins",
+                    "plugins",
+                    vec![
+                        path_dependency("helpers", "crates/helpers"),
+                        registry_dependency("serde"),
+                    ],
+                ),
+                package(
+                    "helpers",
+                    "crates/helpers",
+                    vec![path_dependency("util", "crates/util")],
+                ),
+                package("util", "crates/util", Vec::new()),
+                package("app", "app", Vec::new()),
+                test_support::registry_package("serde"),
+            ],
+        );
+
+        assert_eq!(
+            suite_source_dirs(&meta),
+            [
+                PathBuf::from("/ws/crates/helpers/src"),
+                PathBuf::from("/ws/crates/util/src"),
+                PathBuf::from("/ws/plugins/src"),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_suite_has_no_source_dirs() {
+        let meta = test_support::workspace(json!({}), vec![package("app", "app", Vec::new())]);
+        assert!(suite_source_dirs(&meta).is_empty());
+    }
+}
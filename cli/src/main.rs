@@ -1,35 +1,59 @@
+mod check;
+mod config;
+mod daemon;
+mod db_thread;
+mod diagnostics;
+mod eject;
+mod expand;
+mod fixed_point;
+mod freshness;
+mod list;
 mod loader;
 mod oracle;
+mod output;
+mod profile;
 mod replacements;
+mod review;
+mod scaffold;
+mod seeds;
+mod session;
+mod setup;
+mod state;
+#[cfg(test)]
+mod test_support;
 mod workspace;
+mod wrapper;
 
 use anyhow::{Context, Result};
 use cargo_metadata::DependencyKind as CargoDep;
 use clap::{Parser, Subcommand};
+use db_thread::{DbExecutor, DbThread};
+use forgen_api::profile::ProfileEvent;
 use forgen_api::Replacement;
 use forgen_api::{
     syntax::raw::{Child as SyntaxChild, RawNode, RawToken},
     syntax::SyntaxKind,
     Dependency, DependencySource, DirNode, EnumDef, FieldDef, FileContext as ApiFileContext,
-    FileRef, FnDef, FnParam, FsEntry, ImplDef, LazyValue, LetBinding, PackageManifest,
+    FileRef, FnDef, FnParam, FsEntry, ImplDef, LazyValue, LetBinding, PackageManifest, Profiler,
     SemanticHandle, StructDef, SuiteRuntime, TextRange as ApiTextRange, VariantDef,
     WorkspaceContext, WorkspaceManifest,
 };
-use notify_debouncer_mini::{new_debouncer, notify::*};
-use ra_ap_hir::{attach_db_allow_change, Crate, Semantics};
+use notify_debouncer_mini::{new_debouncer, notify::*, DebounceEventResult};
+use output::{detail, info, notice, Event, MessageFormat, Verbosity};
+use ra_ap_hir::{attach_db_allow_change, Crate};
 use ra_ap_ide_db::{base_db::SourceDatabase, EditionedFileId, FileId, RootDatabase};
 use ra_ap_paths::AbsPathBuf;
 use ra_ap_syntax::{ast, ast::HasName, ast::HasVisibility, AstNode, SourceFile, SyntaxElement};
 use ra_ap_vfs::Vfs;
-use std::collections::{HashMap, HashSet};
+use session::{run_and_persist, watch_targets, DaemonState, SourceChanges};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::marker::PhantomData;
+use wrapper::run_cargo_command;
 
 use std::path::{Path, PathBuf};
 use std::sync::{mpsc::channel, Arc, OnceLock};
 use std::time::{Duration, Instant};
 
-const MAX_PLUGIN_PASSES: usize = 10;
-
 // ---------------------------------------------------------------------------
 // CLI
 // ---------------------------------------------------------------------------
@@ -49,7 +73,261 @@ enum Command {
 }
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<ForgenCommand>,
+
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum ForgenCommand {
+    /// Show, reset or edit persisted plugin state
+    State(StateArgs),
+    /// Show or regenerate seeds pinned in the seed lock file
+    Seeds(SeedsArgs),
+    /// Verify that `target/.forgen/` matches a fresh, deterministic run
+    /// without writing anything (for CI)
+    Check(RunArgs),
+    /// Print the source `forgen::file` compiles for a file, using the saved
+    /// replacements in `target/.forgen/` (no workspace analysis)
+    Expand(ExpandArgs),
+    /// Write the converged source back to disk so it no longer depends on
+    /// forgen
+    Eject(EjectArgs),
+    /// Create a plugin suite crate with a starter plugin and register it in
+    /// the workspace
+    Init(InitArgs),
+    /// Add a plugin to the suite and register it in the suite's `run`
+    NewPlugin(NewPluginArgs),
+    /// Add or repair `#![forgen::file(...)]`, its feature gates and the
+    /// `forgen` dependency
+    Setup(SetupArgs),
+    /// Build the plugin suite and list its plugins
+    List(ListArgs),
+    /// Keep the workspace loaded and run the plugins when a client asks
+    Daemon(DaemonArgs),
+    /// Send a request to a running `cargo forgen daemon`
+    Client(ClientArgs),
+    /// Bring `target/.forgen/` up to date, then run `cargo build`
+    Build(CargoArgs),
+    /// Bring `target/.forgen/` up to date, then run `cargo test`
+    Test(CargoArgs),
+    /// Bring `target/.forgen/` up to date, then run `cargo run`
+    Run(CargoArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct CargoArgs {
+    /// Socket of a running daemon or watcher to ask first (defaults to
+    /// `<target-dir>/forgen-daemon.sock`)
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    #[command(flatten)]
+    run: RunArgs,
+
+    /// Arguments passed on to cargo
+    #[arg(last = true, value_name = "CARGO_ARGS")]
+    cargo_args: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ExpandArgs {
+    /// The file, as a workspace-relative path or a path from the current
+    /// directory
+    #[arg(value_name = "PATH")]
+    path: PathBuf,
+
+    /// Path to Cargo.toml (defaults to ./Cargo.toml in current directory)
+    #[arg(long, value_name = "PATH")]
+    manifest_path: Option<PathBuf>,
+
+    /// Leave generated-region markers out
+    #[arg(long)]
+    strip_markers: bool,
+
+    /// Syntax highlighting
+    #[arg(long, value_enum, default_value_t = expand::Color::Auto)]
+    color: expand::Color,
+}
+
+#[derive(clap::Args, Debug)]
+struct EjectArgs {
+    /// Files to eject, as workspace-relative paths or paths from the current
+    /// directory (defaults to every file with saved output)
+    #[arg(value_name = "PATH")]
+    files: Vec<PathBuf>,
+
+    /// Path to Cargo.toml (defaults to ./Cargo.toml in current directory)
+    #[arg(long, value_name = "PATH")]
+    manifest_path: Option<PathBuf>,
+
+    /// Only eject the output of these plugins (comma-separated or repeated);
+    /// other plugins' regions keep their markers
+    #[arg(long, value_name = "PLUGIN", value_delimiter = ',')]
+    plugin: Vec<String>,
+
+    /// Also remove `#![forgen::file(...)]` and the feature gates it needs
+    #[arg(long, conflicts_with = "plugin")]
+    remove_attr: bool,
+
+    /// Overwrite files with uncommitted changes
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct InitArgs {
+    /// Path to Cargo.toml (defaults to ./Cargo.toml in current directory)
+    #[arg(long, value_name = "PATH")]
+    manifest_path: Option<PathBuf>,
+
+    /// Package name and directory of the suite crate
+    #[arg(long, default_value = scaffold::DEFAULT_SUITE_NAME)]
+    name: String,
+
+    /// Id of the starter plugin
+    #[arg(long, value_name = "ID", default_value = scaffold::DEFAULT_PLUGIN_ID)]
+    plugin: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct NewPluginArgs {
+    /// Plugin id (ASCII letters, digits, `_` and `-`)
+    #[arg(value_name = "ID")]
+    id: String,
+
+    /// Path to Cargo.toml (defaults to ./Cargo.toml in current directory)
+    #[arg(long, value_name = "PATH")]
+    manifest_path: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ListArgs {
+    /// Path to Cargo.toml (defaults to ./Cargo.toml in current directory)
+    #[arg(long, value_name = "PATH")]
+    manifest_path: Option<PathBuf>,
+
+    /// `human` table, or `json` for one `suite` event
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+}
+
+#[derive(clap::Args, Debug)]
+struct DaemonArgs {
+    /// Socket to listen on (defaults to `<target-dir>/forgen-daemon.sock`)
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct ClientArgs {
+    /// Path to Cargo.toml (defaults to ./Cargo.toml in current directory)
+    #[arg(long, value_name = "PATH")]
+    manifest_path: Option<PathBuf>,
+
+    /// The daemon's socket (defaults to `<target-dir>/forgen-daemon.sock`)
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    #[command(subcommand)]
+    request: ClientRequest,
+}
+
+#[derive(Subcommand, Debug)]
+enum ClientRequest {
+    /// Bring `target/.forgen/` up to date and wait until it is
+    Run {
+        /// Run even if no source changed since the last successful run
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print whether the daemon's output is fresh
+    Status,
+    /// Print the diagnostics of the last run
+    Diagnostics,
+    /// Stop the daemon
+    Shutdown,
+}
+
+#[derive(clap::Args, Debug)]
+struct SetupArgs {
+    /// Files to adopt, as workspace-relative paths or paths from the current
+    /// directory (defaults to every file that already has the attribute)
+    #[arg(value_name = "PATH")]
+    files: Vec<PathBuf>,
+
+    /// Path to Cargo.toml (defaults to ./Cargo.toml in current directory)
+    #[arg(long, value_name = "PATH")]
+    manifest_path: Option<PathBuf>,
+
+    /// Adopt the crate root files of these packages (comma-separated or
+    /// repeated)
+    #[arg(long, value_name = "NAME", value_delimiter = ',')]
+    package: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct StateArgs {
+    /// Path to Cargo.toml (defaults to ./Cargo.toml in current directory)
+    #[arg(long, value_name = "PATH")]
+    manifest_path: Option<PathBuf>,
+
+    /// State file to operate on (overrides `state-file` in workspace metadata)
+    #[arg(long, value_name = "PATH")]
+    state_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    action: StateAction,
+}
+
+#[derive(Subcommand, Debug)]
+enum StateAction {
+    /// Print the stored state of one plugin, or of all plugins
+    Show { plugin: Option<String> },
+    /// Delete the stored state of one plugin, or of all plugins
+    Reset { plugin: Option<String> },
+    /// Open one plugin's stored state in $VISUAL / $EDITOR
+    Edit { plugin: String },
+}
+
+#[derive(clap::Args, Debug)]
+struct SeedsArgs {
+    /// Path to Cargo.toml (defaults to ./Cargo.toml in current directory)
+    #[arg(long, value_name = "PATH")]
+    manifest_path: Option<PathBuf>,
+
+    /// Seed lock file to operate on (overrides `seed-lock` in workspace metadata)
+    #[arg(long, value_name = "PATH")]
+    seed_lock: Option<PathBuf>,
+
+    #[command(subcommand)]
+    action: SeedsAction,
+}
+
+#[derive(Subcommand, Debug)]
+enum SeedsAction {
+    /// Print the suite seed and every pinned per-file seed
+    Show,
+    /// Replace pinned seeds with fresh random ones (all of them by default)
+    Regenerate {
+        /// Only regenerate seeds of this plugin
+        #[arg(long)]
+        plugin: Option<String>,
+        /// Only regenerate seeds of this workspace-relative file
+        #[arg(long)]
+        file: Option<String>,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct RunArgs {
     /// Path to Cargo.toml (defaults to ./Cargo.toml in current directory)
     #[arg(value_name = "MANIFEST")]
     manifest: Option<PathBuf>,
@@ -70,9 +348,99 @@ struct Args {
     #[arg(long)]
     with_prefill_caches: bool,
 
-    /// Print oracle inference traces (binding text + inferred type) to stderr
-    #[arg(short, long)]
-    verbose: bool,
+    /// Print only results, warnings and diagnostics
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print more: `-v` adds per-plugin results of every pass and
+    /// rust-analyzer load progress, `-vv` also oracle inference traces
+    /// (binding text + inferred type) on stderr
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// `human` progress output, or `json` for one JSON event per line on
+    /// stdout
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+
+    /// Load plugin state from this file at start and save it after every
+    /// converged run (overrides `state-file` in workspace metadata)
+    #[arg(long, value_name = "PATH")]
+    state_file: Option<PathBuf>,
+
+    /// Suite seed for `rng_for_file` (overrides `seed` in workspace metadata)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Pin per-file seeds in this lock file so generated values survive seed
+    /// changes and file moves (overrides `seed-lock` in workspace metadata)
+    #[arg(long, value_name = "PATH")]
+    seed_lock: Option<PathBuf>,
+
+    /// Also write plugin diagnostics to this file as JSON
+    #[arg(long, value_name = "PATH")]
+    diagnostics_json: Option<PathBuf>,
+
+    /// Also write plugin diagnostics to this file as SARIF 2.1.0
+    #[arg(long, value_name = "PATH")]
+    diagnostics_sarif: Option<PathBuf>,
+
+    /// Worker threads for concurrent plugins and per-file runs (defaults to
+    /// one per CPU; 1 runs everything on the main thread)
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// Only run these plugins (repeatable or comma-separated); overrides
+    /// `disabled` in workspace metadata
+    #[arg(long, value_name = "PLUGIN", value_delimiter = ',')]
+    only: Vec<String>,
+
+    /// Do not run these plugins (repeatable or comma-separated), in addition
+    /// to `disabled` in workspace metadata
+    #[arg(long, value_name = "PLUGIN", value_delimiter = ',')]
+    skip: Vec<String>,
+
+    /// Run every per-file plugin on every file instead of reusing cached
+    /// results for unchanged files
+    #[arg(long)]
+    no_file_cache: bool,
+
+    /// Give up after this many passes if the output keeps changing
+    /// (overrides `max-passes` in workspace metadata; defaults to 10)
+    #[arg(long, value_name = "N")]
+    max_passes: Option<usize>,
+
+    /// Write the sources after every pass, and each pass's diff, to this
+    /// directory
+    #[arg(long, value_name = "DIR")]
+    trace_dir: Option<PathBuf>,
+
+    /// Print the changes and leave `target/.forgen/`, the state file and the
+    /// seed lock untouched
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Print a unified diff between each file's original and converged source
+    #[arg(long)]
+    diff: bool,
+
+    /// Leave generated-region markers out of the diff and patch
+    #[arg(long)]
+    strip_markers: bool,
+
+    /// Write the changes as a patch that `git apply` accepts from the
+    /// workspace root
+    #[arg(long, value_name = "PATH")]
+    patch: Option<PathBuf>,
+
+    /// Print per-plugin, per-query and lazy-field timings after each run
+    #[arg(long)]
+    profile: bool,
+
+    /// Write a Chrome trace of plugin runs, queries and lazy fields to this
+    /// file (open it in chrome://tracing or Perfetto)
+    #[arg(long, value_name = "PATH")]
+    profile_trace: Option<PathBuf>,
 }
 
 // ---------------------------------------------------------------------------
@@ -83,13 +451,242 @@ fn main() -> Result<()> {
     let cli = Cli::parse();
     let Command::Forgen(args) = cli.command;
 
-    println!("🚀 Forgen");
-    println!("=========================================\n");
-
-    let manifest_path = args.manifest.unwrap_or_else(|| PathBuf::from("Cargo.toml"));
-    println!("📦 Loading project: {}", manifest_path.display());
+    let result = run_forgen_command(args);
+    if let Err(err) = &result {
+        if output::is_json() {
+            output::error(format_args!("{err:#}"));
+            std::process::exit(1);
+        }
+    }
+    result
+}
 
+fn run_forgen_command(args: Args) -> Result<()> {
+    match args.command {
+        Some(ForgenCommand::State(state_args)) => return run_state_command(state_args),
+        Some(ForgenCommand::Seeds(seeds_args)) => return run_seeds_command(seeds_args),
+        Some(ForgenCommand::Check(check_args)) => return run_check_command(check_args),
+        Some(ForgenCommand::Expand(expand_args)) => return run_expand_command(expand_args),
+        Some(ForgenCommand::Eject(eject_args)) => return run_eject_command(eject_args),
+        Some(ForgenCommand::Init(init_args)) => return run_init_command(init_args),
+        Some(ForgenCommand::NewPlugin(new_plugin_args)) => {
+            return run_new_plugin_command(new_plugin_args)
+        }
+        Some(ForgenCommand::Setup(setup_args)) => return run_setup_command(setup_args),
+        Some(ForgenCommand::List(list_args)) => return run_list_command(list_args),
+        Some(ForgenCommand::Daemon(daemon_args)) => return run_daemon_command(daemon_args),
+        Some(ForgenCommand::Client(client_args)) => return run_client_command(client_args),
+        Some(ForgenCommand::Build(cargo_args)) => return run_cargo_command("build", cargo_args),
+        Some(ForgenCommand::Test(cargo_args)) => return run_cargo_command("test", cargo_args),
+        Some(ForgenCommand::Run(cargo_args)) => return run_cargo_command("run", cargo_args),
+        None => {}
+    }
+    let args = args.run;
     let total_start = Instant::now();
+    let watch = args.watch;
+    let mode = if args.dry_run {
+        OutputMode::InMemory
+    } else {
+        OutputMode::Save
+    };
+    let mut session = load_session(args, mode)?;
+
+    if watch {
+        info!("👀 Watch mode enabled - monitoring for changes...\n");
+        info!("Press Ctrl+C to stop\n");
+
+        if session.workspace_info.members.is_empty() {
+            anyhow::bail!("No source directories found to watch");
+        }
+
+        let (tx, rx) = channel();
+        let mut debouncer = new_debouncer(Duration::from_millis(500), {
+            let tx = tx.clone();
+            move |events| {
+                let _ = tx.send(WatchMessage::Files(events));
+            }
+        })?;
+        // Answer daemon requests too, so `cargo forgen build` and editors can
+        // wait for the watcher instead of running the plugins themselves. Not
+        // with --dry-run: clients read the output from `target/.forgen/`.
+        #[cfg(unix)]
+        let _server = if mode == OutputMode::Save {
+            let socket = daemon::default_socket_path(&session.workspace_info.cargo_metadata);
+            daemon::Server::bind(&socket, tx)
+                .inspect_err(|e| output::warn(format_args!("{e:#}; not answering daemon requests")))
+                .ok()
+        } else {
+            None
+        };
+
+        // Changes in the suite crate or its local dependencies rebuild and
+        // reload the suite, manifest changes reload the workspace.
+        let mut state = DaemonState::new(&session, mode);
+        state.run(&mut session, true);
+        let mut watched = BTreeMap::new();
+        update_watches(
+            debouncer.watcher(),
+            &mut watched,
+            watch_targets(&session.workspace_info, &state.suite_dirs),
+        )?;
+        info!();
+
+        loop {
+            match rx.recv() {
+                Ok(WatchMessage::Files(Ok(events))) => {
+                    // The events only wake us up; `DaemonState::run` rescans
+                    // the sources to find out what changed.
+                    let changes = SourceChanges::from_paths(events.into_iter().map(|e| e.path));
+                    if changes.is_empty() {
+                        continue;
+                    }
+                    state.run(&mut session, false);
+                }
+                Ok(WatchMessage::Files(Err(e))) => {
+                    output::error(format_args!("Watch error: {e:?}"))
+                }
+                #[cfg(unix)]
+                Ok(WatchMessage::Request(request, reply)) => {
+                    if !state.answer(&mut session, request, reply) {
+                        info!("👋 Watch mode stopped");
+                        break;
+                    }
+                }
+                Err(e) => {
+                    output::error(format_args!("Channel error: {e:?}"));
+                    break;
+                }
+            }
+            let targets = watch_targets(&session.workspace_info, &state.suite_dirs);
+            if let Err(e) = update_watches(debouncer.watcher(), &mut watched, targets) {
+                output::error(format_args!("Watch error: {e:#}"));
+            }
+        }
+    } else {
+        let run_start = Instant::now();
+        let outcome = run_and_persist(&mut session, true, mode)?;
+        info!("⏱ plugin run took {:.2?}", run_start.elapsed());
+        info!("⏱ total CLI time {:.2?}", total_start.elapsed());
+        if outcome.errors > 0 {
+            anyhow::bail!("plugins reported {} error(s)", outcome.errors);
+        }
+        info!("\n✨ Done!");
+    }
+
+    Ok(())
+}
+
+/// What wakes up watch mode.
+enum WatchMessage {
+    Files(DebounceEventResult),
+    #[cfg(unix)]
+    Request(daemon::Request, daemon::Reply),
+}
+
+#[cfg(unix)]
+impl From<(daemon::Request, daemon::Reply)> for WatchMessage {
+    fn from((request, reply): (daemon::Request, daemon::Reply)) -> Self {
+        WatchMessage::Request(request, reply)
+    }
+}
+
+/// Start watching the directories in `targets` that `watched` lacks and stop
+/// watching the ones `targets` no longer has.
+fn update_watches(
+    watcher: &mut dyn Watcher,
+    watched: &mut BTreeMap<PathBuf, RecursiveMode>,
+    targets: BTreeMap<PathBuf, RecursiveMode>,
+) -> Result<()> {
+    for (path, mode) in watched.iter() {
+        if targets.get(path) != Some(mode) {
+            // The directory may be gone already.
+            let _ = watcher.unwatch(path);
+        }
+    }
+    watched.retain(|path, mode| targets.get(path) == Some(mode));
+
+    for (path, mode) in targets {
+        if watched.contains_key(&path) {
+            continue;
+        }
+        watcher
+            .watch(&path, mode)
+            .with_context(|| format!("Failed to watch {:?}", path))?;
+        if mode == RecursiveMode::Recursive {
+            info!("📁 Watching: {}", path.display());
+        } else {
+            detail!("📁 Watching manifests in: {}", path.display());
+        }
+        watched.insert(path, mode);
+    }
+    Ok(())
+}
+
+fn join_ids(plugin_ids: &BTreeSet<String>) -> String {
+    plugin_ids
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A loaded workspace and a suite runtime configured from `RunArgs`.
+struct Session {
+    host: RootDatabase,
+    vfs: Vfs,
+    manifest_path: AbsPathBuf,
+    load_options: workspace::WorkspaceLoadOptions,
+    workspace_info: workspace::WorkspaceInfo,
+    /// Command-line settings that win over `[workspace.metadata.forgen]`,
+    /// re-applied when watch mode reloads the manifest.
+    overrides: ConfigOverrides,
+    verbose: bool,
+    suite_runtime: SuiteRuntime,
+    /// The suite built by the last `run_plugins` call with `build`; watch
+    /// reruns reuse it until its sources change.
+    suite: Option<loader::PluginSuite>,
+    persist: PersistPaths,
+    passes: fixed_point::Options,
+    reports: Reports,
+}
+
+struct ConfigOverrides {
+    max_passes: Option<usize>,
+    only: Vec<String>,
+    skip: Vec<String>,
+}
+
+/// What `run_plugins` does with the output of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputMode {
+    /// Write replacement JSON, diagnostics and the suite seed to
+    /// `target/.forgen/`.
+    Save,
+    /// Leave `target/.forgen/` untouched; the caller looks at the returned
+    /// [`RunOutcome`].
+    InMemory,
+}
+
+/// What one `run_plugins` call produced.
+struct RunOutcome {
+    /// Error diagnostics plus plugins that panicked.
+    errors: usize,
+    changed_files: Vec<replacements::ChangedFile>,
+    /// Changed files without a matching `#![forgen::file(...)]`.
+    unadopted: Vec<String>,
+    diagnostics: Vec<diagnostics::SavedDiagnostic>,
+}
+
+fn load_session(args: RunArgs, mode: OutputMode) -> Result<Session> {
+    output::init(
+        args.message_format,
+        Verbosity::from_flags(args.quiet, args.verbose),
+    );
+    info!("🚀 Forgen");
+    info!("=========================================\n");
+
+    let manifest_path = args.manifest.unwrap_or_else(|| PathBuf::from("Cargo.toml"));
+    info!("📦 Loading project: {}", manifest_path.display());
 
     let manifest_path_abs = manifest_path.canonicalize()?;
     let manifest_path_str = manifest_path_abs
@@ -100,14 +697,17 @@ fn main() -> Result<()> {
 
     let metadata_start = Instant::now();
     let workspace_info = workspace::get_workspace_info(&manifest_path_abs)?;
-    replacements::clear_saved_replacements(&workspace_info.root)?;
-    println!(
+    if mode == OutputMode::Save {
+        replacements::clear_saved_replacements(&workspace_info.root)?;
+    }
+    output::phase("cargo_metadata", metadata_start);
+    info!(
         "⏱ cargo metadata + workspace discovery took {:.2?}",
         metadata_start.elapsed()
     );
 
     let load_start = Instant::now();
-    println!(
+    info!(
         "🧪 Workspace load config: proc_macros={}, build_scripts={}, prefill_caches={}",
         if args.no_proc_macros {
             "disabled"
@@ -125,193 +725,590 @@ fn main() -> Result<()> {
             "disabled"
         },
     );
-    let (mut host, mut vfs) = workspace::load_workspace(
-        &manifest_path,
-        workspace::WorkspaceLoadOptions {
-            proc_macro_server: if args.no_proc_macros {
-                ra_ap_load_cargo::ProcMacroServerChoice::None
-            } else {
-                ra_ap_load_cargo::ProcMacroServerChoice::Sysroot
-            },
-            load_out_dirs_from_check: args.with_build_scripts,
-            prefill_caches: args.with_prefill_caches,
+    let load_options = workspace::WorkspaceLoadOptions {
+        proc_macro_server: if args.no_proc_macros {
+            ra_ap_load_cargo::ProcMacroServerChoice::None
+        } else {
+            ra_ap_load_cargo::ProcMacroServerChoice::Sysroot
         },
-    )?;
-    println!("⏱ workspace load took {:.2?}", load_start.elapsed());
+        load_out_dirs_from_check: args.with_build_scripts,
+        prefill_caches: args.with_prefill_caches,
+    };
+    let (host, vfs) = workspace::load_workspace(&manifest_path, load_options.clone())?;
+    output::phase("workspace_load", load_start);
+    info!("⏱ workspace load took {:.2?}", load_start.elapsed());
 
-    let mut suite_runtime = SuiteRuntime::new();
-    println!("🎲 Suite runtime seed: {}", suite_runtime.seed());
+    let cargo_meta = &workspace_info.cargo_metadata;
+    let persist = PersistPaths {
+        state: state::resolve_state_path(args.state_file.as_deref(), cargo_meta),
+        seed_lock: seeds::resolve_lock_path(args.seed_lock.as_deref(), cargo_meta),
+    };
 
-    if args.watch {
-        println!("👀 Watch mode enabled - monitoring for changes...\n");
-        println!("Press Ctrl+C to stop\n");
-
-        run_plugins(
-            &mut host,
-            &mut vfs,
-            &workspace_info,
-            true,
-            args.verbose,
-            &mut suite_runtime,
-        )?;
+    let seed_lock = match &persist.seed_lock {
+        Some(path) => seeds::load(path)?,
+        None => None,
+    };
+    let passes = fixed_point::Options {
+        max_passes: fixed_point::resolve_max_passes(args.max_passes, cargo_meta)?,
+        trace_dir: args.trace_dir,
+    };
+    let reports = Reports {
+        diagnostics: diagnostics::Exports {
+            json: args.diagnostics_json,
+            sarif: args.diagnostics_sarif,
+        },
+        profile: profile::Options {
+            summary: args.profile,
+            trace: args.profile_trace,
+        },
+        review: review::Options {
+            diff: args.diff || args.dry_run,
+            strip_markers: args.strip_markers,
+            patch: args.patch,
+        },
+    };
 
-        let (tx, rx) = channel();
-        let mut debouncer = new_debouncer(Duration::from_millis(500), tx)?;
+    // Without a configured seed, reproduce the run that wrote the saved output.
+    let recorded_seed = match mode {
+        OutputMode::Save => None,
+        OutputMode::InMemory => seeds::recorded_suite_seed(&workspace_info.root)?,
+    };
+    let seed = seeds::resolve_suite_seed(args.seed, cargo_meta, seed_lock.as_ref(), recorded_seed)?;
+    let mut suite_runtime = SuiteRuntime::with_seed(seed);
+    info!("🎲 Suite runtime seed: {}", suite_runtime.seed());
+    if let Some(jobs) = args.jobs {
+        suite_runtime.set_jobs(jobs);
+    }
+    info!("🧵 Worker threads: {}", suite_runtime.jobs());
+    let filter = config::plugin_filter(&args.only, &args.skip, cargo_meta)?;
+    if !filter.only.is_empty() {
+        info!("🎛  Running only: {}", join_ids(&filter.only));
+    }
+    if !filter.skip.is_empty() {
+        info!("⏭  Skipping: {}", join_ids(&filter.skip));
+    }
+    suite_runtime.set_plugin_filter(filter);
+    suite_runtime.set_file_cache(!args.no_file_cache);
+    // Per-plugin results of a pass come from the profiler.
+    if reports.profile.enabled() || output::is_json() || output::shows(Verbosity::Verbose) {
+        suite_runtime.set_profiler(Some(Arc::new(Profiler::new())));
+    }
 
-        if workspace_info.members.is_empty() {
-            anyhow::bail!("No source directories found to watch");
+    if let Some(path) = &persist.seed_lock {
+        let lock = seed_lock.map(|file| file.lock).unwrap_or_default();
+        info!(
+            "📌 Seed lock enabled: {} ({} file(s) pinned)",
+            path.display(),
+            lock.files().count()
+        );
+        suite_runtime.enable_seed_lock(lock);
+    }
+
+    if let Some(path) = &persist.state {
+        let stored = state::load(path)?;
+        stored.restore_into(&mut suite_runtime);
+        info!(
+            "💾 Restored state of {} plugin(s) from {}",
+            stored.plugins.len(),
+            path.display()
+        );
+    }
+
+    Ok(Session {
+        host,
+        vfs,
+        manifest_path,
+        load_options,
+        workspace_info,
+        overrides: ConfigOverrides {
+            max_passes: args.max_passes,
+            only: args.only,
+            skip: args.skip,
+        },
+        verbose: output::traces(),
+        suite_runtime,
+        suite: None,
+        persist,
+        passes,
+        reports,
+    })
+}
+
+/// Where `run_plugins` reports what happened besides the replacement JSON.
+struct Reports {
+    diagnostics: diagnostics::Exports,
+    profile: profile::Options,
+    review: review::Options,
+}
+
+/// Files the suite runtime is persisted to after every converged run.
+struct PersistPaths {
+    state: Option<PathBuf>,
+    seed_lock: Option<PathBuf>,
+}
+
+impl PersistPaths {
+    fn save(&self, suite_runtime: &SuiteRuntime) -> Result<()> {
+        if let Some(path) = &self.state {
+            let stored = state::StateFile::from_runtime(suite_runtime);
+            state::save(path, &stored)?;
+            info!(
+                "💾 Saved state of {} plugin(s) to {}",
+                stored.plugins.len(),
+                path.display()
+            );
         }
 
-        for src_path in &workspace_info.members {
-            debouncer
-                .watcher()
-                .watch(src_path, RecursiveMode::Recursive)
-                .with_context(|| format!("Failed to watch {:?}", src_path))?;
-            println!("📁 Watching: {}", src_path.display());
+        if let (Some(path), Some(lock)) = (&self.seed_lock, suite_runtime.seed_lock()) {
+            seeds::save(path, suite_runtime.seed(), lock)?;
         }
-        println!();
 
-        loop {
-            match rx.recv() {
-                Ok(Ok(events)) => {
-                    // Collect every .rs path that was touched — this covers
-                    // creates, deletes, renames, and plain modifications since
-                    // notify-debouncer-mini collapses all of them into a single
-                    // path-level event.
-                    let changed_files: Vec<_> = events
-                        .iter()
-                        .filter(|e| e.path.extension().and_then(|s| s.to_str()) == Some("rs"))
-                        .map(|e| e.path.clone())
-                        .collect();
-
-                    if !changed_files.is_empty() {
-                        // Summarise what happened so the user knows why a
-                        // re-run was triggered (created / deleted / modified).
-                        let created: Vec<_> = changed_files
-                            .iter()
-                            .filter(|p| {
-                                // A file that exists now but the VFS doesn't
-                                // know about yet is effectively "new".
-                                p.exists()
-                            })
-                            .collect();
-                        let deleted: Vec<_> =
-                            changed_files.iter().filter(|p| !p.exists()).collect();
-
-                        if !created.is_empty() {
-                            for p in &created {
-                                println!(
-                                    "  📝 {}",
-                                    p.file_name()
-                                        .and_then(|n| n.to_str())
-                                        .unwrap_or("(unknown)")
-                                );
-                            }
-                        }
-                        if !deleted.is_empty() {
-                            for p in &deleted {
-                                println!(
-                                    "  🗑  {}",
-                                    p.file_name()
-                                        .and_then(|n| n.to_str())
-                                        .unwrap_or("(unknown)")
-                                );
-                            }
-                        }
-
-                        println!("🔄 File system change detected, re-running plugins...");
-                        match workspace::apply_file_changes(&mut host, &mut vfs, &changed_files) {
-                            Ok(_) => {
-                                match run_plugins(
-                                    &mut host,
-                                    &mut vfs,
-                                    &workspace_info,
-                                    false,
-                                    args.verbose,
-                                    &mut suite_runtime,
-                                ) {
-                                    Ok(_) => println!("✅ Done\n"),
-                                    Err(e) => eprintln!("❌ Plugin error: {}\n", e),
-                                }
-                            }
-                            Err(e) => eprintln!("❌ Error applying file changes: {}\n", e),
-                        }
-                    }
-                }
-                Ok(Err(e)) => eprintln!("Watch error: {:?}", e),
-                Err(e) => {
-                    eprintln!("Channel error: {:?}", e);
-                    break;
-                }
-            }
+        Ok(())
+    }
+}
+
+/// Run the suite to a fixed point twice without saving anything, then compare
+/// the first run with `target/.forgen/` and with the second run.
+fn run_check_command(args: RunArgs) -> Result<()> {
+    if args.watch {
+        anyhow::bail!("`cargo forgen check` does not support --watch");
+    }
+
+    let mut session = load_session(args, OutputMode::InMemory)?;
+    let initial_runtime = session.suite_runtime.clone();
+
+    info!("🔍 Check run 1/2");
+    let first = run_plugins(&mut session, true, OutputMode::InMemory)?;
+
+    // Start the second run from the same state, and without the per-file
+    // results of the first one, so both runs execute every plugin.
+    session.suite_runtime = initial_runtime;
+    session.suite_runtime.clear_file_cache();
+    info!("\n🔍 Check run 2/2 (determinism)");
+    let second = run_plugins(&mut session, false, OutputMode::InMemory)?;
+
+    let saved = replacements::load_saved_replacements(&session.workspace_info.root)?;
+    let mismatches = check::compare_saved(&first.changed_files, &saved);
+    let nondeterministic =
+        check::nondeterministic_paths(&first.changed_files, &second.changed_files);
+
+    notice!();
+    if !mismatches.is_empty() {
+        notice!(
+            "❌ target/.forgen/ is out of date for {} file(s):",
+            mismatches.len()
+        );
+        check::print_mismatches(&mismatches);
+    }
+    if !nondeterministic.is_empty() {
+        notice!(
+            "❌ Plugin output differs between two runs for {} file(s):",
+            nondeterministic.len()
+        );
+        for path in &nondeterministic {
+            notice!("   {path}");
         }
-    } else {
-        let run_start = Instant::now();
-        run_plugins(
-            &mut host,
-            &mut vfs,
-            &workspace_info,
-            true,
-            args.verbose,
-            &mut suite_runtime,
-        )?;
-        println!("⏱ plugin run took {:.2?}", run_start.elapsed());
-        println!("⏱ total CLI time {:.2?}", total_start.elapsed());
-        println!("\n✨ Done!");
     }
 
+    let mut failures = Vec::new();
+    if !mismatches.is_empty() {
+        failures.push(format!("{} stale file(s)", mismatches.len()));
+    }
+    if !nondeterministic.is_empty() {
+        failures.push(format!(
+            "{} non-deterministic file(s)",
+            nondeterministic.len()
+        ));
+    }
+    if !first.unadopted.is_empty() {
+        failures.push(format!(
+            "{} file(s) without `#![forgen::file(...)]`",
+            first.unadopted.len()
+        ));
+    }
+    if first.errors > 0 {
+        failures.push(format!("{} plugin error(s)", first.errors));
+    }
+    output::emit(&Event::Check {
+        passed: failures.is_empty(),
+        mismatches: &mismatches,
+        nondeterministic: &nondeterministic,
+    });
+    if !failures.is_empty() {
+        anyhow::bail!("check failed: {}", failures.join(", "));
+    }
+
+    notice!(
+        "✅ target/.forgen/ is up to date ({} file(s) with generated output)",
+        first.changed_files.len()
+    );
+    Ok(())
+}
+
+fn run_expand_command(args: ExpandArgs) -> Result<()> {
+    let manifest_path = args
+        .manifest_path
+        .unwrap_or_else(|| PathBuf::from("Cargo.toml"))
+        .canonicalize()?;
+    let workspace_info = workspace::get_workspace_info(&manifest_path)?;
+    let root = &workspace_info.root;
+    let rel_path = workspace_rel_path(root, &args.path)?;
+
+    expand::print(
+        root,
+        &rel_path,
+        expand::Options {
+            strip_markers: args.strip_markers,
+            color: args.color,
+        },
+    )
+}
+
+fn run_eject_command(args: EjectArgs) -> Result<()> {
+    let manifest_path = args
+        .manifest_path
+        .unwrap_or_else(|| PathBuf::from("Cargo.toml"))
+        .canonicalize()?;
+    let workspace_info = workspace::get_workspace_info(&manifest_path)?;
+    let root = &workspace_info.root;
+    let files = args
+        .files
+        .iter()
+        .map(|path| workspace_rel_path(root, path))
+        .collect::<Result<Vec<_>>>()?;
+
+    eject::run(
+        root,
+        &files,
+        &eject::Options {
+            plugins: args.plugin,
+            remove_attr: args.remove_attr,
+            force: args.force,
+        },
+    )
+}
+
+fn run_init_command(args: InitArgs) -> Result<()> {
+    let manifest_path = args
+        .manifest_path
+        .unwrap_or_else(|| PathBuf::from("Cargo.toml"))
+        .canonicalize()?;
+    let workspace_info = workspace::get_workspace_info(&manifest_path)?;
+
+    scaffold::init(
+        &workspace_info.cargo_metadata,
+        &scaffold::InitOptions {
+            name: args.name,
+            plugin_id: args.plugin,
+        },
+    )
+}
+
+fn run_new_plugin_command(args: NewPluginArgs) -> Result<()> {
+    let manifest_path = args
+        .manifest_path
+        .unwrap_or_else(|| PathBuf::from("Cargo.toml"))
+        .canonicalize()?;
+    let workspace_info = workspace::get_workspace_info(&manifest_path)?;
+
+    scaffold::new_plugin(&workspace_info.cargo_metadata, &args.id)
+}
+
+fn run_list_command(args: ListArgs) -> Result<()> {
+    output::init(args.message_format, Verbosity::Normal);
+    let manifest_path = args
+        .manifest_path
+        .unwrap_or_else(|| PathBuf::from("Cargo.toml"))
+        .canonicalize()?;
+    let workspace_info = workspace::get_workspace_info(&manifest_path)?;
+    let cargo_meta = &workspace_info.cargo_metadata;
+
+    let Some(suite_name) = loader::suite_name(cargo_meta) else {
+        anyhow::bail!(
+            "No plugin suite configured; set `suite` under [workspace.metadata.forgen] \
+             or run `cargo forgen init`"
+        );
+    };
+    let Some(suite) = loader::load_suite(cargo_meta, true) else {
+        anyhow::bail!("Failed to load plugin suite '{suite_name}'; see the messages above");
+    };
+    list::print(&suite_name, &suite.plugins()?);
+    Ok(())
+}
+
+/// Load the workspace once, run the plugins, and then run them again whenever
+/// a client sends a `run` request and sources changed.
+#[cfg(unix)]
+fn run_daemon_command(args: DaemonArgs) -> Result<()> {
+    if args.run.watch {
+        anyhow::bail!(
+            "`cargo forgen daemon` does not support --watch; send `cargo forgen client run` instead"
+        );
+    }
+    // Clients take a `run` answer to mean `target/.forgen/` is up to date.
+    if args.run.dry_run {
+        anyhow::bail!("`cargo forgen daemon` does not support --dry-run");
+    }
+    let mode = OutputMode::Save;
+    let mut session = load_session(args.run, mode)?;
+    let socket = args
+        .socket
+        .unwrap_or_else(|| daemon::default_socket_path(&session.workspace_info.cargo_metadata));
+    // Bind before the first run so clients that connect meanwhile wait for it.
+    let (tx, requests) = channel::<(daemon::Request, daemon::Reply)>();
+    let server = daemon::Server::bind(&socket, tx)?;
+
+    let mut state = DaemonState::new(&session, mode);
+    state.run(&mut session, true);
+    info!("🛰  Daemon listening on {}\n", server.path().display());
+
+    while let Ok((request, reply)) = requests.recv() {
+        if !state.answer(&mut session, request, reply) {
+            break;
+        }
+    }
+    info!("👋 Daemon stopped");
     Ok(())
 }
 
+#[cfg(not(unix))]
+fn run_daemon_command(_args: DaemonArgs) -> Result<()> {
+    anyhow::bail!("`cargo forgen daemon` needs Unix domain sockets")
+}
+
+/// Send one request to the daemon and print its JSON answer. Fails when the
+/// daemon answers with an error or its run reported plugin errors.
+#[cfg(unix)]
+fn run_client_command(args: ClientArgs) -> Result<()> {
+    let socket = match args.socket {
+        Some(socket) => socket,
+        None => default_socket(args.manifest_path)?,
+    };
+    let request = match args.request {
+        ClientRequest::Run { force } => daemon::Request::Run { force },
+        ClientRequest::Status => daemon::Request::Status,
+        ClientRequest::Diagnostics => daemon::Request::Diagnostics,
+        ClientRequest::Shutdown => daemon::Request::Shutdown,
+    };
+
+    let (line, response) = daemon::request(&socket, &request)?;
+    println!("{line}");
+    match response {
+        daemon::Response::Error { message } => anyhow::bail!("{message}"),
+        daemon::Response::Run { errors, .. } if errors > 0 => {
+            anyhow::bail!("plugins reported {errors} error(s)")
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn run_client_command(_args: ClientArgs) -> Result<()> {
+    anyhow::bail!("`cargo forgen client` needs Unix domain sockets")
+}
+
+/// The default daemon socket of the workspace at `manifest_path`.
+fn default_socket(manifest_path: Option<PathBuf>) -> Result<PathBuf> {
+    let manifest_path = manifest_path
+        .unwrap_or_else(|| PathBuf::from("Cargo.toml"))
+        .canonicalize()?;
+    // Only the target directory is needed, so skip resolving dependencies.
+    let meta = cargo_metadata::MetadataCommand::new()
+        .manifest_path(&manifest_path)
+        .no_deps()
+        .exec()
+        .context("Failed to load cargo metadata")?;
+    Ok(daemon::default_socket_path(&meta))
+}
+
+fn run_setup_command(args: SetupArgs) -> Result<()> {
+    let manifest_path = args
+        .manifest_path
+        .unwrap_or_else(|| PathBuf::from("Cargo.toml"))
+        .canonicalize()?;
+    let workspace_info = workspace::get_workspace_info(&manifest_path)?;
+    let root = &workspace_info.root;
+    let files = args
+        .files
+        .iter()
+        .map(|path| workspace_rel_path(root, path))
+        .collect::<Result<Vec<_>>>()?;
+
+    setup::run(
+        &workspace_info.cargo_metadata,
+        &setup::Options {
+            files,
+            packages: args.package,
+        },
+    )
+}
+
+/// `path` relative to the workspace root: resolved from the current directory
+/// when it exists there, otherwise taken as workspace-relative already.
+fn workspace_rel_path(root: &Path, path: &Path) -> Result<String> {
+    match path.canonicalize() {
+        Ok(abs_path) => {
+            let root = root.canonicalize()?;
+            let rel = abs_path.strip_prefix(&root).map_err(|_| {
+                anyhow::anyhow!(
+                    "{} is outside the workspace at {}",
+                    abs_path.display(),
+                    root.display()
+                )
+            })?;
+            Ok(normalize_path_str(&rel.to_string_lossy()))
+        }
+        Err(_) => Ok(normalize_path_str(&path.to_string_lossy())),
+    }
+}
+
+fn run_state_command(args: StateArgs) -> Result<()> {
+    let manifest_path = args
+        .manifest_path
+        .unwrap_or_else(|| PathBuf::from("Cargo.toml"))
+        .canonicalize()?;
+    let workspace_info = workspace::get_workspace_info(&manifest_path)?;
+
+    let Some(path) =
+        state::resolve_state_path(args.state_file.as_deref(), &workspace_info.cargo_metadata)
+    else {
+        anyhow::bail!(
+            "Plugin state persistence is not enabled. Pass `--state-file <PATH>` or set \
+             `state-file = \"...\"` under `[workspace.metadata.forgen]`."
+        );
+    };
+
+    match args.action {
+        StateAction::Show { plugin } => state::show(&path, plugin.as_deref()),
+        StateAction::Reset { plugin } => state::reset(&path, plugin.as_deref()),
+        StateAction::Edit { plugin } => state::edit(&path, &plugin),
+    }
+}
+
+fn run_seeds_command(args: SeedsArgs) -> Result<()> {
+    let manifest_path = args
+        .manifest_path
+        .unwrap_or_else(|| PathBuf::from("Cargo.toml"))
+        .canonicalize()?;
+    let workspace_info = workspace::get_workspace_info(&manifest_path)?;
+
+    let Some(path) =
+        seeds::resolve_lock_path(args.seed_lock.as_deref(), &workspace_info.cargo_metadata)
+    else {
+        anyhow::bail!(
+            "Seed lock mode is not enabled. Pass `--seed-lock <PATH>` or set \
+             `seed-lock = \"...\"` under `[workspace.metadata.forgen]`."
+        );
+    };
+
+    match args.action {
+        SeedsAction::Show => seeds::show(&path),
+        SeedsAction::Regenerate { plugin, file } => {
+            seeds::regenerate(&path, plugin.as_deref(), file.as_deref())
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Plugin runner
 // ---------------------------------------------------------------------------
 
-fn run_plugins(
-    db: &mut RootDatabase,
-    vfs: &mut Vfs,
-    workspace_info: &workspace::WorkspaceInfo,
-    build: bool,
-    verbose: bool,
-    suite_runtime: &mut SuiteRuntime,
-) -> Result<()> {
+/// Run the plugin suite to a fixed point and, with [`OutputMode::Save`], save
+/// its output.
+fn run_plugins(session: &mut Session, build: bool, mode: OutputMode) -> Result<RunOutcome> {
+    let Session {
+        host: db,
+        vfs,
+        workspace_info,
+        verbose,
+        suite_runtime,
+        suite: loaded_suite,
+        passes,
+        reports,
+        ..
+    } = session;
+    let verbose = *verbose;
+    let (passes, reports) = (&*passes, &*reports);
+    let save = mode == OutputMode::Save;
     let run_start = Instant::now();
     let project_dir = &workspace_info.root;
     let cargo_meta = &workspace_info.cargo_metadata;
+    let saved_to = save.then_some(project_dir.as_path());
 
-    replacements::clear_saved_replacements(project_dir)?;
-
-    // Plugin suite: `[workspace.metadata.forgen] suite = "..."`
-    let suite_start = Instant::now();
-    let suite = loader::load_suite(cargo_meta, build);
-    if suite.is_some() {
-        println!();
+    if save {
+        replacements::clear_saved_replacements(project_dir)?;
     }
-    println!("⏱ plugin suite load took {:.2?}", suite_start.elapsed());
 
-    let Some(suite) = suite else {
-        let write_start = Instant::now();
-        let total_saved = replacements::write_final_file_replacements(project_dir, &[])?;
-        println!(
-            "⏱ replacement JSON write took {:.2?}",
-            write_start.elapsed()
-        );
+    // Plugin suite: `[workspace.metadata.forgen] suite = "..."`. Without
+    // `build` the suite of the previous run is reused.
+    if build {
+        let suite_start = Instant::now();
+        match loader::load_suite(cargo_meta, true) {
+            Some(new_suite) => {
+                info!();
+                // Typos in plugin ids would otherwise silently run or skip
+                // nothing.
+                if let Ok(plugins) = new_suite.plugins() {
+                    list::warn_unknown_filter_ids(suite_runtime.plugin_filter(), &plugins);
+                }
+                loader::replace_suite(loaded_suite, new_suite, suite_runtime);
+            }
+            // The configured suite failed to build or load; the messages
+            // above say why. Running without it, or with the suite of the
+            // previous run, would save output that looks up to date.
+            None if loader::suite_name(cargo_meta).is_some() => {
+                anyhow::bail!("Failed to load the plugin suite; see the messages above")
+            }
+            None => {}
+        }
+        info!("⏱ plugin suite load took {:.2?}", suite_start.elapsed());
+    }
 
-        println!();
-        if total_saved > 0 {
-            println!(
-                "✅ Saved {} total replacement patch(es) to target/.forgen/",
-                total_saved
+    let Some(suite) = loaded_suite.as_ref() else {
+        if save {
+            let write_start = Instant::now();
+            replacements::write_final_file_replacements(project_dir, &[])?;
+            output::emit(&Event::Saved {
+                files: 0,
+                replacements: 0,
+                elapsed_ms: output::elapsed_ms(write_start),
+            });
+            info!(
+                "⏱ replacement JSON write took {:.2?}",
+                write_start.elapsed()
             );
-        } else {
-            println!("✅ No replacements generated");
         }
 
-        println!("⏱ run_plugins total took {:.2?}", run_start.elapsed());
-        return Ok(());
+        notice!();
+        notice!("✅ No replacements generated");
+
+        diagnostics::Collector::default().finish(
+            saved_to,
+            &reports.diagnostics,
+            &HashMap::new(),
+        )?;
+        if save {
+            seeds::record_suite_seed(project_dir, suite_runtime.seed())?;
+        }
+        output::emit(&Event::Finished {
+            replacements: 0,
+            changed_files: 0,
+            errors: 0,
+            saved: save,
+            elapsed_ms: output::elapsed_ms(run_start),
+        });
+        info!("⏱ run_plugins total took {:.2?}", run_start.elapsed());
+        return Ok(RunOutcome {
+            errors: 0,
+            changed_files: Vec::new(),
+            unadopted: Vec::new(),
+            diagnostics: Vec::new(),
+        });
     };
 
     let mut working_suite_runtime = suite_runtime.clone();
+    working_suite_runtime.begin_run();
+    let profiler = working_suite_runtime.profiler().cloned();
 
     let file_queue = enumerate_workspace_file_queue(db, vfs, project_dir, cargo_meta)?;
     let snapshots = snapshot_workspace_sources(db, vfs, project_dir, &file_queue)?;
@@ -320,45 +1317,80 @@ fn run_plugins(
         .map(|snapshot| (snapshot.rel_path.clone(), snapshot.source.clone()))
         .collect();
     let mut file_models = replacements::build_file_models(&original_sources);
+    let mut collected_diagnostics = diagnostics::Collector::default();
+    let mut panicked_plugins: BTreeMap<String, String> = BTreeMap::new();
+    let mut skipped_plugins: BTreeSet<String> = BTreeSet::new();
+    if let Some(lock) = working_suite_runtime.seed_lock_mut() {
+        for (from, to) in lock.sync_files(&original_sources) {
+            info!("📌 Pinned seeds moved with file: {from} → {to}");
+        }
+    }
     let abs_paths_by_rel: HashMap<String, PathBuf> = snapshots
         .iter()
         .map(|snapshot| (snapshot.rel_path.clone(), snapshot.abs_path.clone()))
         .collect();
 
-    let fixed_point_result = run_fixed_point_passes(
+    let fixed_point_result = fixed_point::run_fixed_point_passes(
         original_sources.clone(),
+        passes,
         |pass, current_sources| {
-            println!("🧩 Pass {pass}/{MAX_PLUGIN_PASSES}");
+            output::emit(&Event::PassStart {
+                pass,
+                max_passes: passes.max_passes,
+            });
+            info!("🧩 Pass {pass}/{}", passes.max_passes);
             apply_source_snapshot(db, vfs, &abs_paths_by_rel, current_sources, true)?;
 
             let pass_start = Instant::now();
+            if let Some(profiler) = &profiler {
+                profiler.set_pass(pass);
+            }
+            let mut cached_results = 0;
             let pass_output = attach_db_allow_change(db, || -> Result<_> {
-                println!("  entering semantics init");
-                let sema = Semantics::new(db);
-                println!("  finished semantics init");
-
-                println!(
+                info!(
                     "  Building workspace context from {} file(s)...",
                     file_queue.len()
                 );
 
+                let db_thread = DbThread::new();
+                let jobs = working_suite_runtime.jobs();
                 let workspace_ctx_start = Instant::now();
                 let ctx = build_workspace_context(
-                    &sema,
                     db,
                     vfs,
-                    project_dir,
+                    workspace_info,
                     file_queue.clone(),
-                    cargo_meta,
                     verbose,
+                    &db_thread.executor(),
+                    jobs,
                 )?;
-                println!(
+                info!(
                     "  workspace context build took {:.2?}",
                     workspace_ctx_start.elapsed()
                 );
+                if let Some(profiler) = &profiler {
+                    profiler.span("build workspace context", workspace_ctx_start);
+                }
 
                 let suite_exec_start = Instant::now();
-                let file_replacements = suite.run(&ctx, &mut working_suite_runtime);
+                // Plugins on worker threads send rust-analyzer work back here.
+                let file_replacements = if jobs > 1 {
+                    db_thread.serve(|| suite.run(&ctx, &mut working_suite_runtime))?
+                } else {
+                    suite.run(&ctx, &mut working_suite_runtime)?
+                };
+                if let Some(profiler) = &profiler {
+                    profiler.span("suite execution", suite_exec_start);
+                }
+                let cache_stats = working_suite_runtime.take_file_cache_stats();
+                cached_results = cache_stats.hits;
+                if cache_stats.hits > 0 {
+                    info!(
+                        "  reused {} of {} per-file plugin result(s)",
+                        cache_stats.hits,
+                        cache_stats.hits + cache_stats.misses
+                    );
+                }
                 let mut replacements_by_path: HashMap<String, Vec<Replacement>> = HashMap::new();
                 let mut generated_replacements = 0usize;
 
@@ -374,18 +1406,35 @@ fn run_plugins(
                         .extend(fr.replacements);
                 }
 
-                println!(
+                info!(
                     "  suite execution took {:.2?} ({} replacement(s))",
                     suite_exec_start.elapsed(),
                     generated_replacements
                 );
 
-                Ok(PassOutput {
+                Ok(fixed_point::PassOutput {
                     replacements_by_path,
                     generated_replacements,
                 })
             })?;
 
+            // Diagnostic ranges refer to this pass's sources, which the file
+            // models still describe until the replacements below are applied.
+            collected_diagnostics.record(working_suite_runtime.take_diagnostics(), &file_models);
+            for panic in working_suite_runtime.take_panics() {
+                panicked_plugins.insert(panic.plugin_id, panic.message);
+            }
+            for warning in working_suite_runtime.take_warnings() {
+                output::warn(warning);
+            }
+            if let Some(error) = working_suite_runtime.take_schedule_error() {
+                anyhow::bail!("Invalid plugin schedule: {error}");
+            }
+            skipped_plugins.extend(working_suite_runtime.take_skipped());
+            if let Some(profiler) = &profiler {
+                report_plugin_runs(pass, &profiler.runs_in_pass(pass));
+            }
+
             let changed_paths = replacements::changed_paths_from_replacements(
                 current_sources,
                 &pass_output.replacements_by_path,
@@ -394,7 +1443,7 @@ fn run_plugins(
             changed_paths.sort();
 
             if changed_paths.is_empty() {
-                println!(
+                info!(
                     "  pass {pass} converged in {:.2?} ({} replacement(s), no source changes)",
                     pass_start.elapsed(),
                     pass_output.generated_replacements
@@ -424,22 +1473,34 @@ fn run_plugins(
                     })?;
                 }
 
-                let changed_files = replacements::collect_changed_files(&file_models)?;
-                let saved_count =
-                    replacements::replace_saved_replacements(project_dir, &changed_files)?;
-                println!(
-                    "  saved intermediate replacement JSON after pass {pass} ({} patch(es))",
-                    saved_count
-                );
+                if save {
+                    let changed_files = replacements::collect_changed_files(&file_models)?;
+                    let saved_count =
+                        replacements::replace_saved_replacements(project_dir, &changed_files)?;
+                    info!(
+                        "  saved intermediate replacement JSON after pass {pass} ({} patch(es))",
+                        saved_count
+                    );
+                }
 
-                println!(
+                info!(
                     "  pass {pass} changed {} file(s) in {:.2?}: {}",
                     changed_paths.len(),
                     pass_start.elapsed(),
                     changed_paths.join(", ")
                 );
             }
+            output::emit(&Event::PassEnd {
+                pass,
+                replacements: pass_output.generated_replacements,
+                cached_results,
+                changed_files: &changed_paths,
+                elapsed_ms: output::elapsed_ms(pass_start),
+            });
 
+            if let Some(profiler) = &profiler {
+                profiler.span(format!("pass {pass}"), pass_start);
+            }
             Ok(pass_output)
         },
     );
@@ -458,42 +1519,124 @@ fn run_plugins(
         }
     };
 
-    let write_start = Instant::now();
     let changed_files = replacements::collect_changed_files(&file_models)?;
-    let total_saved = replacements::replace_saved_replacements(project_dir, &changed_files)?;
-    println!(
-        "⏱ replacement JSON write took {:.2?}",
-        write_start.elapsed()
-    );
+    let total_patches: usize = changed_files
+        .iter()
+        .map(|file| file.replacements.len())
+        .sum();
+    if save {
+        let write_start = Instant::now();
+        replacements::replace_saved_replacements(project_dir, &changed_files)?;
+        output::emit(&Event::Saved {
+            files: changed_files.len(),
+            replacements: total_patches,
+            elapsed_ms: output::elapsed_ms(write_start),
+        });
+        info!(
+            "⏱ replacement JSON write took {:.2?}",
+            write_start.elapsed()
+        );
+    }
 
-    println!();
-    if total_saved > 0 {
-        println!(
+    notice!();
+    if total_patches > 0 && save {
+        notice!(
             "✅ Saved {} total replacement patch(es) to target/.forgen/",
-            total_saved
+            total_patches
+        );
+    } else if total_patches > 0 {
+        notice!(
+            "✅ Generated {} total replacement patch(es) (not saved)",
+            total_patches
         );
     } else if fixed_point_result.total_generated_replacements > 0 {
-        println!("✅ Replacements were generated, but all serialised patch sets were empty");
+        notice!("✅ Replacements were generated, but all serialised patch sets were empty");
     } else {
-        println!("✅ No replacements generated");
+        notice!("✅ No replacements generated");
+    }
+
+    let unadopted = setup::unadopted_files(&changed_files, &original_sources);
+    if !unadopted.is_empty() {
+        setup::print_unadopted(&unadopted);
     }
 
-    println!("⏱ run_plugins total took {:.2?}", run_start.elapsed());
+    review::report(&file_models, &reports.review)?;
+
+    let mut plugin_errors =
+        collected_diagnostics.finish(saved_to, &reports.diagnostics, &original_sources)?;
+    if !skipped_plugins.is_empty() {
+        output::emit(&Event::Skipped {
+            plugins: &skipped_plugins,
+        });
+        notice!(
+            "\n⏭  Skipped {} plugin(s): {}",
+            skipped_plugins.len(),
+            join_ids(&skipped_plugins)
+        );
+    }
+    if !panicked_plugins.is_empty() {
+        notice!();
+        for (plugin_id, message) in &panicked_plugins {
+            output::emit(&Event::Panicked {
+                plugin: plugin_id,
+                message,
+            });
+            notice!("💥 plugin `{plugin_id}` panicked: {message} (its output was discarded)");
+        }
+        plugin_errors += panicked_plugins.len();
+    }
+    if let Some(profiler) = &profiler {
+        profile::report(&profiler.take(), &reports.profile)?;
+    }
+    if save {
+        seeds::record_suite_seed(project_dir, working_suite_runtime.seed())?;
+    }
+    output::emit(&Event::Finished {
+        replacements: total_patches,
+        changed_files: changed_files.len(),
+        errors: plugin_errors,
+        saved: save,
+        elapsed_ms: output::elapsed_ms(run_start),
+    });
+    info!("⏱ run_plugins total took {:.2?}", run_start.elapsed());
 
     *suite_runtime = working_suite_runtime;
 
-    Ok(())
+    Ok(RunOutcome {
+        errors: plugin_errors,
+        changed_files,
+        unadopted,
+        diagnostics: collected_diagnostics
+            .diagnostics()
+            .into_iter()
+            .cloned()
+            .collect(),
+    })
 }
 
-#[derive(Debug)]
-struct PassOutput {
-    replacements_by_path: HashMap<String, Vec<Replacement>>,
-    generated_replacements: usize,
-}
+/// Report the plugin runs a profiler recorded in `pass`, one line or event
+/// per plugin.
+fn report_plugin_runs(pass: usize, runs: &[ProfileEvent]) {
+    let mut by_plugin: Vec<(&str, usize, Duration)> = Vec::new();
+    for run in runs {
+        match by_plugin.iter_mut().find(|(id, ..)| *id == run.name) {
+            Some((_, replacements, elapsed)) => {
+                *replacements += run.replacements;
+                *elapsed += run.duration;
+            }
+            None => by_plugin.push((&run.name, run.replacements, run.duration)),
+        }
+    }
 
-#[derive(Debug)]
-struct FixedPointResult {
-    total_generated_replacements: usize,
+    for (plugin, replacements, elapsed) in by_plugin {
+        output::emit(&Event::Plugin {
+            pass,
+            plugin,
+            replacements,
+            elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+        });
+        detail!("  🔌 {plugin}: {replacements} replacement(s) in {elapsed:.2?}");
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -503,61 +1646,6 @@ struct WorkspaceFileSnapshot {
     source: String,
 }
 
-fn run_fixed_point_passes<F>(
-    initial_sources: HashMap<String, String>,
-    mut run_pass: F,
-) -> Result<FixedPointResult>
-where
-    F: FnMut(usize, &HashMap<String, String>) -> Result<PassOutput>,
-{
-    let mut current_sources = initial_sources;
-    let mut total_generated_replacements = 0usize;
-
-    for pass in 1..=MAX_PLUGIN_PASSES {
-        let pass_output = run_pass(pass, &current_sources)?;
-        total_generated_replacements += pass_output.generated_replacements;
-
-        let changed_paths = replacements::changed_paths_from_replacements(
-            &current_sources,
-            &pass_output.replacements_by_path,
-        )?;
-
-        if changed_paths.is_empty() {
-            return Ok(FixedPointResult {
-                total_generated_replacements,
-            });
-        }
-
-        let mut changed_paths: Vec<_> = changed_paths.into_iter().collect();
-        changed_paths.sort();
-
-        if pass == MAX_PLUGIN_PASSES {
-            anyhow::bail!(
-                "Plugin execution did not converge after {} passes; files still changing: {}",
-                MAX_PLUGIN_PASSES,
-                changed_paths.join(", ")
-            );
-        }
-
-        let mut next_sources = current_sources.clone();
-        for path in changed_paths {
-            let source = current_sources
-                .get(&path)
-                .ok_or_else(|| anyhow::anyhow!("Replacement references unknown file `{path}`"))?;
-            let replacements = pass_output
-                .replacements_by_path
-                .get(&path)
-                .ok_or_else(|| anyhow::anyhow!("Missing replacement set for `{path}`"))?;
-            let rewritten = replacements::apply_replacements_to_source(source, replacements)
-                .with_context(|| format!("Failed to rewrite `{path}` during pass {pass}"))?;
-            next_sources.insert(path, rewritten);
-        }
-        current_sources = next_sources;
-    }
-
-    unreachable!("fixed-point pass loop should either converge or bail")
-}
-
 fn enumerate_workspace_file_queue(
     db: &RootDatabase,
     vfs: &Vfs,
@@ -643,7 +1731,7 @@ fn enumerate_workspace_file_queue(
     });
     file_queue.dedup_by_key(|ef| ef.file_id(db));
 
-    println!(
+    detail!(
         "  crate-graph file enumeration took {:.2?} (local={}, skipped={})",
         enumerate_start.elapsed(),
         local_vfs_file_count,
@@ -840,20 +1928,21 @@ impl FileRuntime {
 }
 
 fn build_workspace_context(
-    _sema: &Semantics<RootDatabase>,
     db: &RootDatabase,
     vfs: &Vfs,
-    project_dir: &PathBuf,
+    workspace_info: &workspace::WorkspaceInfo,
     file_queue: Vec<EditionedFileId>,
-    cargo_meta: &cargo_metadata::Metadata,
     verbose: bool,
+    executor: &DbExecutor,
+    jobs: usize,
 ) -> Result<WorkspaceContext> {
     let ctx_start = Instant::now();
+    let project_dir = &workspace_info.root;
 
     let root_norm = normalize_path_str(&project_dir.to_string_lossy());
     let manifest_start = Instant::now();
-    let manifest = build_manifest(cargo_meta);
-    println!("⏱ manifest build took {:.2?}", manifest_start.elapsed());
+    let manifest = build_manifest(&workspace_info.cargo_metadata);
+    detail!("⏱ manifest build took {:.2?}", manifest_start.elapsed());
 
     // Build the CliOracle (shared across all file contexts for this run).
     let mut file_map: HashMap<String, EditionedFileId> = HashMap::new();
@@ -880,6 +1969,7 @@ fn build_workspace_context(
         file_map,
         root_norm: root_norm.clone(),
         verbose,
+        executor: executor.clone(),
     });
     let workspace_handle: SemanticHandle = oracle.clone().into_handle();
 
@@ -890,10 +1980,13 @@ fn build_workspace_context(
         _not_send_sync: PhantomData,
     };
 
-    let mut files: Vec<ApiFileContext> = Vec::new();
     let mut paths_for_tree: Vec<String> = Vec::new();
     let mut skipped_files = 0usize;
+    let mut file_entries: Vec<(FileRuntime, String)> = Vec::new();
+    let mut sources = Vec::new();
 
+    // Source text is read from the database on this thread; parsing does not
+    // need rust-analyzer and is fanned out below.
     for editioned_id in file_queue {
         let runtime = FileRuntime {
             shared,
@@ -906,29 +1999,37 @@ fn build_workspace_context(
         };
 
         paths_for_tree.push(rel_path.clone());
+        let (_file_id, edition) = editioned_id.unpack(db);
+        sources.push((runtime.source(), edition));
+        file_entries.push((runtime, rel_path));
+    }
+
+    // ── Syntax pass (no RA) ───────────────────────────────────────────────
+    // Extract binding stubs from the CST, then enrich each unannotated binding
+    // with a per-binding lazy closure that fires RA on first `.ty()` call.
+    // This is safe because the oracle (and therefore `db`) remains valid for
+    // the lifetime of the `attach_db_allow_change` scope that wraps both this
+    // build step AND `plugin.run()`.
+    let syntax_bindings = forgen_api::parallel::map_ordered(jobs, sources, |(source, edition)| {
+        oracle::extract_let_bindings_from_syntax(
+            SourceFile::parse(&source, edition).tree().syntax(),
+        )
+    });
 
-        // ── Syntax pass (no RA) ───────────────────────────────────────────
-        // Extract binding stubs from the CST, then enrich each unannotated
-        // binding with a per-binding lazy closure that fires RA on first
-        // `.ty()` call.  This is safe because the oracle (and therefore `db`)
-        // remains valid for the lifetime of the `attach_db_allow_change` scope
-        // that wraps both this build step AND `plugin.run()`.
-        let syntax_bindings =
-            oracle::extract_let_bindings_from_syntax(&runtime.syntax_from_source());
-        let let_bindings: Vec<LetBinding> = {
-            let oracle_for_bindings = Arc::clone(&oracle);
-            let rel_for_bindings = rel_path.clone();
-            syntax_bindings
-                .into_iter()
-                .map(move |b| {
-                    let inferred_type = if b.explicit_type.is_some() {
-                        // Annotated — no RA ever needed.
-                        LazyValue::from_value(None)
-                    } else if let Some(init_range) = b.initializer_range {
-                        // Unannotated — defer to oracle on first `.ty()` call.
-                        let o = Arc::clone(&oracle_for_bindings);
-                        let fp = rel_for_bindings.clone();
-                        LazyValue::new(move || {
+    let mut files: Vec<ApiFileContext> = Vec::with_capacity(file_entries.len());
+    for ((runtime, rel_path), syntax_bindings) in file_entries.into_iter().zip(syntax_bindings) {
+        let let_bindings: Vec<LetBinding> = syntax_bindings
+            .into_iter()
+            .map(|b| {
+                let inferred_type = if b.explicit_type.is_some() {
+                    // Annotated — no RA ever needed.
+                    LazyValue::from_value(None)
+                } else if let Some(init_range) = b.initializer_range {
+                    // Unannotated — defer to oracle on first `.ty()` call.
+                    let o = Arc::clone(&oracle);
+                    let fp = rel_path.clone();
+                    executor.lazy(move || {
+                        oracle::retry_cancelled("type inference", || {
                             let db = unsafe { &*o.db };
                             let sema_inner = ra_ap_hir::Semantics::new(db);
                             o.file_map.get(&fp).and_then(|&eid| {
@@ -942,36 +2043,27 @@ fn build_workspace_context(
                                 )
                             })
                         })
-                    } else {
-                        LazyValue::from_value(None)
-                    };
-                    LetBinding { inferred_type, ..b }
-                })
-                .collect()
-        };
+                        .flatten()
+                    })
+                } else {
+                    LazyValue::from_value(None)
+                };
+                LetBinding { inferred_type, ..b }
+            })
+            .collect();
 
         let file_handle: SemanticHandle = Arc::clone(&oracle).into_handle();
 
-        let source_runtime = runtime;
-        let generated_regions_runtime = runtime;
-        let tree_runtime = runtime;
-        let functions_runtime = runtime;
-        let structs_runtime = runtime;
-        let enums_runtime = runtime;
-        let impls_runtime = runtime;
-
         files.push(ApiFileContext::new(
             rel_path,
-            LazyValue::new(move || source_runtime.source()),
-            LazyValue::new(move || {
-                forgen_api::parse_generated_regions(&generated_regions_runtime.source())
-            }),
-            LazyValue::new(move || tree_runtime.tree()),
+            executor.lazy(move || runtime.source()),
+            executor.lazy(move || forgen_api::parse_generated_regions(&runtime.source())),
+            executor.lazy(move || runtime.tree()),
             LazyValue::from_value(let_bindings),
-            LazyValue::new(move || functions_runtime.functions()),
-            LazyValue::new(move || structs_runtime.structs()),
-            LazyValue::new(move || enums_runtime.enums()),
-            LazyValue::new(move || impls_runtime.impls()),
+            executor.lazy(move || runtime.functions()),
+            executor.lazy(move || runtime.structs()),
+            executor.lazy(move || runtime.enums()),
+            executor.lazy(move || runtime.impls()),
             Some(file_handle),
         ));
     }
@@ -979,14 +2071,14 @@ fn build_workspace_context(
     let file_tree_start = Instant::now();
     let file_tree_paths = paths_for_tree.clone();
     let file_tree = LazyValue::new(move || build_file_tree(&file_tree_paths));
-    println!(
+    detail!(
         "⏱ workspace context file handle build took {:.2?} (files={}, skipped={})",
         file_tree_start.elapsed(),
         files.len(),
         skipped_files
     );
 
-    println!(
+    detail!(
         "⏱ build_workspace_context total took {:.2?} (files={}, skipped={})",
         ctx_start.elapsed(),
         files.len(),
@@ -1397,8 +2489,16 @@ fn build_manifest(meta: &cargo_metadata::Metadata) -> WorkspaceManifest {
                 .map(|(k, v): (&String, &Vec<String>)| (k.clone(), v.clone()))
                 .collect();
 
+            let root = pkg
+                .manifest_path
+                .parent()
+                .and_then(|dir| dir.strip_prefix(&meta.workspace_root).ok())
+                .map(|dir| dir.as_str().replace('\\', "/"))
+                .unwrap_or_default();
+
             PackageManifest {
                 name: pkg.name.clone(),
+                root,
                 version: pkg.version.to_string(),
                 edition: pkg.edition.to_string(),
                 authors: pkg.authors.clone(),
@@ -1,15 +1,23 @@
+use crate::db_thread::DbExecutor;
+use crate::output;
 use forgen_api::{
     query::{SemanticHandle, SemanticQuery, SemanticResult},
     LazyValue, LetBinding, TextRange,
 };
+use ra_ap_base_db::salsa::Cancelled;
 use ra_ap_hir::{DisplayTarget, HirDisplay, Semantics};
 use ra_ap_ide_db::EditionedFileId;
 use ra_ap_ide_db::RootDatabase;
 use ra_ap_syntax::{ast, ast::AstNode, ast::HasName};
 use ra_ap_vfs::Vfs;
 use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 
+/// How often a semantic query is attempted before a rust-analyzer
+/// cancellation is reported and the query answers with an empty result.
+const MAX_QUERY_ATTEMPTS: usize = 3;
+
 /// Wraps rust-analyzer handles behind raw pointers so the oracle can be
 /// stored in an `Arc` and passed through lazy closures.
 ///
@@ -28,11 +36,14 @@ pub(crate) struct CliOracle {
     pub root_norm: String,
     /// When true, emit `[oracle]` lines to stderr for every RA inference call.
     pub verbose: bool,
+    /// Runs queries on the thread that owns `db`.
+    pub executor: DbExecutor,
 }
 
 // SAFETY: CliOracle is only used inside the `attach_db_allow_change` scope,
-// which is single-threaded in our usage.  The raw pointers are valid for the
-// lifetime of that scope.
+// and every query is sent through `executor`, so `db` is only dereferenced on
+// the thread that owns it.  The raw pointers are valid for the lifetime of
+// that scope.
 unsafe impl Send for CliOracle {}
 unsafe impl Sync for CliOracle {}
 
@@ -40,18 +51,26 @@ impl CliOracle {
     /// Wrap `self` in a `SemanticHandle` that plugins can use.
     pub fn into_handle(self: Arc<Self>) -> SemanticHandle {
         SemanticHandle {
-            oracle: Arc::new(move |q| self.dispatch(q)),
+            oracle: Arc::new(move |q| {
+                let oracle = Arc::clone(&self);
+                self.executor.run(move || oracle.dispatch(q))
+            }),
         }
     }
 
     fn dispatch(&self, q: SemanticQuery) -> SemanticResult {
+        retry_cancelled("semantic query", || self.dispatch_once(&q))
+            .unwrap_or(SemanticResult::Unsupported)
+    }
+
+    fn dispatch_once(&self, q: &SemanticQuery) -> SemanticResult {
         let db = unsafe { &*self.db };
         let sema = Semantics::new(db);
 
         match q {
             SemanticQuery::InferTypeAt { file, range } => {
-                let result = self.file_map.get(&file).and_then(|&eid| {
-                    infer_type_at_range(&sema, db, eid, range, &file, self.verbose)
+                let result = self.file_map.get(file).and_then(|&eid| {
+                    infer_type_at_range(&sema, db, eid, *range, file, self.verbose)
                 });
                 SemanticResult::InferredType(result)
             }
@@ -59,8 +78,8 @@ impl CliOracle {
             SemanticQuery::LetBindings { file } => {
                 let result = self
                     .file_map
-                    .get(&file)
-                    .map(|&eid| self.compute_let_bindings_all(&sema, db, eid, &file))
+                    .get(file)
+                    .map(|&eid| self.compute_let_bindings_all(&sema, db, eid, file))
                     .unwrap_or_default();
                 SemanticResult::LetBindings(result)
             }
@@ -68,8 +87,8 @@ impl CliOracle {
             SemanticQuery::LetBindingsInScope { file, scope } => {
                 let result = self
                     .file_map
-                    .get(&file)
-                    .map(|&eid| self.compute_let_bindings_in_scope(&sema, db, eid, scope, &file))
+                    .get(file)
+                    .map(|&eid| self.compute_let_bindings_in_scope(&sema, db, eid, *scope, file))
                     .unwrap_or_default();
                 SemanticResult::LetBindings(result)
             }
@@ -134,6 +153,24 @@ impl CliOracle {
 // Free helpers (no self needed)
 // ---------------------------------------------------------------------------
 
+/// Run `query`, retrying when rust-analyzer cancels it. Returns `None` (after
+/// reporting it) when every attempt was cancelled, so a cancellation never
+/// unwinds into plugin code.
+pub(crate) fn retry_cancelled<T>(what: &str, query: impl Fn() -> T) -> Option<T> {
+    for attempt in 1..=MAX_QUERY_ATTEMPTS {
+        match Cancelled::catch(AssertUnwindSafe(&query)) {
+            Ok(value) => return Some(value),
+            Err(cancelled) if attempt == MAX_QUERY_ATTEMPTS => {
+                output::warn(format_args!(
+                    "{what} was cancelled by rust-analyzer {MAX_QUERY_ATTEMPTS} times ({cancelled:?}); answering with an empty result"
+                ));
+            }
+            Err(_) => {}
+        }
+    }
+    None
+}
+
 /// Infer the type of the expression whose byte range matches `range`.
 /// `range` should be the `initializer_range` stored in `LetBinding`.
 ///
@@ -0,0 +1,290 @@
+use crate::check::Mismatch;
+use crate::diagnostics::SavedDiagnostic;
+use forgen_api::profile::ProfileSummary;
+use forgen_api::PluginInfo;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::Instant;
+
+/// How `cargo forgen` and `cargo forgen check` report what they do.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Emoji-decorated progress lines for people.
+    #[default]
+    Human,
+    /// One JSON [`Event`] per line on stdout.
+    Json,
+}
+
+/// How much human output to print. JSON mode always emits every event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Results, warnings, diagnostics and explicitly requested reports only.
+    Quiet,
+    Normal,
+    /// Also per-plugin results of every pass and rust-analyzer load progress.
+    Verbose,
+    /// Also oracle inference traces on stderr.
+    Trace,
+}
+
+impl Verbosity {
+    /// `-q` wins over any number of `-v`s.
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Trace,
+        }
+    }
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// Set the output mode for the rest of the process. Commands that never call
+/// this print human output at [`Verbosity::Normal`].
+pub fn init(format: MessageFormat, verbosity: Verbosity) {
+    JSON.store(format == MessageFormat::Json, Ordering::Relaxed);
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Whether human output of `level` is printed.
+pub fn shows(level: Verbosity) -> bool {
+    !is_json() && VERBOSITY.load(Ordering::Relaxed) >= level as u8
+}
+
+/// Whether oracle inference traces go to stderr (`-vv`).
+pub fn traces() -> bool {
+    VERBOSITY.load(Ordering::Relaxed) >= Verbosity::Trace as u8
+}
+
+/// `println!` for results, also printed with `--quiet`.
+macro_rules! notice {
+    ($($arg:tt)*) => {
+        if $crate::output::shows($crate::output::Verbosity::Quiet) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// `println!` for regular progress output.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::output::shows($crate::output::Verbosity::Normal) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// `println!` for `-v` output.
+macro_rules! detail {
+    ($($arg:tt)*) => {
+        if $crate::output::shows($crate::output::Verbosity::Verbose) {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use {detail, info, notice};
+
+// ---------------------------------------------------------------------------
+// JSON events
+// ---------------------------------------------------------------------------
+
+/// One line of `--message-format=json` output. Times are in milliseconds.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// A load step finished: `cargo_metadata`, `workspace_load`,
+    /// `suite_build` or `suite_load`.
+    Phase {
+        name: &'a str,
+        elapsed_ms: f64,
+    },
+    PassStart {
+        pass: usize,
+        max_passes: usize,
+    },
+    /// The runs of one plugin in a pass.
+    Plugin {
+        pass: usize,
+        plugin: &'a str,
+        replacements: usize,
+        elapsed_ms: f64,
+    },
+    PassEnd {
+        pass: usize,
+        replacements: usize,
+        /// Per-file plugin results reused from the file cache.
+        cached_results: usize,
+        changed_files: &'a [String],
+        elapsed_ms: f64,
+    },
+    /// Replacement JSON written to `target/.forgen/`.
+    Saved {
+        files: usize,
+        replacements: usize,
+        elapsed_ms: f64,
+    },
+    Diagnostic {
+        #[serde(flatten)]
+        diagnostic: &'a SavedDiagnostic,
+    },
+    /// The `--diff` / `--dry-run` patch.
+    Diff {
+        patch: &'a str,
+    },
+    /// Changed files without a matching `#![forgen::file(...)]`.
+    Unadopted {
+        files: &'a [String],
+    },
+    Skipped {
+        plugins: &'a BTreeSet<String>,
+    },
+    Panicked {
+        plugin: &'a str,
+        message: &'a str,
+    },
+    /// The `--profile` summary.
+    Profile {
+        summary: &'a ProfileSummary,
+    },
+    /// The end of one run to a fixed point.
+    Finished {
+        replacements: usize,
+        changed_files: usize,
+        errors: usize,
+        saved: bool,
+        elapsed_ms: f64,
+    },
+    /// The result of `cargo forgen check`.
+    Check {
+        passed: bool,
+        mismatches: &'a [Mismatch],
+        nondeterministic: &'a [String],
+    },
+    /// The plugins of the suite, for `cargo forgen list`.
+    Suite {
+        name: &'a str,
+        plugins: &'a [PluginInfo],
+    },
+    /// Watch mode loaded a rebuilt suite. Removed plugins lost their state.
+    Reloaded {
+        removed_plugins: &'a [String],
+    },
+    /// Watch mode re-runs the plugins because these files changed.
+    Rerun {
+        files: Vec<String>,
+    },
+    Warning {
+        message: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Write `event` as one line to stdout if JSON output is enabled.
+pub fn emit(event: &Event) {
+    if !is_json() {
+        return;
+    }
+    let line = serde_json::to_string(event).expect("events serialize to JSON");
+    let mut stdout = std::io::stdout().lock();
+    // A closed pipe is not worth failing the run for.
+    let _ = writeln!(stdout, "{line}");
+    let _ = stdout.flush();
+}
+
+pub fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Emit a [`Event::Phase`] for a step that started at `start`.
+pub fn phase(name: &str, start: Instant) {
+    emit(&Event::Phase {
+        name,
+        elapsed_ms: elapsed_ms(start),
+    });
+}
+
+/// Report a problem that does not stop the command.
+pub fn warn(message: impl Display) {
+    if is_json() {
+        emit(&Event::Warning {
+            message: message.to_string(),
+        });
+    } else {
+        eprintln!("  ⚠️  {message}");
+    }
+}
+
+/// Report an error that the command recovers from, like a failed watch rerun.
+pub fn error(message: impl Display) {
+    if is_json() {
+        emit(&Event::Error {
+            message: message.to_string(),
+        });
+    } else {
+        eprintln!("❌ {message}\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_wins_over_verbose_flags() {
+        assert_eq!(Verbosity::from_flags(false, 0), Verbosity::Normal);
+        assert_eq!(Verbosity::from_flags(false, 1), Verbosity::Verbose);
+        assert_eq!(Verbosity::from_flags(false, 3), Verbosity::Trace);
+        assert_eq!(Verbosity::from_flags(true, 2), Verbosity::Quiet);
+    }
+
+    #[test]
+    fn events_are_tagged_objects() {
+        let changed = vec!["src/lib.rs".to_owned()];
+        let event = Event::PassEnd {
+            pass: 2,
+            replacements: 3,
+            cached_results: 0,
+            changed_files: &changed,
+            elapsed_ms: 1.5,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "event": "pass_end",
+                "pass": 2,
+                "replacements": 3,
+                "cached_results": 0,
+                "changed_files": ["src/lib.rs"],
+                "elapsed_ms": 1.5,
+            })
+        );
+
+        let mismatches = vec![Mismatch::Missing {
+            path: "src/lib.rs".to_owned(),
+            expected: 1,
+        }];
+        let event = Event::Check {
+            passed: false,
+            mismatches: &mismatches,
+            nondeterministic: &[],
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap()["mismatches"],
+            serde_json::json!([{ "kind": "missing", "path": "src/lib.rs", "expected": 1 }])
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use crate::output::{self, info, notice, Event};
+use anyhow::{Context, Result};
+use forgen_api::profile::{EventKind, Profile, ProfileSummary, TimingStats};
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// What to do with the profile of a run.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Print the per-plugin, per-query and per-field tables.
+    pub summary: bool,
+    /// Write a Chrome trace (`chrome://tracing`, Perfetto) to this file.
+    pub trace: Option<PathBuf>,
+}
+
+impl Options {
+    pub fn enabled(&self) -> bool {
+        self.summary || self.trace.is_some()
+    }
+}
+
+/// Print the summary and write the trace of `profile` as requested.
+pub fn report(profile: &Profile, options: &Options) -> Result<()> {
+    let summary = profile.summary();
+    if options.summary {
+        output::emit(&Event::Profile { summary: &summary });
+        print_summary(&summary);
+    }
+    if let Some(path) = &options.trace {
+        write_file(
+            path,
+            &serde_json::to_string(&chrome_trace(profile, &summary))?,
+        )?;
+        info!("📝 Wrote profile trace to {}", path.display());
+    }
+    Ok(())
+}
+
+fn print_summary(summary: &ProfileSummary) {
+    notice!("\n📊 Plugin profile");
+    if summary.plugins.is_empty() {
+        notice!("   (no plugin runs recorded)");
+        return;
+    }
+
+    let width = summary
+        .plugins
+        .iter()
+        .map(|plugin| plugin.plugin_id.len())
+        .max()
+        .unwrap_or(0)
+        .max("plugin".len());
+    notice!(
+        "   {:<width$}  {:>5}  {:>10}  {:>10}  {:>8}  {:>7}  {:>10}  {:>5}  {:>10}",
+        "plugin",
+        "runs",
+        "total",
+        "max",
+        "replaced",
+        "queries",
+        "query",
+        "lazy",
+        "lazy time"
+    );
+    for plugin in &summary.plugins {
+        notice!(
+            "   {:<width$}  {:>5}  {:>10}  {:>10}  {:>8}  {:>7}  {:>10}  {:>5}  {:>10}",
+            plugin.plugin_id,
+            plugin.runs,
+            millis(plugin.run_time),
+            millis(plugin.max_run_time),
+            plugin.replacements,
+            plugin.queries,
+            millis(plugin.query_time),
+            plugin.lazy_inits,
+            millis(plugin.lazy_init_time),
+        );
+    }
+
+    print_stats("semantic query", &summary.queries);
+    print_stats("lazy field", &summary.lazy_fields);
+}
+
+fn print_stats(title: &str, stats: &[TimingStats]) {
+    if stats.is_empty() {
+        return;
+    }
+
+    let width = stats
+        .iter()
+        .map(|entry| entry.name.len())
+        .max()
+        .unwrap_or(0)
+        .max(title.len());
+    notice!();
+    notice!(
+        "   {:<width$}  {:>7}  {:>10}  {:>10}  {:>10}",
+        title,
+        "count",
+        "total",
+        "mean",
+        "max"
+    );
+    for entry in stats {
+        notice!(
+            "   {:<width$}  {:>7}  {:>10}  {:>10}  {:>10}",
+            entry.name,
+            entry.count,
+            millis(entry.total),
+            millis(entry.mean()),
+            millis(entry.max),
+        );
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Chrome trace event format: one complete (`"X"`) event per profile event,
+/// plugins as categories, and the summary under `otherData`.
+fn chrome_trace(profile: &Profile, summary: &ProfileSummary) -> serde_json::Value {
+    let mut events = Vec::with_capacity(profile.events.len());
+    let mut threads = BTreeSet::new();
+
+    for event in &profile.events {
+        threads.insert(event.thread);
+        let category = match event.kind {
+            EventKind::Run => "run",
+            EventKind::File => "file",
+            EventKind::Query => "query",
+            EventKind::LazyInit => "lazy",
+            EventKind::Span => "runner",
+        };
+        let name = match (event.kind, &event.plugin_id) {
+            (EventKind::Run | EventKind::Span, _) | (_, None) => event.name.clone(),
+            (_, Some(plugin_id)) => format!("{plugin_id}: {}", event.name),
+        };
+        events.push(json!({
+            "name": name,
+            "cat": category,
+            "ph": "X",
+            "ts": event.start.as_micros() as u64,
+            "dur": event.duration.as_micros() as u64,
+            "pid": 1,
+            "tid": event.thread,
+            "args": {
+                "plugin": event.plugin_id,
+                "pass": event.pass,
+                "replacements": event.replacements,
+            },
+        }));
+    }
+
+    for thread in threads {
+        let name = if thread == 0 {
+            "cargo-forgen".to_owned()
+        } else {
+            format!("worker {thread}")
+        };
+        events.push(json!({
+            "name": "thread_name",
+            "ph": "M",
+            "pid": 1,
+            "tid": thread,
+            "args": { "name": name },
+        }));
+    }
+
+    json!({
+        "traceEvents": events,
+        "displayTimeUnit": "ms",
+        "otherData": { "summary": summary },
+    })
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
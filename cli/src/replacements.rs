@@ -1,23 +1,10 @@
 use anyhow::{anyhow, bail, Context, Result};
+pub use forgen_api::saved::SavedReplacement;
 use forgen_api::Replacement;
-use serde::Serialize;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-/// Replacement format written to `target/.forgen/<mirrored-path>.json`.
-#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
-pub struct SavedReplacement {
-    /// The nth occurrence of `old_text` in the original source file.
-    ///
-    /// This is zero-based and counts *overlapping* matches.
-    pub index: usize,
-    /// The original text to replace.
-    pub old_text: String,
-    /// The replacement text.
-    pub new_text: String,
-}
-
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ChangedFile {
     pub path: String,
@@ -105,6 +92,54 @@ pub fn write_final_file_replacements(
     Ok(total_saved)
 }
 
+/// Read every replacement file in `target/.forgen/`, keyed by the
+/// workspace-relative path of the source file it belongs to.
+pub fn load_saved_replacements(
+    workspace_root: &Path,
+) -> Result<BTreeMap<String, Vec<SavedReplacement>>> {
+    let out_root = workspace_root.join("target").join(".forgen");
+    let mut saved = BTreeMap::new();
+    if out_root.exists() {
+        load_saved_dir(&out_root, "", &mut saved)?;
+    }
+    Ok(saved)
+}
+
+fn load_saved_dir(
+    dir: &Path,
+    rel_dir: &str,
+    saved: &mut BTreeMap<String, Vec<SavedReplacement>>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let rel_path = if rel_dir.is_empty() {
+            name.clone()
+        } else {
+            format!("{rel_dir}/{name}")
+        };
+        let path = entry.path();
+
+        if entry.file_type()?.is_dir() {
+            load_saved_dir(&path, &rel_path, saved)?;
+            continue;
+        }
+        if name.ends_with(".diagnostics.json") {
+            continue;
+        }
+        let Some(source_path) = rel_path.strip_suffix(".json") else {
+            continue;
+        };
+
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let replacements = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        saved.insert(source_path.to_owned(), replacements);
+    }
+    Ok(())
+}
+
 pub fn collect_changed_files(file_models: &HashMap<String, FileModel>) -> Result<Vec<ChangedFile>> {
     let mut out = Vec::new();
 
@@ -176,7 +211,7 @@ impl FileModel {
         Self { original, segments }
     }
 
-    fn rendered(&self) -> String {
+    pub fn rendered(&self) -> String {
         let mut out = String::new();
         for segment in &self.segments {
             match segment {
@@ -187,7 +222,40 @@ impl FileModel {
         out
     }
 
-    fn is_changed(&self) -> bool {
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+
+    /// Map a byte offset in the rendered text back to the original source.
+    ///
+    /// Offsets inside inserted text map to the point where that text was
+    /// inserted, and the returned flag is `true`.
+    pub fn original_offset(&self, offset: usize) -> (usize, bool) {
+        let mut cursor = 0usize;
+        let mut last_original_end = 0usize;
+
+        for segment in &self.segments {
+            let next = cursor + segment.len();
+            match segment {
+                Segment::Original { start, end } => {
+                    if offset <= next {
+                        return (start + offset.saturating_sub(cursor), false);
+                    }
+                    last_original_end = *end;
+                }
+                Segment::Inserted(_) => {
+                    if offset < next {
+                        return (last_original_end, true);
+                    }
+                }
+            }
+            cursor = next;
+        }
+
+        (last_original_end, false)
+    }
+
+    pub fn is_changed(&self) -> bool {
         match self.segments.as_slice() {
             [] => !self.original.is_empty(),
             [Segment::Original { start, end }] => *start != 0 || *end != self.original.len(),
@@ -495,16 +563,21 @@ fn make_saved_replacement(
 }
 
 fn mirrored_json_path(out_root: &Path, rel_path: &str) -> Result<PathBuf> {
+    mirrored_output_path(out_root, rel_path, ".json")
+}
+
+/// `<out_root>/<rel_path><suffix>`, e.g. `target/.forgen/src/lib.rs.json`.
+pub fn mirrored_output_path(out_root: &Path, rel_path: &str, suffix: &str) -> Result<PathBuf> {
     let rel = Path::new(rel_path);
     let file_name = rel
         .file_name()
         .ok_or_else(|| anyhow!("Invalid relative file path `{rel_path}`"))?;
 
-    let mut json_name = file_name.to_os_string();
-    json_name.push(".json");
+    let mut output_name = file_name.to_os_string();
+    output_name.push(suffix);
 
     let dir = rel.parent().unwrap_or_else(|| Path::new(""));
-    Ok(out_root.join(dir).join(json_name))
+    Ok(out_root.join(dir).join(output_name))
 }
 
 fn apply_raw_ops(source: &str, ops: &[RawOp]) -> Result<String> {
@@ -764,6 +837,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn maps_rendered_offsets_back_to_original_source() {
+        let source = "fn main() {\n    let x = 1;\n}\n";
+        let mut model = FileModel::new(source.to_owned());
+        let insert_at = source.find('}').unwrap();
+        apply_replacements_to_file_model(
+            &mut model,
+            source,
+            &[Replacement::insert(
+                insert_at as u32,
+                "    dbg!(x);\n".to_owned(),
+            )],
+        )
+        .unwrap();
+        let rendered = model.rendered();
+
+        let x = rendered.find("x = 1").unwrap();
+        assert_eq!(
+            model.original_offset(x),
+            (source.find("x = 1").unwrap(), false)
+        );
+
+        let dbg = rendered.find("dbg!").unwrap();
+        assert_eq!(model.original_offset(dbg), (insert_at, true));
+
+        let close = rendered.rfind('}').unwrap();
+        assert_eq!(model.original_offset(close), (insert_at, false));
+        assert_eq!(model.original_offset(rendered.len()), (source.len(), false));
+    }
+
     #[test]
     fn clears_saved_replacement_directory() {
         let temp = tempfile::tempdir().unwrap();
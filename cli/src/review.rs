@@ -0,0 +1,148 @@
+use crate::output::{self, info, notice, Event, Verbosity};
+use crate::replacements::FileModel;
+use anyhow::{Context, Result};
+use forgen_api::parse_generated_regions;
+use similar::TextDiff;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// How to show the converged sources of a run before (or instead of) saving
+/// them.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Print a unified diff of every changed file.
+    pub diff: bool,
+    /// Remove `/*#start:…*/` and `/*#end:…*/` markers from the diff and patch.
+    pub strip_markers: bool,
+    /// Write the diff as a patch that `git apply` accepts from the workspace
+    /// root.
+    pub patch: Option<PathBuf>,
+}
+
+impl Options {
+    pub fn enabled(&self) -> bool {
+        self.diff || self.patch.is_some()
+    }
+}
+
+/// Print and write the changes recorded in `file_models` as requested.
+pub fn report(file_models: &HashMap<String, FileModel>, options: &Options) -> Result<()> {
+    if !options.enabled() {
+        return Ok(());
+    }
+
+    let patch = render_patch(file_models, options.strip_markers);
+    if options.diff {
+        output::emit(&Event::Diff { patch: &patch });
+        if patch.is_empty() {
+            notice!("\n📄 No source changes");
+        } else if output::shows(Verbosity::Quiet) {
+            println!("\n📄 Source changes:\n");
+            print!("{patch}");
+        }
+    }
+    if let Some(path) = &options.patch {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(path, &patch).with_context(|| format!("Failed to write {}", path.display()))?;
+        info!("📝 Wrote patch to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Git-style diff of every changed file, ordered by path.
+pub fn render_patch(file_models: &HashMap<String, FileModel>, strip_markers: bool) -> String {
+    let mut paths: Vec<_> = file_models
+        .iter()
+        .filter(|(_, model)| model.is_changed())
+        .map(|(path, _)| path)
+        .collect();
+    paths.sort();
+
+    let mut patch = String::new();
+    for path in paths {
+        let model = &file_models[path];
+        let (original, converged) = if strip_markers {
+            (
+                strip_generated_markers(model.original()),
+                strip_generated_markers(&model.rendered()),
+            )
+        } else {
+            (model.original().to_owned(), model.rendered())
+        };
+        patch.push_str(&file_diff(path, &original, &converged));
+    }
+    patch
+}
+
+fn file_diff(path: &str, original: &str, converged: &str) -> String {
+    if original == converged {
+        return String::new();
+    }
+    let diff = TextDiff::from_lines(original, converged)
+        .unified_diff()
+        .header(&format!("a/{path}"), &format!("b/{path}"))
+        .to_string();
+    format!("diff --git a/{path} b/{path}\n{diff}")
+}
+
+/// `source` without the start and end markers of its generated regions; the
+/// generated text itself is kept.
+pub fn strip_generated_markers(source: &str) -> String {
+    strip_markers_where(source, |_| true)
+}
+
+/// Like [`strip_generated_markers`], but only for regions of plugins that
+/// `strip` returns `true` for.
+pub fn strip_markers_where(source: &str, strip: impl Fn(&str) -> bool) -> String {
+    let mut markers: Vec<_> = parse_generated_regions(source)
+        .into_iter()
+        .filter(|region| strip(&region.plugin_id))
+        .flat_map(|region| [region.start_marker_range, region.end_marker_range])
+        .map(|range| (range.start as usize, range.end as usize))
+        .collect();
+    markers.sort_unstable();
+
+    let mut out = String::with_capacity(source.len());
+    let mut cursor = 0;
+    for (start, end) in markers {
+        out.push_str(&source[cursor..start]);
+        cursor = end;
+    }
+    out.push_str(&source[cursor..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replacements;
+    use forgen_api::Replacement;
+
+    #[test]
+    fn patch_shows_converged_source_with_optional_markers() {
+        let source = "fn main() {\n}\n";
+        let sources = HashMap::from([("src/main.rs".to_owned(), source.to_owned())]);
+        let mut models = replacements::build_file_models(&sources);
+        let generated = "/*#start:demo:1*/    run();\n/*#end:demo:1*/";
+        replacements::apply_replacements_to_file_model(
+            models.get_mut("src/main.rs").unwrap(),
+            source,
+            &[Replacement::insert(12, generated.to_owned())],
+        )
+        .unwrap();
+
+        let patch = render_patch(&models, false);
+        assert!(patch.starts_with(
+            "diff --git a/src/main.rs b/src/main.rs\n--- a/src/main.rs\n+++ b/src/main.rs\n"
+        ));
+        assert!(patch.contains("+/*#start:demo:1*/    run();\n"), "{patch}");
+
+        let stripped = render_patch(&models, true);
+        assert!(stripped.contains("\n+    run();\n"), "{stripped}");
+        assert!(!stripped.contains("#start"), "{stripped}");
+    }
+}
@@ -0,0 +1,499 @@
+use crate::loader;
+use crate::setup::relative_path;
+use anyhow::{bail, Context, Result};
+use cargo_metadata::Metadata;
+use forgen_api::{is_valid_plugin_id, FORGEN_API_VERSION};
+use std::fs;
+use std::path::Path;
+use toml_edit::{table, value, Array, DocumentMut};
+
+pub const DEFAULT_SUITE_NAME: &str = "plugins";
+pub const DEFAULT_PLUGIN_ID: &str = "example";
+
+/// Plugin ids become module names, so they must not be keywords.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl",
+    "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+const SUITE_MANIFEST: &str = r#"[package]
+name = "{name}"
+version = "0.1.0"
+edition = "2021"
+publish = false
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+forgen-api = {api_dependency}
+"#;
+
+const SUITE_LIB: &str = r#"//! Plugin suite of this workspace, built and loaded by `cargo forgen`.
+//!
+//! Each plugin lives in a module under `plugins/` and is registered in `run`.
+//! `cargo forgen new-plugin <id>` adds both.
+
+mod plugins;
+
+use forgen_api::{
+    plugin_suite, FileReplacement, Registration, Schedule, SuiteRuntime, WorkspaceContext,
+};
+
+fn run(ctx: &WorkspaceContext, runtime: &mut SuiteRuntime) -> Vec<FileReplacement> {
+    let schedule = Schedule::new();
+
+    runtime.run_schedule(&schedule, ctx)
+}
+
+plugin_suite!(run);
+"#;
+
+const PLUGIN_MODULE: &str = r#"use forgen_api::{FileContext, Plugin, PluginRuntime, Replacement, WorkspaceContext};
+
+/// Generates nothing yet.
+pub struct {type_name};
+
+impl Plugin for {type_name} {
+    fn name(&self) -> &str {
+        "{plugin_id}"
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("Generates nothing yet.")
+    }
+
+    // Called for every file, on worker threads. Return the replacements for
+    // `file`; override `run` instead to look at several files at once.
+    fn run_file(
+        &self,
+        _file: &FileContext,
+        _ctx: &WorkspaceContext,
+        _runtime: &mut PluginRuntime<'_>,
+    ) -> Vec<Replacement> {
+        Vec::new()
+    }
+}
+"#;
+
+#[derive(Debug, Clone)]
+pub struct InitOptions {
+    /// Package name and directory of the suite crate.
+    pub name: String,
+    /// Id of the starter plugin.
+    pub plugin_id: String,
+}
+
+/// Create the suite crate with a starter plugin, add it to the workspace
+/// members and name it in `[workspace.metadata.forgen]`.
+pub fn init(meta: &Metadata, options: &InitOptions) -> Result<()> {
+    let name = &options.name;
+    if name.is_empty()
+        || !name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+    {
+        bail!("invalid suite name `{name}`: use only ASCII letters, digits, `_` and `-`");
+    }
+    let plugin = PluginModule::new(&options.plugin_id)?;
+    if let Some(existing) = loader::suite_name(meta) {
+        bail!(
+            "this workspace already has the plugin suite '{existing}'; \
+             add plugins to it with `cargo forgen new-plugin <id>`"
+        );
+    }
+
+    let root = meta.workspace_root.as_std_path();
+    let suite_dir = root.join(name);
+    if suite_dir.exists() {
+        bail!("{} already exists", suite_dir.display());
+    }
+
+    let manifest_path = root.join("Cargo.toml");
+    let manifest = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+    let manifest = register_suite(&manifest, name)
+        .with_context(|| format!("Failed to update {}", manifest_path.display()))?;
+
+    write_suite(&suite_dir, name, &api_dependency(meta, &suite_dir), &plugin)?;
+    fs::write(&manifest_path, manifest)
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+
+    println!(
+        "🧩 Created plugin suite '{name}' in {}",
+        suite_dir.display()
+    );
+    println!("   with the starter plugin '{}'", plugin.id);
+    println!("   and registered it in {}", manifest_path.display());
+    println!(
+        "\nNext: depend on the `forgen` crate from the crates to rewrite, add\n\n    \
+         #![feature(custom_inner_attributes, prelude_import)]\n    \
+         #![forgen::file(\"<workspace-relative path>\")]\n\n\
+         to their crate root files (this needs nightly), and run `cargo forgen`."
+    );
+    Ok(())
+}
+
+/// Write the suite crate `name` with `plugin` registered into `suite_dir`.
+fn write_suite(
+    suite_dir: &Path,
+    name: &str,
+    api_dependency: &str,
+    plugin: &PluginModule,
+) -> Result<()> {
+    let suite_manifest = SUITE_MANIFEST
+        .replace("{name}", name)
+        .replace("{api_dependency}", api_dependency);
+    let lib = register_plugin(SUITE_LIB, plugin)?;
+    let plugins_dir = suite_dir.join("src").join("plugins");
+    write_new(&suite_dir.join("Cargo.toml"), &suite_manifest)?;
+    write_new(&suite_dir.join("src").join("lib.rs"), &lib)?;
+    write_new(&plugins_dir.join("mod.rs"), &add_module("", &plugin.module))?;
+    write_new(
+        &plugins_dir.join(format!("{}.rs", plugin.module)),
+        &plugin.source(),
+    )
+}
+
+/// Add a plugin module to the workspace's suite and register it in the
+/// schedule of its `run` function.
+pub fn new_plugin(meta: &Metadata, plugin_id: &str) -> Result<()> {
+    let plugin = PluginModule::new(plugin_id)?;
+    let Some(suite) = loader::suite_name(meta) else {
+        bail!("this workspace has no plugin suite yet; create one with `cargo forgen init`");
+    };
+    let package = meta
+        .workspace_packages()
+        .into_iter()
+        .find(|package| package.name == suite)
+        .with_context(|| format!("the plugin suite '{suite}' is not a workspace member"))?;
+    let lib_path = package
+        .targets
+        .iter()
+        .find(|target| {
+            target
+                .kind
+                .iter()
+                .any(|kind| kind == "cdylib" || kind == "lib")
+        })
+        .map(|target| target.src_path.clone().into_std_path_buf())
+        .with_context(|| format!("the plugin suite '{suite}' has no library target"))?;
+
+    let lib = fs::read_to_string(&lib_path)
+        .with_context(|| format!("Failed to read {}", lib_path.display()))?;
+    let src_dir = lib_path.parent().unwrap_or(Path::new("."));
+    let plugins_dir = src_dir.join("plugins");
+    let module_path = plugins_dir.join(format!("{}.rs", plugin.module));
+    if module_path.exists() {
+        bail!("{} already exists", module_path.display());
+    }
+
+    // `plugins/mod.rs` or `plugins.rs`; declared in the crate root when new.
+    let mod_path = [plugins_dir.join("mod.rs"), src_dir.join("plugins.rs")]
+        .into_iter()
+        .find(|path| path.exists());
+    let (mod_path, mod_source, lib) = match mod_path {
+        Some(path) => {
+            let source = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            (path, source, lib)
+        }
+        None => (
+            plugins_dir.join("mod.rs"),
+            String::new(),
+            declare_plugins_module(&lib),
+        ),
+    };
+    let lib = register_plugin(&lib, &plugin)
+        .with_context(|| format!("Failed to register '{plugin_id}' in {}", lib_path.display()))?;
+
+    write_new(&module_path, &plugin.source())?;
+    fs::write(&mod_path, add_module(&mod_source, &plugin.module))
+        .with_context(|| format!("Failed to write {}", mod_path.display()))?;
+    fs::write(&lib_path, lib).with_context(|| format!("Failed to write {}", lib_path.display()))?;
+
+    println!("🧩 Added plugin '{plugin_id}' in {}", module_path.display());
+    println!("   and registered it in {}", lib_path.display());
+    Ok(())
+}
+
+/// The module and type a plugin id is scaffolded as.
+#[derive(Debug, PartialEq, Eq)]
+struct PluginModule {
+    id: String,
+    module: String,
+    type_name: String,
+}
+
+impl PluginModule {
+    fn new(plugin_id: &str) -> Result<Self> {
+        if !is_valid_plugin_id(plugin_id) {
+            bail!("invalid plugin id `{plugin_id}`: use only ASCII letters, digits, `_` and `-`");
+        }
+        if !plugin_id.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            bail!("plugin id `{plugin_id}` must start with a letter, since it names a module");
+        }
+        let module = plugin_id.replace('-', "_").to_ascii_lowercase();
+        if KEYWORDS.contains(&module.as_str()) {
+            bail!("plugin id `{plugin_id}` is a Rust keyword and cannot name a module");
+        }
+
+        let mut type_name: String = module
+            .split('_')
+            .filter(|part| !part.is_empty())
+            .map(|part| part[..1].to_ascii_uppercase() + &part[1..])
+            .collect();
+        type_name.push_str("Plugin");
+
+        Ok(Self {
+            id: plugin_id.to_owned(),
+            module,
+            type_name,
+        })
+    }
+
+    fn source(&self) -> String {
+        PLUGIN_MODULE
+            .replace("{type_name}", &self.type_name)
+            .replace("{plugin_id}", &self.id)
+    }
+}
+
+/// `manifest` with `name` among the workspace members and set as the suite.
+fn register_suite(manifest: &str, name: &str) -> Result<String> {
+    let mut doc: DocumentMut = manifest.parse()?;
+    let workspace = doc
+        .entry("workspace")
+        .or_insert(table())
+        .as_table_mut()
+        .context("`workspace` is not a table")?;
+
+    let members = workspace
+        .entry("members")
+        .or_insert(value(Array::new()))
+        .as_array_mut()
+        .context("`workspace.members` is not an array")?;
+    if !members.iter().any(|member| member.as_str() == Some(name)) {
+        members.push(name);
+    }
+
+    let metadata = workspace
+        .entry("metadata")
+        .or_insert(table())
+        .as_table_mut()
+        .context("`workspace.metadata` is not a table")?;
+    metadata.set_implicit(true);
+    let forgen = metadata
+        .entry("forgen")
+        .or_insert(table())
+        .as_table_mut()
+        .context("`workspace.metadata.forgen` is not a table")?;
+    forgen["suite"] = value(name);
+
+    Ok(doc.to_string())
+}
+
+/// How the suite depends on `forgen-api`: by path when the workspace uses a
+/// local checkout, otherwise pinned to the release this CLI was built with so
+/// the ABI versions match.
+fn api_dependency(meta: &Metadata, suite_dir: &Path) -> String {
+    let local = meta
+        .packages
+        .iter()
+        .find(|package| package.name == "forgen-api" && package.source.is_none());
+    match local.and_then(|package| package.manifest_path.parent()) {
+        Some(api_dir) => {
+            let path = relative_path(suite_dir, api_dir.as_std_path());
+            format!(
+                "{{ path = \"{}\" }}",
+                path.to_string_lossy().replace('\\', "/")
+            )
+        }
+        None => format!("\"={FORGEN_API_VERSION}\""),
+    }
+}
+
+/// Add a registration for `plugin` to the `Schedule::new()` chain in `lib`.
+fn register_plugin(lib: &str, plugin: &PluginModule) -> Result<String> {
+    let Some(start) = lib.find("Schedule::new()") else {
+        bail!(
+            "no `Schedule::new()` found; register `plugins::{}::{}` in `run` by hand",
+            plugin.module,
+            plugin.type_name
+        );
+    };
+    let Some(end) = lib[start..].find(';').map(|offset| start + offset) else {
+        bail!("the `Schedule::new()` statement has no end");
+    };
+
+    let line_start = lib[..start].rfind('\n').map_or(0, |i| i + 1);
+    let indent: String = lib[line_start..]
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect();
+    let registration = format!(
+        "\n{indent}    .register(Registration::new(&plugins::{}::{}))",
+        plugin.module, plugin.type_name
+    );
+
+    let mut out = String::with_capacity(lib.len() + registration.len());
+    out.push_str(&lib[..end]);
+    out.push_str(&registration);
+    out.push_str(&lib[end..]);
+    Ok(out)
+}
+
+fn add_module(mod_source: &str, module: &str) -> String {
+    let mut out = mod_source.to_owned();
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&format!("pub mod {module};\n"));
+    out
+}
+
+/// `lib` with `mod plugins;` after its inner doc comments and attributes.
+fn declare_plugins_module(lib: &str) -> String {
+    let mut header_len = 0;
+    for line in lib.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if !(trimmed.is_empty() || trimmed.starts_with("//!") || trimmed.starts_with("#![")) {
+            break;
+        }
+        header_len += line.len();
+    }
+    format!(
+        "{}mod plugins;\n\n{}",
+        &lib[..header_len],
+        &lib[header_len..]
+    )
+}
+
+fn write_new(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_module_and_type_from_plugin_id() {
+        let plugin = PluginModule::new("f64-logger").unwrap();
+        assert_eq!(plugin.module, "f64_logger");
+        assert_eq!(plugin.type_name, "F64LoggerPlugin");
+
+        assert!(PluginModule::new("no spaces").is_err());
+        assert!(PluginModule::new("1st").is_err());
+        assert!(PluginModule::new("type").is_err());
+    }
+
+    #[test]
+    fn registers_plugins_in_the_schedule() {
+        let lib = register_plugin(SUITE_LIB, &PluginModule::new("first").unwrap()).unwrap();
+        let lib = register_plugin(&lib, &PluginModule::new("second-one").unwrap()).unwrap();
+        assert!(lib.contains(
+            "    let schedule = Schedule::new()\n        \
+             .register(Registration::new(&plugins::first::FirstPlugin))\n        \
+             .register(Registration::new(&plugins::second_one::SecondOnePlugin));\n"
+        ));
+
+        assert_eq!(
+            declare_plugins_module("//! Suite.\n\nuse forgen_api::*;\n"),
+            "//! Suite.\n\nmod plugins;\n\nuse forgen_api::*;\n"
+        );
+        assert_eq!(
+            add_module("pub mod first;", "second"),
+            "pub mod first;\npub mod second;\n"
+        );
+    }
+
+    /// `init` and `new-plugin` output must build against this `forgen-api`,
+    /// which is what the pinned version resolves to for a released CLI.
+    #[test]
+    fn scaffolded_suite_compiles_against_the_current_api() {
+        let dir = tempfile::tempdir().unwrap();
+        let suite_dir = dir.path().join("plugins");
+        let cli_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let api_dir = cli_dir.join("../api").canonicalize().unwrap();
+        let dependency = format!(
+            "{{ path = \"{}\" }}",
+            api_dir.to_string_lossy().replace('\\', "/")
+        );
+        write_suite(
+            &suite_dir,
+            "plugins",
+            &dependency,
+            &PluginModule::new(DEFAULT_PLUGIN_ID).unwrap(),
+        )
+        .unwrap();
+
+        // What `new-plugin` adds to an existing suite.
+        let second = PluginModule::new("second-one").unwrap();
+        let src = suite_dir.join("src");
+        let lib = fs::read_to_string(src.join("lib.rs")).unwrap();
+        fs::write(src.join("lib.rs"), register_plugin(&lib, &second).unwrap()).unwrap();
+        let mod_path = src.join("plugins").join("mod.rs");
+        let mod_source = fs::read_to_string(&mod_path).unwrap();
+        fs::write(&mod_path, add_module(&mod_source, &second.module)).unwrap();
+        write_new(
+            &src.join("plugins").join(format!("{}.rs", second.module)),
+            &second.source(),
+        )
+        .unwrap();
+
+        // A workspace of its own, so the temporary directory's parents do not
+        // matter.
+        let manifest_path = suite_dir.join("Cargo.toml");
+        let manifest = fs::read_to_string(&manifest_path).unwrap();
+        fs::write(&manifest_path, manifest + "\n[workspace]\n").unwrap();
+
+        let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+        let output = std::process::Command::new(cargo)
+            .args(["check", "--quiet", "--offline"])
+            .current_dir(&suite_dir)
+            .env("CARGO_TARGET_DIR", cli_dir.join("../target/scaffold-check"))
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn registers_the_suite_in_the_workspace_manifest() {
+        let manifest = "[workspace]\n# The crates.\nmembers = [\"app\"]\n\n\
+                        [workspace.metadata.forgen]\nseed = 7\n";
+        assert_eq!(
+            register_suite(manifest, "plugins").unwrap(),
+            "[workspace]\n# The crates.\nmembers = [\"app\", \"plugins\"]\n\n\
+             [workspace.metadata.forgen]\nseed = 7\nsuite = \"plugins\"\n"
+        );
+
+        let manifest = "[package]\nname = \"app\"\n";
+        let registered = register_suite(manifest, "plugins").unwrap();
+        assert!(
+            registered.contains("[workspace]\nmembers = [\"plugins\"]\n"),
+            "{registered}"
+        );
+        assert!(registered.contains("[workspace.metadata.forgen]\nsuite = \"plugins\"\n"));
+        assert!(
+            !registered.contains("[workspace.metadata]\n"),
+            "{registered}"
+        );
+
+        assert_eq!(
+            relative_path(Path::new("/ws/plugins"), Path::new("/ws/crates/api")),
+            Path::new("../crates/api")
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use anyhow::{bail, Context, Result};
+use forgen_api::SeedLock;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the on-disk seed lock layout.
+const SEED_LOCK_FORMAT: u32 = 1;
+
+/// Name of the file in `target/.forgen/` that records the suite seed of the
+/// last run.
+const SEED_RECORD_FILE: &str = "seed";
+
+/// Seed lock file, written to the path configured with `--seed-lock` or
+/// `[workspace.metadata.forgen] seed-lock = "..."`.
+///
+/// ```json
+/// {
+///   "format": 1,
+///   "seed": 1234,
+///   "files": {
+///     "src/lib.rs": { "fingerprint": "…", "seeds": { "seeded-binding": 987 } }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeedLockFile {
+    pub format: u32,
+    /// Suite seed used when neither `--seed` nor `seed` metadata is set, so
+    /// files that are not pinned yet still get reproducible values.
+    pub seed: u64,
+    #[serde(flatten)]
+    pub lock: SeedLock,
+}
+
+/// Resolve where the seed lock lives: `--seed-lock`, else
+/// `[workspace.metadata.forgen] seed-lock`. `None` disables lock mode.
+pub fn resolve_lock_path(
+    explicit: Option<&Path>,
+    meta: &cargo_metadata::Metadata,
+) -> Option<PathBuf> {
+    crate::config::configured_path(explicit, meta, "seed-lock")
+}
+
+/// Pick the suite seed: `--seed`, then `[workspace.metadata.forgen] seed`,
+/// then the seed stored in the lock file, then `fallback`, then a random one.
+pub fn resolve_suite_seed(
+    explicit: Option<u64>,
+    meta: &cargo_metadata::Metadata,
+    lock: Option<&SeedLockFile>,
+    fallback: Option<u64>,
+) -> Result<u64> {
+    if let Some(seed) = explicit {
+        return Ok(seed);
+    }
+    if let Some(seed) = crate::config::metadata_u64(meta, "seed")? {
+        return Ok(seed);
+    }
+    Ok(lock
+        .map(|lock| lock.seed)
+        .or(fallback)
+        .unwrap_or_else(forgen_api::rand::random))
+}
+
+/// Load the lock at `path`. A missing file is an empty lock.
+pub fn load(path: &Path) -> Result<Option<SeedLockFile>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read seed lock {}", path.display()))?;
+    let file: SeedLockFile = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse seed lock {}", path.display()))?;
+
+    if file.format != SEED_LOCK_FORMAT {
+        bail!(
+            "Seed lock {} has format {}, but this cargo-forgen reads format {}.",
+            path.display(),
+            file.format,
+            SEED_LOCK_FORMAT
+        );
+    }
+
+    Ok(Some(file))
+}
+
+pub fn save(path: &Path, seed: u64, lock: &SeedLock) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let file = SeedLockFile {
+        format: SEED_LOCK_FORMAT,
+        seed,
+        lock: lock.clone(),
+    };
+    let mut json = serde_json::to_string_pretty(&file)?;
+    json.push('\n');
+    fs::write(path, json).with_context(|| format!("Failed to write seed lock {}", path.display()))
+}
+
+/// Record the suite seed of this run in `target/.forgen/seed`, next to the
+/// saved replacements it produced.
+pub fn record_suite_seed(workspace_root: &Path, seed: u64) -> Result<()> {
+    let path = workspace_root
+        .join("target")
+        .join(".forgen")
+        .join(SEED_RECORD_FILE);
+    fs::write(&path, format!("{seed}\n"))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// The suite seed recorded by [`record_suite_seed`], if there is one.
+pub fn recorded_suite_seed(workspace_root: &Path) -> Result<Option<u64>> {
+    let path = workspace_root
+        .join("target")
+        .join(".forgen")
+        .join(SEED_RECORD_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let seed = text
+        .trim()
+        .parse()
+        .with_context(|| format!("{} does not contain a seed", path.display()))?;
+    Ok(Some(seed))
+}
+
+// ---------------------------------------------------------------------------
+// `cargo forgen seeds ...`
+// ---------------------------------------------------------------------------
+
+pub fn show(path: &Path) -> Result<()> {
+    let Some(file) = load(path)? else {
+        println!("No seed lock at {}", path.display());
+        return Ok(());
+    };
+
+    println!("🎲 Suite seed: {}", file.seed);
+    for (rel_path, locked) in file.lock.files() {
+        println!("  {rel_path}");
+        for (plugin_id, seed) in &locked.seeds {
+            println!("    {plugin_id}: {seed}");
+        }
+    }
+
+    Ok(())
+}
+
+pub fn regenerate(path: &Path, plugin_id: Option<&str>, file_path: Option<&str>) -> Result<()> {
+    let Some(mut file) = load(path)? else {
+        println!("No seed lock at {}; nothing to regenerate", path.display());
+        return Ok(());
+    };
+
+    let count = file.lock.regenerate(plugin_id, file_path);
+    if plugin_id.is_none() && file_path.is_none() {
+        file.seed = forgen_api::rand::random();
+    }
+    save(path, file.seed, &file.lock)?;
+
+    println!(
+        "🎲 Regenerated {count} pinned seed(s) in {}",
+        path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+
+    fn lock_file(seed: u64) -> SeedLockFile {
+        SeedLockFile {
+            format: SEED_LOCK_FORMAT,
+            seed,
+            lock: SeedLock::new(),
+        }
+    }
+
+    #[test]
+    fn suite_seed_precedence() {
+        let with_seed = test_support::metadata(json!({ "forgen": { "seed": 2 } }));
+        let without_seed = test_support::metadata(json!({}));
+        let lock = lock_file(3);
+        let resolve = |explicit, meta, lock, fallback| {
+            resolve_suite_seed(explicit, meta, lock, fallback).unwrap()
+        };
+
+        assert_eq!(resolve(Some(1), &with_seed, Some(&lock), Some(4)), 1);
+        assert_eq!(resolve(None, &with_seed, Some(&lock), Some(4)), 2);
+        assert_eq!(resolve(None, &without_seed, Some(&lock), Some(4)), 3);
+        assert_eq!(resolve(None, &without_seed, None, Some(4)), 4);
+        assert!(resolve_suite_seed(None, &without_seed, None, None).is_ok());
+    }
+
+    #[test]
+    fn rejects_an_invalid_metadata_seed() {
+        let meta = test_support::metadata(json!({ "forgen": { "seed": "soon" } }));
+        assert!(resolve_suite_seed(None, &meta, None, Some(4)).is_err());
+        // `--seed` is taken before the metadata is read.
+        assert_eq!(resolve_suite_seed(Some(1), &meta, None, None).unwrap(), 1);
+    }
+
+    #[test]
+    fn recorded_suite_seed_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        assert_eq!(recorded_suite_seed(root).unwrap(), None);
+
+        fs::create_dir_all(root.join("target/.forgen")).unwrap();
+        record_suite_seed(root, u64::MAX).unwrap();
+        assert_eq!(recorded_suite_seed(root).unwrap(), Some(u64::MAX));
+
+        fs::write(root.join("target/.forgen/seed"), "not a seed\n").unwrap();
+        assert!(recorded_suite_seed(root).is_err());
+    }
+
+    #[test]
+    fn seed_lock_round_trips_and_checks_its_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("locks/seeds.json");
+        assert!(load(&path).unwrap().is_none());
+
+        let mut lock = SeedLock::new();
+        lock.pin("seeded-binding", "src/lib.rs", 987);
+        save(&path, 1234, &lock).unwrap();
+        let file = load(&path).unwrap().unwrap();
+        assert_eq!(file.seed, 1234);
+        assert_eq!(file.lock, lock);
+        assert_eq!(file.lock.get("seeded-binding", "src/lib.rs"), Some(987));
+
+        let text = fs::read_to_string(&path).unwrap();
+        fs::write(&path, text.replace("\"format\": 1", "\"format\": 2")).unwrap();
+        let error = load(&path).unwrap_err().to_string();
+        assert!(error.contains("has format 2"), "{error}");
+    }
+}
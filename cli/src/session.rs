@@ -0,0 +1,351 @@
+//! Keeping a loaded [`Session`] up to date between runs, shared by watch mode
+//! and the daemon.
+
+use crate::output::{self, info, Event};
+use crate::{
+    config, daemon, fixed_point, freshness, loader, run_plugins, workspace, OutputMode, RunOutcome,
+    Session,
+};
+use anyhow::Result;
+use notify_debouncer_mini::notify::RecursiveMode;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// `.rs` files and manifests that changed on disk, from the watcher or a
+/// daemon `run` request.
+pub struct SourceChanges {
+    files: Vec<PathBuf>,
+    manifests: Vec<PathBuf>,
+}
+
+impl SourceChanges {
+    pub fn from_paths(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let mut changes = SourceChanges {
+            files: Vec::new(),
+            manifests: Vec::new(),
+        };
+        for path in paths {
+            // Creates, deletes, renames and plain modifications all arrive as
+            // a single path.
+            if path.extension().and_then(|s| s.to_str()) == Some("rs") {
+                changes.files.push(path);
+            } else if path.file_name().is_some_and(|name| name == "Cargo.toml") {
+                changes.manifests.push(path);
+            }
+        }
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.manifests.is_empty()
+    }
+
+    /// Summarise what happened so the user knows why a re-run was triggered
+    /// (created / deleted / modified).
+    fn report(&self) {
+        for path in &self.files {
+            // A file that exists now but the VFS doesn't know about yet is
+            // effectively "new".
+            let icon = if path.exists() { "📝" } else { "🗑 " };
+            info!(
+                "  {icon} {}",
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("(unknown)")
+            );
+        }
+        for path in &self.manifests {
+            info!("  📦 {}", path.display());
+        }
+        output::emit(&Event::Rerun {
+            files: self
+                .files
+                .iter()
+                .chain(&self.manifests)
+                .map(|path| path.display().to_string())
+                .collect(),
+        });
+    }
+}
+
+/// Bring the session up to date with `changes` before a rerun: reload the
+/// workspace for changed manifests and update the rust-analyzer database. A
+/// manifest that fails to load is reported and the previous configuration
+/// stays in effect. Returns whether the plugin suite needs a rebuild.
+fn sync_changes(
+    session: &mut Session,
+    suite_dirs: &mut Vec<PathBuf>,
+    changes: &SourceChanges,
+) -> Result<bool> {
+    let mut rebuild_suite = changes
+        .files
+        .iter()
+        .any(|path| suite_dirs.iter().any(|dir| path.starts_with(dir)));
+    if !changes.manifests.is_empty() {
+        info!("📦 Manifest change detected, reloading the workspace...");
+        match reload_manifest(session) {
+            Ok(graph_changed) => {
+                rebuild_suite |= graph_changed;
+                *suite_dirs = loader::suite_source_dirs(&session.workspace_info.cargo_metadata);
+            }
+            Err(e) => output::error(format_args!("Failed to reload the workspace: {e:#}")),
+        }
+    }
+    if rebuild_suite {
+        info!("🔨 Plugin suite sources changed, rebuilding...");
+    }
+
+    info!("🔄 File system change detected, re-running plugins...");
+    // Suite dependencies outside the workspace are not part of the
+    // rust-analyzer database.
+    let members = &session.workspace_info.members;
+    let workspace_files: Vec<_> = changes
+        .files
+        .iter()
+        .filter(|path| members.iter().any(|member| path.starts_with(member)))
+        .cloned()
+        .collect();
+    workspace::apply_file_changes(&mut session.host, &mut session.vfs, &workspace_files)?;
+    Ok(rebuild_suite)
+}
+
+/// `run_plugins`, then persist the suite runtime if the run saved its output.
+pub fn run_and_persist(session: &mut Session, build: bool, mode: OutputMode) -> Result<RunOutcome> {
+    let outcome = run_plugins(session, build, mode)?;
+    if mode == OutputMode::Save {
+        session.persist.save(&session.suite_runtime)?;
+    }
+    Ok(outcome)
+}
+
+/// Directories watch mode observes: source directories recursively, and the
+/// directories of every manifest that can change the workspace or the suite.
+pub fn watch_targets(
+    workspace_info: &workspace::WorkspaceInfo,
+    suite_dirs: &[PathBuf],
+) -> BTreeMap<PathBuf, RecursiveMode> {
+    let meta = &workspace_info.cargo_metadata;
+    let manifest_dirs = std::iter::once(meta.workspace_root.as_std_path())
+        .chain(
+            meta.workspace_packages()
+                .into_iter()
+                .filter_map(|package| package.manifest_path.parent())
+                .map(|dir| dir.as_std_path()),
+        )
+        .chain(suite_dirs.iter().filter_map(|dir| dir.parent()));
+
+    let mut targets: BTreeMap<_, _> = manifest_dirs
+        .map(|dir| (dir.to_path_buf(), RecursiveMode::NonRecursive))
+        .collect();
+    for dir in workspace_info.members.iter().chain(suite_dirs) {
+        if dir.is_dir() {
+            targets.insert(dir.clone(), RecursiveMode::Recursive);
+        }
+    }
+    targets
+}
+
+/// Re-run `cargo metadata` after a manifest changed and apply the result:
+/// reload rust-analyzer when the crate graph changed, and re-read
+/// `[workspace.metadata.forgen]`. Plugin state and the file cache are kept.
+/// Nothing changes when the new manifest is invalid. Returns whether the
+/// plugin suite needs a rebuild.
+fn reload_manifest(session: &mut Session) -> Result<bool> {
+    let metadata_start = Instant::now();
+    let manifest_path: &Path = session.manifest_path.as_ref();
+    let workspace_info = workspace::get_workspace_info(&manifest_path.to_path_buf())?;
+    output::phase("cargo_metadata", metadata_start);
+
+    let old_meta = &session.workspace_info.cargo_metadata;
+    let new_meta = &workspace_info.cargo_metadata;
+    let overrides = &session.overrides;
+    let max_passes = fixed_point::resolve_max_passes(overrides.max_passes, new_meta)?;
+    let filter = config::plugin_filter(&overrides.only, &overrides.skip, new_meta)?;
+
+    let graph_changed = !workspace::same_crate_graph(old_meta, new_meta);
+    if graph_changed {
+        // A full reload rather than a crate graph change on the existing
+        // database: new members and dependencies add source roots whose files
+        // only `load_workspace_at` loads into the `Vfs`, and build script
+        // outputs and proc-macro dylibs are only rebuilt and attached
+        // there. `ra_ap_load_cargo` has no API to extend a loaded workspace,
+        // and redoing its steps here would tie the CLI to its internals. The
+        // file cache and plugin state live in the `SuiteRuntime` and survive.
+        let load_start = Instant::now();
+        let (host, vfs) =
+            workspace::load_workspace(&session.manifest_path, session.load_options.clone())?;
+        output::phase("workspace_load", load_start);
+        session.host = host;
+        session.vfs = vfs;
+    } else {
+        info!("  Crate graph unchanged; keeping the loaded workspace");
+    }
+
+    // These select the persisted seed and state, which are only read at start.
+    let restart_keys: Vec<&str> = ["seed", "state-file", "seed-lock"]
+        .into_iter()
+        .filter(|key| {
+            let old = config::forgen_metadata(old_meta).and_then(|forgen| forgen.get(key));
+            let new = config::forgen_metadata(new_meta).and_then(|forgen| forgen.get(key));
+            old != new
+        })
+        .collect();
+    if !restart_keys.is_empty() {
+        output::warn(format_args!(
+            "Changes to `{}` under [workspace.metadata.forgen] take effect after a restart",
+            restart_keys.join("`, `")
+        ));
+    }
+
+    let rebuild_suite =
+        graph_changed || loader::suite_name(old_meta) != loader::suite_name(new_meta);
+    session.passes.max_passes = max_passes;
+    session.suite_runtime.set_plugin_filter(filter);
+    session.workspace_info = workspace_info;
+    Ok(rebuild_suite)
+}
+
+/// What the daemon and watch mode keep between runs.
+pub struct DaemonState {
+    mode: OutputMode,
+    pub suite_dirs: Vec<PathBuf>,
+    /// The sources as of the last run that picked up changes.
+    sources: freshness::Sources,
+    runs: usize,
+    last_run: Option<DaemonRun>,
+    /// Why the last run failed; the next `run` request retries.
+    last_error: Option<String>,
+    /// A suite rebuild was requested by a run that failed, so the next run
+    /// rebuilds even if the suite sources did not change again.
+    rebuild_pending: bool,
+}
+
+/// The last successful run of the daemon or watch mode.
+struct DaemonRun {
+    outcome: RunOutcome,
+    elapsed_ms: f64,
+}
+
+impl DaemonState {
+    pub fn new(session: &Session, mode: OutputMode) -> Self {
+        let suite_dirs = loader::suite_source_dirs(&session.workspace_info.cargo_metadata);
+        let sources =
+            freshness::Sources::scan(&watch_targets(&session.workspace_info, &suite_dirs));
+        DaemonState {
+            mode,
+            suite_dirs,
+            sources,
+            runs: 0,
+            last_run: None,
+            last_error: None,
+            rebuild_pending: false,
+        }
+    }
+
+    fn changes(&self, session: &Session) -> (freshness::Sources, SourceChanges) {
+        let current =
+            freshness::Sources::scan(&watch_targets(&session.workspace_info, &self.suite_dirs));
+        let changes = SourceChanges::from_paths(current.changed_since(&self.sources));
+        (current, changes)
+    }
+
+    /// Pick up changed sources and re-run, unless nothing changed since the
+    /// last successful run and `force` is not set.
+    pub fn run(&mut self, session: &mut Session, force: bool) -> daemon::Response {
+        let (current, changes) = self.changes(session);
+        if let Some(last_run) = &self.last_run {
+            if !force && changes.is_empty() && self.last_error.is_none() {
+                return last_run.response(false);
+            }
+        }
+
+        let run_start = Instant::now();
+        let mut result = Ok(force || self.rebuild_pending);
+        if !changes.is_empty() {
+            changes.report();
+            result = sync_changes(session, &mut self.suite_dirs, &changes)
+                .map(|rebuild_suite| rebuild_suite || force || self.rebuild_pending);
+        }
+        let result = result.and_then(|build| {
+            self.sources = current;
+            self.runs += 1;
+            self.rebuild_pending = build;
+            run_and_persist(session, build, self.mode)
+        });
+        match result {
+            Ok(outcome) => {
+                info!("✅ Done\n");
+                self.last_error = None;
+                self.rebuild_pending = false;
+                let last_run = self.last_run.insert(DaemonRun {
+                    outcome,
+                    elapsed_ms: output::elapsed_ms(run_start),
+                });
+                last_run.response(true)
+            }
+            Err(e) => {
+                let message = format!("{e:#}");
+                output::error(format_args!("Plugin error: {message}"));
+                self.last_error = Some(message.clone());
+                daemon::Response::Error { message }
+            }
+        }
+    }
+
+    /// Answer one client request. Returns `false` once asked to shut down.
+    #[cfg(unix)]
+    pub fn answer(
+        &mut self,
+        session: &mut Session,
+        request: daemon::Request,
+        reply: daemon::Reply,
+    ) -> bool {
+        let response = match request {
+            daemon::Request::Run { force } => self.run(session, force),
+            daemon::Request::Status => self.status(session),
+            daemon::Request::Diagnostics => daemon::Response::Diagnostics {
+                diagnostics: self
+                    .last_run
+                    .as_ref()
+                    .map(|run| run.outcome.diagnostics.clone())
+                    .unwrap_or_default(),
+            },
+            daemon::Request::Shutdown => {
+                reply.send(&daemon::Response::ShuttingDown);
+                return false;
+            }
+        };
+        reply.send(&response);
+        true
+    }
+
+    #[cfg(unix)]
+    fn status(&self, session: &Session) -> daemon::Response {
+        let (_, changes) = self.changes(session);
+        daemon::Response::Status {
+            pid: std::process::id(),
+            workspace_root: session.workspace_info.root.display().to_string(),
+            runs: self.runs,
+            fresh: self.last_run.is_some() && self.last_error.is_none() && changes.is_empty(),
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+impl DaemonRun {
+    fn response(&self, ran: bool) -> daemon::Response {
+        daemon::Response::Run {
+            ran,
+            changed_files: self
+                .outcome
+                .changed_files
+                .iter()
+                .map(|file| file.path.clone())
+                .collect(),
+            unadopted: self.outcome.unadopted.clone(),
+            errors: self.outcome.errors,
+            elapsed_ms: self.elapsed_ms,
+        }
+    }
+}
@@ -0,0 +1,576 @@
+use crate::output::{self, notice, Event};
+use crate::replacements::ChangedFile;
+use anyhow::{bail, Context, Result};
+use cargo_metadata::{DependencyKind, Metadata, Package};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use toml_edit::{table, value, DocumentMut, InlineTable};
+
+/// Crate features `#![forgen::file(...)]` needs at the crate root.
+pub const FORGEN_FEATURES: &[&str] = &["custom_inner_attributes", "prelude_import"];
+
+/// Also needed when the crate root declares `mod name;` file modules, which
+/// then become part of the macro's input.
+const FILE_MODULES_FEATURE: &str = "proc_macro_hygiene";
+
+const ATTR_PREFIX: &str = "#![forgen::file(\"";
+const ATTR_SUFFIX: &str = "\")]";
+
+/// Which files `cargo forgen setup` adopts.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Workspace-relative files.
+    pub files: Vec<String>,
+    /// Workspace packages whose crate roots are adopted.
+    pub packages: Vec<String>,
+}
+
+/// Insert or repair `#![forgen::file(...)]` in the selected files (every
+/// file that already has one when nothing is selected), enable the features
+/// it needs in their crate roots and add the `forgen` dependency to their
+/// packages.
+pub fn run(meta: &Metadata, options: &Options) -> Result<()> {
+    let root = meta.workspace_root.as_std_path();
+    let files = selected_files(meta, options)?;
+    if files.is_empty() {
+        println!("Nothing to set up: no file has `#![forgen::file(...)]` yet.");
+        println!("Select crates with --package <name> or files by path.");
+        return Ok(());
+    }
+
+    // Reject files the macro cannot rewrite before touching any of them.
+    let crates = files
+        .iter()
+        .map(|rel_path| crate_root_package(meta, &root.join(rel_path), rel_path))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut packages: Vec<&Package> = Vec::new();
+    let mut changed = 0;
+    for (rel_path, package) in files.iter().zip(crates) {
+        let path = root.join(rel_path);
+        let original = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        let mut notes = Vec::new();
+        let (source, change) = set_forgen_attr(&original, rel_path);
+        match change {
+            AttrChange::Added => notes.push("added `#![forgen::file(...)]`".to_owned()),
+            AttrChange::Repaired { from } => notes.push(format!("fixed the path (was `{from}`)")),
+            AttrChange::Unchanged => {}
+        }
+        let (source, added) = enable_features(&source);
+        if !added.is_empty() {
+            notes.push(format!("enabled `{}`", added.join("`, `")));
+        }
+
+        if source != original {
+            fs::write(&path, source)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!("🔧 {rel_path}: {}", notes.join(", "));
+            changed += 1;
+        }
+        if !packages.iter().any(|p| p.id == package.id) {
+            packages.push(package);
+        }
+    }
+
+    for package in packages {
+        let forgen = package
+            .dependencies
+            .iter()
+            .find(|dep| dep.name == "forgen" && dep.kind == DependencyKind::Normal);
+        if let Some(dep) = forgen {
+            // Cargo rejects a second dependency on the same package, and
+            // `forgen::file` only resolves under the crate's own name.
+            if let Some(rename) = dep.rename.as_deref().filter(|name| *name != "forgen") {
+                output::warn(format_args!(
+                    "{}: depends on `forgen` as `{rename}`; rename it to `forgen` so that \
+                     `forgen::file` resolves",
+                    package.name
+                ));
+            }
+            continue;
+        }
+        add_forgen_dependency(meta, package)?;
+        println!("📦 {}: added the `forgen` dependency", package.name);
+        changed += 1;
+    }
+
+    if changed == 0 {
+        println!("✅ {} file(s) already set up", files.len());
+    } else {
+        println!("\n✅ Set up {} file(s)", files.len());
+    }
+    Ok(())
+}
+
+/// Changed files that `forgen::file` will not rewrite because their source
+/// lacks `#![forgen::file("<their path>")]`, ordered by path. Plugin edits to
+/// them are otherwise dropped silently.
+pub fn unadopted_files(
+    changed_files: &[ChangedFile],
+    sources: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut paths: Vec<String> = changed_files
+        .iter()
+        .filter(|file| !file.replacements.is_empty())
+        .filter(|file| {
+            sources
+                .get(&file.path)
+                .and_then(|source| declared_path(source))
+                != Some(file.path.as_str())
+        })
+        .map(|file| file.path.clone())
+        .collect();
+    paths.sort();
+    paths
+}
+
+pub fn print_unadopted(paths: &[String]) {
+    output::emit(&Event::Unadopted { files: paths });
+    notice!(
+        "\n⚠️  Plugins edited {} file(s) without a matching `#![forgen::file(...)]`; \
+         these edits are not applied when compiling:",
+        paths.len()
+    );
+    for path in paths {
+        notice!("   {path}");
+    }
+    notice!(
+        "   Adopt crate root files with `cargo forgen setup {}`; other module files \
+         cannot be rewritten.",
+        paths.join(" ")
+    );
+}
+
+fn selected_files(meta: &Metadata, options: &Options) -> Result<Vec<String>> {
+    let root = meta.workspace_root.as_std_path();
+    let mut files: BTreeSet<String> = options.files.iter().cloned().collect();
+
+    for name in &options.packages {
+        let Some(package) = meta
+            .workspace_packages()
+            .into_iter()
+            .find(|package| &package.name == name)
+        else {
+            bail!("`{name}` is not a workspace package");
+        };
+        for target in package
+            .targets
+            .iter()
+            .filter(|target| is_rust_crate(target))
+        {
+            files.insert(rel_path_of(root, target.src_path.as_std_path()));
+        }
+    }
+
+    if options.files.is_empty() && options.packages.is_empty() {
+        let mut sources = Vec::new();
+        for package in meta.workspace_packages() {
+            if let Some(dir) = package.manifest_path.parent() {
+                collect_rs_files(dir.as_std_path(), &mut sources)?;
+            }
+        }
+        for path in sources {
+            let source = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            if declared_path(&source).is_some() {
+                files.insert(rel_path_of(root, &path));
+            }
+        }
+    }
+
+    for rel_path in &files {
+        if !root.join(rel_path).is_file() {
+            bail!("{rel_path} does not exist");
+        }
+    }
+    Ok(files.into_iter().collect())
+}
+
+fn collect_rs_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        if path.is_dir() {
+            if name != "target" && !name.starts_with('.') {
+                collect_rs_files(&path, out)?;
+            }
+        } else if name.ends_with(".rs") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The workspace package that compiles `path` as a crate root. The macro
+/// cannot rewrite other module files, so those are rejected.
+fn crate_root_package<'a>(meta: &'a Metadata, path: &Path, rel_path: &str) -> Result<&'a Package> {
+    let packages = meta.workspace_packages();
+    let compiled_by = packages.iter().find(|package| {
+        package
+            .targets
+            .iter()
+            .any(|target| is_rust_crate(target) && target.src_path == path)
+    });
+    if let Some(package) = compiled_by {
+        return Ok(package);
+    }
+
+    let roots: Vec<String> = packages
+        .iter()
+        .filter(|package| {
+            package
+                .manifest_path
+                .parent()
+                .is_some_and(|dir| path.starts_with(dir))
+        })
+        .flat_map(|package| &package.targets)
+        .filter(|target| is_rust_crate(target))
+        .map(|target| {
+            rel_path_of(
+                meta.workspace_root.as_std_path(),
+                target.src_path.as_std_path(),
+            )
+        })
+        .collect();
+    if roots.is_empty() {
+        bail!("{rel_path} does not belong to a workspace package");
+    }
+    bail!(
+        "{rel_path} is not a crate root file; `forgen::file` only rewrites crate roots \
+         (here: {})",
+        roots.join(", ")
+    )
+}
+
+fn is_rust_crate(target: &cargo_metadata::Target) -> bool {
+    target
+        .kind
+        .iter()
+        .any(|kind| matches!(kind.as_str(), "lib" | "rlib" | "bin"))
+}
+
+fn add_forgen_dependency(meta: &Metadata, package: &Package) -> Result<()> {
+    let manifest_path = package.manifest_path.as_std_path();
+    let manifest = fs::read_to_string(manifest_path)
+        .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+    let mut doc: DocumentMut = manifest
+        .parse()
+        .with_context(|| format!("Failed to parse {}", manifest_path.display()))?;
+
+    let dependencies = doc
+        .entry("dependencies")
+        .or_insert(table())
+        .as_table_like_mut()
+        .with_context(|| {
+            format!(
+                "`dependencies` in {} is not a table",
+                manifest_path.display()
+            )
+        })?;
+
+    // Use the workspace's own checkout of the macro when it has one, then the
+    // requirement other members already use; `cargo-forgen` and `forgen` are
+    // released together otherwise.
+    let local = meta
+        .packages
+        .iter()
+        .find(|p| p.name == "forgen" && p.source.is_none())
+        .and_then(|p| p.manifest_path.parent());
+    let dependency = match local {
+        Some(dir) => {
+            let package_dir = manifest_path.parent().unwrap_or(Path::new("."));
+            let path = relative_path(package_dir, dir.as_std_path());
+            let mut inline = InlineTable::new();
+            inline.insert("path", path.to_string_lossy().replace('\\', "/").into());
+            value(inline)
+        }
+        None => {
+            let requirement = meta
+                .workspace_packages()
+                .iter()
+                .flat_map(|p| &p.dependencies)
+                .find(|dep| dep.name == "forgen")
+                .map(|dep| dep.req.to_string())
+                .unwrap_or_else(|| env!("CARGO_PKG_VERSION").to_owned());
+            value(requirement)
+        }
+    };
+    dependencies.insert("forgen", dependency);
+
+    fs::write(manifest_path, doc.to_string())
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))
+}
+
+// ---------------------------------------------------------------------------
+// Source edits
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, PartialEq, Eq)]
+enum AttrChange {
+    Added,
+    Repaired { from: String },
+    Unchanged,
+}
+
+/// The path declared by the `#![forgen::file("...")]` in `source`.
+pub fn declared_path(source: &str) -> Option<&str> {
+    declared_path_at(source).map(|(start, path)| &source[start..start + path.len()])
+}
+
+/// Byte offset and text of the declared path. Only an attribute that starts
+/// a line counts, so docs mentioning it are not mistaken for one.
+fn declared_path_at(source: &str) -> Option<(usize, &str)> {
+    line_offsets(source).find_map(|(offset, line)| {
+        let indent = line.len() - line.trim_start().len();
+        let rest = line.trim_start().strip_prefix(ATTR_PREFIX)?;
+        let len = rest.find(ATTR_SUFFIX)?;
+        Some((offset + indent + ATTR_PREFIX.len(), &rest[..len]))
+    })
+}
+
+/// `source` with `#![forgen::file("<rel_path>")]`, inserted after its inner
+/// attributes or with the declared path corrected.
+fn set_forgen_attr(source: &str, rel_path: &str) -> (String, AttrChange) {
+    match declared_path_at(source) {
+        Some((_, declared)) if declared == rel_path => (source.to_owned(), AttrChange::Unchanged),
+        Some((start, declared)) => {
+            let end = start + declared.len();
+            let repaired = format!("{}{rel_path}{}", &source[..start], &source[end..]);
+            (
+                repaired,
+                AttrChange::Repaired {
+                    from: declared.to_owned(),
+                },
+            )
+        }
+        None => {
+            let at = inner_attributes_end(source);
+            let line = format!("{ATTR_PREFIX}{rel_path}{ATTR_SUFFIX}\n");
+            let added = format!("{}{line}{}", &source[..at], &source[at..]);
+            (added, AttrChange::Added)
+        }
+    }
+}
+
+/// `source` with [`FORGEN_FEATURES`] (and [`FILE_MODULES_FEATURE`] when it
+/// has file modules) enabled, and the features it added.
+fn enable_features(source: &str) -> (String, Vec<&'static str>) {
+    let mut required = FORGEN_FEATURES.to_vec();
+    if has_file_modules(source) {
+        required.push(FILE_MODULES_FEATURE);
+    }
+    let feature_lines: Vec<(usize, &str)> = line_offsets(source)
+        .filter(|(_, line)| feature_list(line).is_some())
+        .collect();
+    let enabled: Vec<&str> = feature_lines
+        .iter()
+        .filter_map(|(_, line)| feature_list(line))
+        .flat_map(|list| list.split(',').map(str::trim))
+        .collect();
+    let missing: Vec<&'static str> = required
+        .into_iter()
+        .filter(|feature| !enabled.contains(feature))
+        .collect();
+    if missing.is_empty() {
+        return (source.to_owned(), missing);
+    }
+
+    let updated = match feature_lines.first() {
+        Some(&(offset, line)) => {
+            let list = feature_list(line).unwrap_or_default().trim();
+            let mut features: Vec<&str> = list
+                .split(',')
+                .map(str::trim)
+                .filter(|f| !f.is_empty())
+                .collect();
+            features.extend(&missing);
+            let indent = &line[..line.len() - line.trim_start().len()];
+            let end = offset + line.trim_end().len();
+            format!(
+                "{}{indent}#![feature({})]{}",
+                &source[..offset],
+                features.join(", "),
+                &source[end..]
+            )
+        }
+        None => {
+            // Right above `#![forgen::file(...)]`, like the README shows.
+            let at = declared_path_at(source)
+                .map(|(i, _)| source[..i].rfind('\n').map_or(0, |nl| nl + 1))
+                .unwrap_or_else(|| inner_attributes_end(source));
+            format!(
+                "{}#![feature({})]\n{}",
+                &source[..at],
+                missing.join(", "),
+                &source[at..]
+            )
+        }
+    };
+    (updated, missing)
+}
+
+fn has_file_modules(source: &str) -> bool {
+    source.lines().map(str::trim).any(|line| {
+        let decl = match line.strip_prefix("pub") {
+            Some(rest) if rest.starts_with('(') => rest.split_once(')').map_or("", |(_, r)| r),
+            Some(rest) => rest,
+            None => line,
+        };
+        decl.trim_start()
+            .strip_prefix("mod ")
+            .and_then(|rest| rest.strip_suffix(';'))
+            .is_some_and(|name| name.trim().chars().all(|c| c.is_alphanumeric() || c == '_'))
+    })
+}
+
+fn feature_list(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix("#![feature(")
+        .and_then(|rest| rest.strip_suffix(")]"))
+}
+
+fn line_offsets(source: &str) -> impl Iterator<Item = (usize, &str)> {
+    source.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line))
+    })
+}
+
+/// Byte offset just past the leading inner attributes and inner doc
+/// comments of `source` (0 when it has none).
+fn inner_attributes_end(source: &str) -> usize {
+    let mut end = 0;
+    let mut open_attr = 0i32;
+    for (offset, line) in line_offsets(source) {
+        let trimmed = line.trim();
+        if open_attr > 0 || trimmed.starts_with("#![") {
+            open_attr += trimmed.matches('[').count() as i32 - trimmed.matches(']').count() as i32;
+            end = offset + line.len();
+        } else if trimmed.starts_with("//!") {
+            end = offset + line.len();
+        } else if !(trimmed.is_empty()
+            || trimmed.starts_with("//")
+            || trimmed.starts_with("/*")
+            || trimmed.starts_with('*'))
+        {
+            break;
+        }
+    }
+    end
+}
+
+fn rel_path_of(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// `to` relative to the directory `from`; both are absolute.
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    path.extend(&to[common..]);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replacements::SavedReplacement;
+
+    #[test]
+    fn adds_and_repairs_the_attribute() {
+        let source = "//! Docs.\n#![allow(dead_code)]\n\nfn main() {}\n";
+        let (added, change) = set_forgen_attr(source, "app/src/main.rs");
+        assert_eq!(change, AttrChange::Added);
+        assert_eq!(
+            added,
+            "//! Docs.\n#![allow(dead_code)]\n#![forgen::file(\"app/src/main.rs\")]\n\nfn main() {}\n"
+        );
+
+        let (repaired, change) = set_forgen_attr(&added, "app/src/bin/main.rs");
+        assert_eq!(
+            change,
+            AttrChange::Repaired {
+                from: "app/src/main.rs".to_owned()
+            }
+        );
+        assert_eq!(declared_path(&repaired), Some("app/src/bin/main.rs"));
+        assert_eq!(declared_path("/// `#![forgen::file(\"x.rs\")]`\n"), None);
+        assert_eq!(
+            set_forgen_attr(&repaired, "app/src/bin/main.rs").1,
+            AttrChange::Unchanged
+        );
+    }
+
+    #[test]
+    fn enables_missing_features() {
+        let (source, added) = enable_features("#![forgen::file(\"src/lib.rs\")]\nfn a() {}\n");
+        assert_eq!(added, FORGEN_FEATURES);
+        assert_eq!(
+            source,
+            "#![feature(custom_inner_attributes, prelude_import)]\n\
+             #![forgen::file(\"src/lib.rs\")]\nfn a() {}\n"
+        );
+
+        let (source, added) = enable_features("#![feature(never_type, prelude_import)]\n");
+        assert_eq!(added, ["custom_inner_attributes"]);
+        assert_eq!(
+            source,
+            "#![feature(never_type, prelude_import, custom_inner_attributes)]\n"
+        );
+        assert!(enable_features(&source).1.is_empty());
+
+        let (_, added) = enable_features(&format!("{source}pub(crate) mod util;\n"));
+        assert_eq!(added, [FILE_MODULES_FEATURE]);
+        assert!(!has_file_modules("mod inline {\n}\n"));
+    }
+
+    #[test]
+    fn finds_edited_files_without_the_attribute() {
+        let changed = |path: &str| ChangedFile {
+            path: path.to_owned(),
+            replacements: vec![SavedReplacement {
+                index: 0,
+                old_text: String::new(),
+                new_text: "x".to_owned(),
+            }],
+        };
+        let sources = HashMap::from([
+            (
+                "src/lib.rs".to_owned(),
+                "#![forgen::file(\"src/lib.rs\")]\n".to_owned(),
+            ),
+            (
+                "src/moved.rs".to_owned(),
+                "#![forgen::file(\"src/old.rs\")]\n".to_owned(),
+            ),
+            ("src/plain.rs".to_owned(), "fn a() {}\n".to_owned()),
+        ]);
+        assert_eq!(
+            unadopted_files(
+                &[
+                    changed("src/plain.rs"),
+                    changed("src/lib.rs"),
+                    changed("src/moved.rs")
+                ],
+                &sources
+            ),
+            ["src/moved.rs", "src/plain.rs"]
+        );
+    }
+}
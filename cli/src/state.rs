@@ -0,0 +1,276 @@
+use anyhow::{bail, Context, Result};
+use forgen_api::{PluginState, SuiteRuntime};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Version of the on-disk state file layout (not of any plugin's state).
+const STATE_FILE_FORMAT: u32 = 1;
+
+/// Persisted plugin state, written to the file configured with
+/// `--state-file` or `[workspace.metadata.forgen] state-file = "..."`.
+///
+/// ```json
+/// {
+///   "format": 1,
+///   "plugins": {
+///     "seeded-binding": { "version": 0, "state": { "next-id": 3 } }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StateFile {
+    pub format: u32,
+    #[serde(default)]
+    pub plugins: BTreeMap<String, PersistedPluginState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersistedPluginState {
+    /// `Plugin::state_version()` of the plugin that wrote `state`.
+    pub version: u32,
+    pub state: PluginState,
+}
+
+impl StateFile {
+    pub fn from_runtime(runtime: &SuiteRuntime) -> Self {
+        let plugins = runtime
+            .plugin_states()
+            .filter(|(_, _, state)| !state.is_empty())
+            .map(|(plugin_id, version, state)| {
+                (
+                    plugin_id.to_owned(),
+                    PersistedPluginState {
+                        version: version.unwrap_or_default(),
+                        state: state.clone(),
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            format: STATE_FILE_FORMAT,
+            plugins,
+        }
+    }
+
+    pub fn restore_into(&self, runtime: &mut SuiteRuntime) {
+        for (plugin_id, persisted) in &self.plugins {
+            runtime.restore_plugin_state(plugin_id, persisted.version, persisted.state.clone());
+        }
+    }
+}
+
+/// Resolve where plugin state should be persisted: `--state-file`, else
+/// `[workspace.metadata.forgen] state-file`. Returns `None` when neither is
+/// set, which keeps state in memory only.
+pub fn resolve_state_path(
+    explicit: Option<&Path>,
+    meta: &cargo_metadata::Metadata,
+) -> Option<PathBuf> {
+    crate::config::configured_path(explicit, meta, "state-file")
+}
+
+/// Load the state file at `path`. A missing file is an empty state.
+pub fn load(path: &Path) -> Result<StateFile> {
+    if !path.exists() {
+        return Ok(StateFile {
+            format: STATE_FILE_FORMAT,
+            plugins: BTreeMap::new(),
+        });
+    }
+
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read state file {}", path.display()))?;
+    let file: StateFile = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse state file {}", path.display()))?;
+
+    if file.format != STATE_FILE_FORMAT {
+        bail!(
+            "State file {} has format {}, but this cargo-forgen reads format {}. \
+             Run `cargo forgen state reset` to start over.",
+            path.display(),
+            file.format,
+            STATE_FILE_FORMAT
+        );
+    }
+
+    Ok(file)
+}
+
+pub fn save(path: &Path, file: &StateFile) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let mut json = serde_json::to_string_pretty(file)?;
+    json.push('\n');
+    fs::write(path, json).with_context(|| format!("Failed to write state file {}", path.display()))
+}
+
+// ---------------------------------------------------------------------------
+// `cargo forgen state ...`
+// ---------------------------------------------------------------------------
+
+pub fn show(path: &Path, plugin_id: Option<&str>) -> Result<()> {
+    let file = load(path)?;
+
+    match plugin_id {
+        Some(plugin_id) => {
+            let Some(persisted) = file.plugins.get(plugin_id) else {
+                println!(
+                    "No stored state for plugin `{plugin_id}` in {}",
+                    path.display()
+                );
+                return Ok(());
+            };
+            println!("{}", serde_json::to_string_pretty(persisted)?);
+        }
+        None if file.plugins.is_empty() => {
+            println!("No stored plugin state in {}", path.display());
+        }
+        None => println!("{}", serde_json::to_string_pretty(&file)?),
+    }
+
+    Ok(())
+}
+
+pub fn reset(path: &Path, plugin_id: Option<&str>) -> Result<()> {
+    let mut file = load(path)?;
+
+    match plugin_id {
+        Some(plugin_id) => {
+            if file.plugins.remove(plugin_id).is_none() {
+                println!("No stored state for plugin `{plugin_id}`");
+                return Ok(());
+            }
+            save(path, &file)?;
+            println!("🗑  Reset state of plugin `{plugin_id}`");
+        }
+        None => {
+            file.plugins.clear();
+            save(path, &file)?;
+            println!("🗑  Reset state of all plugins in {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Open one plugin's state in `$VISUAL` / `$EDITOR` and write it back if it
+/// still parses.
+pub fn edit(path: &Path, plugin_id: &str) -> Result<()> {
+    if !forgen_api::is_valid_plugin_id(plugin_id) {
+        bail!("`{plugin_id}` is not a valid plugin id");
+    }
+
+    let mut file = load(path)?;
+    let current = file
+        .plugins
+        .get(plugin_id)
+        .cloned()
+        .unwrap_or(PersistedPluginState {
+            version: 0,
+            state: PluginState::new(),
+        });
+
+    let scratch = std::env::temp_dir().join(format!(
+        "forgen-state-{plugin_id}-{}.json",
+        std::process::id()
+    ));
+    fs::write(&scratch, serde_json::to_string_pretty(&current)? + "\n")
+        .with_context(|| format!("Failed to write {}", scratch.display()))?;
+
+    let edited = run_editor(&scratch).and_then(|()| {
+        let text = fs::read_to_string(&scratch)
+            .with_context(|| format!("Failed to read {}", scratch.display()))?;
+        serde_json::from_str::<PersistedPluginState>(&text).context(
+            "Edited state is not valid; expected `{ \"version\": <u32>, \"state\": { ... } }`",
+        )
+    });
+    let _ = fs::remove_file(&scratch);
+    let edited = edited?;
+
+    if edited.state.is_empty() {
+        file.plugins.remove(plugin_id);
+    } else {
+        file.plugins.insert(plugin_id.to_owned(), edited);
+    }
+    save(path, &file)?;
+    println!(
+        "✅ Saved state of plugin `{plugin_id}` to {}",
+        path.display()
+    );
+
+    Ok(())
+}
+
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_owned());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().context("$EDITOR is empty")?;
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to launch editor `{editor}`"))?;
+
+    if !status.success() {
+        bail!("Editor `{editor}` exited with {status}; state left unchanged");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_state_restores_into_a_new_runtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/forgen.json");
+        let mut state = PluginState::new();
+        state.set("next-id", 3u32).unwrap();
+        let mut runtime = SuiteRuntime::with_seed(1);
+        runtime.restore_plugin_state("seeded-binding", 2, state);
+        // Empty states are not written.
+        runtime.plugin_state_mut("idle");
+
+        save(&path, &StateFile::from_runtime(&runtime)).unwrap();
+        let file = load(&path).unwrap();
+        assert_eq!(file.format, STATE_FILE_FORMAT);
+        assert_eq!(file.plugins.keys().collect::<Vec<_>>(), ["seeded-binding"]);
+
+        let mut restored = SuiteRuntime::with_seed(2);
+        file.restore_into(&mut restored);
+        let (plugin_id, version, state) = restored.plugin_states().next().unwrap();
+        assert_eq!((plugin_id, version), ("seeded-binding", Some(2)));
+        assert_eq!(state.get::<u32>("next-id"), Some(3));
+    }
+
+    #[test]
+    fn a_missing_file_is_an_empty_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = load(&dir.path().join("missing.json")).unwrap();
+        assert_eq!(file.format, STATE_FILE_FORMAT);
+        assert!(file.plugins.is_empty());
+    }
+
+    #[test]
+    fn rejects_an_unknown_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forgen.json");
+        fs::write(&path, r#"{ "format": 2, "plugins": {} }"#).unwrap();
+
+        let error = load(&path).unwrap_err().to_string();
+        assert!(error.contains("has format 2"), "{error}");
+        assert!(error.contains("cargo forgen state reset"), "{error}");
+    }
+}
@@ -0,0 +1,90 @@
+//! Fixtures shared by the unit tests of several modules.
+
+use cargo_metadata::Metadata;
+use serde_json::{json, Value};
+
+/// `cargo metadata` output for a workspace at `/ws` without packages, with
+/// `[workspace.metadata]` set to `workspace_metadata`.
+pub fn metadata(workspace_metadata: Value) -> Metadata {
+    workspace(workspace_metadata, Vec::new())
+}
+
+/// `cargo metadata` output for a workspace at `/ws` with `packages` built by
+/// [`package`] and [`registry_package`]. Packages without a source are the
+/// workspace members.
+pub fn workspace(workspace_metadata: Value, packages: Vec<Value>) -> Metadata {
+    let members: Vec<&Value> = packages
+        .iter()
+        .filter(|package| package["source"].is_null())
+        .map(|package| &package["id"])
+        .collect();
+    serde_json::from_value(json!({
+        "packages": packages,
+        "workspace_members": members,
+        "resolve": null,
+        "workspace_root": "/ws",
+        "target_directory": "/ws/target",
+        "metadata": workspace_metadata,
+        "version": 1,
+    }))
+    .expect("fixture is valid cargo metadata")
+}
+
+/// A local package with its manifest at `/ws/<dir>/Cargo.toml`.
+pub fn package(name: &str, dir: &str, dependencies: Vec<Value>) -> Value {
+    package_json(name, None, &format!("/ws/{dir}"), dependencies)
+}
+
+/// A crates.io package, unpacked where cargo keeps registry sources.
+pub fn registry_package(name: &str) -> Value {
+    let dir = format!("/home/.cargo/registry/src/index.crates.io/{name}-1.0.0");
+    let source = "registry+https://github.com/rust-lang/crates.io-index";
+    package_json(name, Some(source), &dir, Vec::new())
+}
+
+fn package_json(name: &str, source: Option<&str>, dir: &str, dependencies: Vec<Value>) -> Value {
+    json!({
+        "name": name,
+        "version": "0.1.0",
+        "id": format!("{}#{name}@0.1.0", source.unwrap_or("path+file://")),
+        "source": source,
+        "dependencies": dependencies,
+        "targets": [{
+            "name": name,
+            "kind": ["lib"],
+            "crate_types": ["lib"],
+            "src_path": format!("{dir}/src/lib.rs"),
+            "edition": "2021",
+        }],
+        "features": {},
+        "manifest_path": format!("{dir}/Cargo.toml"),
+        "rust_version": null,
+    })
+}
+
+/// A dependency on the local package in `/ws/<dir>`.
+pub fn path_dependency(name: &str, dir: &str) -> Value {
+    json!({
+        "name": name,
+        "source": null,
+        "req": "*",
+        "kind": null,
+        "optional": false,
+        "uses_default_features": true,
+        "features": [],
+        "path": format!("/ws/{dir}"),
+    })
+}
+
+/// A dependency on a crates.io package.
+pub fn registry_dependency(name: &str) -> Value {
+    json!({
+        "name": name,
+        "source": "registry+https://github.com/rust-lang/crates.io-index",
+        "req": "^1",
+        "kind": null,
+        "optional": false,
+        "uses_default_features": true,
+        "features": [],
+    })
+}
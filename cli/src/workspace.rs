@@ -1,3 +1,4 @@
+use crate::output::{detail, info};
 use anyhow::{Context, Result};
 use cargo_metadata::{Metadata, MetadataCommand};
 use ra_ap_hir::ChangeWithProcMacros;
@@ -59,6 +60,28 @@ pub fn get_workspace_info(manifest_path: &PathBuf) -> Result<WorkspaceInfo> {
     })
 }
 
+/// Whether two `cargo metadata` results describe the same packages, targets
+/// and dependencies, ignoring `[package.metadata]` and
+/// `[workspace.metadata]`, which rust-analyzer does not read.
+pub fn same_crate_graph(old: &Metadata, new: &Metadata) -> bool {
+    let graph = |meta: &Metadata| {
+        let mut value = serde_json::to_value(meta).expect("cargo metadata serializes to JSON");
+        if let Some(object) = value.as_object_mut() {
+            // `workspace_metadata` serializes as `metadata`.
+            object.remove("metadata");
+        }
+        if let Some(packages) = value.get_mut("packages").and_then(|p| p.as_array_mut()) {
+            for package in packages {
+                if let Some(package) = package.as_object_mut() {
+                    package.remove("metadata");
+                }
+            }
+        }
+        value
+    };
+    graph(old) == graph(new)
+}
+
 pub fn load_workspace(
     manifest_path: &AbsPathBuf,
     options: WorkspaceLoadOptions,
@@ -66,7 +89,7 @@ pub fn load_workspace(
     let start = Instant::now();
     let progress_start = Instant::now();
 
-    println!(
+    detail!(
         "⏱️  load_workspace: proc macros = {}",
         match options.proc_macro_server {
             ProcMacroServerChoice::Sysroot => "enabled (sysroot)",
@@ -74,7 +97,7 @@ pub fn load_workspace(
             ProcMacroServerChoice::None => "disabled",
         }
     );
-    println!(
+    detail!(
         "⏱️  load_workspace: build scripts / out dirs = {}",
         if options.load_out_dirs_from_check {
             "enabled"
@@ -82,7 +105,7 @@ pub fn load_workspace(
             "disabled"
         }
     );
-    println!(
+    detail!(
         "⏱️  load_workspace: prefill caches = {}",
         if options.prefill_caches {
             "enabled"
@@ -90,7 +113,7 @@ pub fn load_workspace(
             "disabled"
         }
     );
-    println!(
+    detail!(
         "⏱️  load_workspace: starting rust-analyzer load for {}",
         <AbsPathBuf as AsRef<std::path::Path>>::as_ref(manifest_path).display()
     );
@@ -109,7 +132,7 @@ pub fn load_workspace(
             proc_macro_processes: 1,
         },
         &|msg: String| {
-            println!(
+            detail!(
                 "  [{:>6.2}s] {}",
                 progress_start.elapsed().as_secs_f32(),
                 msg
@@ -118,7 +141,7 @@ pub fn load_workspace(
     )
     .with_context(|| "Failed to load workspace")?;
 
-    println!(
+    info!(
         "✅ Workspace loaded successfully in {:.2}s!\n",
         start.elapsed().as_secs_f32()
     );
@@ -249,3 +272,58 @@ pub fn file_id_to_path(
         vfs.file_path(file_id).as_path()?
     )))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        package, path_dependency, registry_dependency, registry_package, workspace,
+    };
+    use serde_json::{json, Value};
+
+    fn members(app_dependencies: Vec<Value>) -> Vec<Value> {
+        vec![
+            package("app", "app", app_dependencies),
+            package("plugins", "plugins", Vec::new()),
+        ]
+    }
+
+    #[test]
+    fn metadata_edits_keep_the_crate_graph() {
+        let old = workspace(json!({ "forgen": { "seed": 1 } }), members(Vec::new()));
+        let mut packages = members(Vec::new());
+        packages[0]["metadata"] = json!({ "forgen": { "plugins": { "demo": { "n": 2 } } } });
+        let new = workspace(
+            json!({ "forgen": { "seed": 2, "suite": "plugins" } }),
+            packages,
+        );
+
+        assert!(same_crate_graph(&old, &new));
+    }
+
+    #[test]
+    fn a_new_member_changes_the_crate_graph() {
+        let old = workspace(json!({}), members(Vec::new()));
+        let mut packages = members(Vec::new());
+        packages.push(package("tools", "tools", Vec::new()));
+        let new = workspace(json!({}), packages);
+
+        assert!(!same_crate_graph(&old, &new));
+    }
+
+    #[test]
+    fn a_new_dependency_changes_the_crate_graph() {
+        let old = workspace(json!({}), members(Vec::new()));
+        let mut packages = members(vec![registry_dependency("serde")]);
+        packages.push(registry_package("serde"));
+        let new = workspace(json!({}), packages);
+        assert!(!same_crate_graph(&old, &new));
+
+        // A dependency between members adds no package.
+        let new = workspace(
+            json!({}),
+            members(vec![path_dependency("plugins", "plugins")]),
+        );
+        assert!(!same_crate_graph(&old, &new));
+    }
+}
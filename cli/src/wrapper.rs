@@ -0,0 +1,287 @@
+//! `cargo forgen build` / `test` / `run`: refresh `target/.forgen/`, then
+//! hand over to cargo.
+
+use crate::output::{self, info, Verbosity};
+use crate::session::{run_and_persist, watch_targets};
+use crate::{
+    daemon, default_socket, freshness, load_session, loader, seeds, state, workspace, CargoArgs,
+    OutputMode, RunArgs,
+};
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Bring `target/.forgen/` up to date, then run `cargo <subcommand>` with the
+/// passthrough arguments and exit with its status. A daemon or watcher on the
+/// socket does the run; without one the plugins run here, unless no input
+/// changed since the last run of this command.
+pub fn run_cargo_command(subcommand: &str, args: CargoArgs) -> Result<()> {
+    if args.run.watch || args.run.dry_run {
+        anyhow::bail!("`cargo forgen {subcommand}` does not support --watch or --dry-run");
+    }
+    output::init(
+        args.run.message_format,
+        Verbosity::from_flags(args.run.quiet, args.run.verbose),
+    );
+    let manifest = args.run.manifest.clone();
+    let socket = match args.socket {
+        Some(socket) => socket,
+        None => default_socket(manifest.clone())?,
+    };
+    if !refresh_through_daemon(&socket, &daemon_ignored_flags(&args.run))? {
+        refresh_in_process(args.run)?;
+    }
+
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let status = Command::new(cargo)
+        .args(cargo_command_args(
+            subcommand,
+            manifest.as_deref(),
+            &args.cargo_args,
+        ))
+        .status()
+        .with_context(|| format!("Failed to run `cargo {subcommand}`"))?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+/// The arguments of `cargo <subcommand>`: `--manifest-path manifest`, unless
+/// `cargo_args` name a manifest themselves, then `cargo_args`.
+fn cargo_command_args(
+    subcommand: &str,
+    manifest: Option<&Path>,
+    cargo_args: &[String],
+) -> Vec<OsString> {
+    let mut command_args = vec![OsString::from(subcommand)];
+    // Arguments after cargo's own `--` belong to the test binary or program.
+    let user_manifest = cargo_args
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "--manifest-path" || arg.starts_with("--manifest-path="));
+    if let Some(manifest) = manifest.filter(|_| !user_manifest) {
+        command_args.push("--manifest-path".into());
+        command_args.push(manifest.into());
+    }
+    command_args.extend(cargo_args.iter().map(OsString::from));
+    command_args
+}
+
+/// The options of `args` that change what a run does or writes. A daemon or
+/// watcher runs with the options it was started with, so it cannot honour
+/// them.
+fn daemon_ignored_flags(args: &RunArgs) -> Vec<&'static str> {
+    [
+        ("--state-file", args.state_file.is_some()),
+        ("--seed", args.seed.is_some()),
+        ("--seed-lock", args.seed_lock.is_some()),
+        ("--only", !args.only.is_empty()),
+        ("--skip", !args.skip.is_empty()),
+        ("--max-passes", args.max_passes.is_some()),
+        ("--no-proc-macros", args.no_proc_macros),
+        ("--with-build-scripts", args.with_build_scripts),
+        ("--diagnostics-json", args.diagnostics_json.is_some()),
+        ("--diagnostics-sarif", args.diagnostics_sarif.is_some()),
+        ("--trace-dir", args.trace_dir.is_some()),
+        ("--diff", args.diff),
+        ("--strip-markers", args.strip_markers),
+        ("--patch", args.patch.is_some()),
+        ("--profile", args.profile),
+        ("--profile-trace", args.profile_trace.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(flag, set)| set.then_some(flag))
+    .collect()
+}
+
+/// Ask the daemon or watcher on `socket` to bring the output up to date.
+/// Returns `false` when nothing answers there. Fails instead of asking when
+/// `ignored_flags` were given and something answers.
+#[cfg(unix)]
+fn refresh_through_daemon(socket: &Path, ignored_flags: &[&str]) -> Result<bool> {
+    if !socket.exists() {
+        return Ok(false);
+    }
+    if !ignored_flags.is_empty() {
+        if daemon::request(socket, &daemon::Request::Status).is_err() {
+            return Ok(false);
+        }
+        anyhow::bail!(
+            "A daemon or watcher answers on {} and runs with its own options, so it would \
+             ignore {}; stop it or leave them out",
+            socket.display(),
+            ignored_flags.join(", ")
+        );
+    }
+    let Ok((_, response)) = daemon::request(socket, &daemon::Request::Run { force: false }) else {
+        return Ok(false);
+    };
+    match response {
+        daemon::Response::Run { ran, errors, .. } => {
+            if errors > 0 {
+                anyhow::bail!("plugins reported {errors} error(s)");
+            }
+            if ran {
+                info!("✅ {} refreshed target/.forgen/", socket.display());
+            } else {
+                info!("✅ target/.forgen/ is up to date ({})", socket.display());
+            }
+            Ok(true)
+        }
+        daemon::Response::Error { message } => anyhow::bail!("{message}"),
+        other => anyhow::bail!("Unexpected answer to a run request: {other:?}"),
+    }
+}
+
+#[cfg(not(unix))]
+fn refresh_through_daemon(_socket: &Path, _ignored_flags: &[&str]) -> Result<bool> {
+    Ok(false)
+}
+
+/// Run the plugins like `cargo forgen`, unless `target/.forgen/` was saved by
+/// this command from the same sources, manifests, state file, seed lock and
+/// options. Options that write or print something besides `target/.forgen/`
+/// always run.
+fn refresh_in_process(args: RunArgs) -> Result<()> {
+    let manifest_path = args
+        .manifest
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("Cargo.toml"))
+        .canonicalize()?;
+    let workspace_info = workspace::get_workspace_info(&manifest_path)?;
+    let meta = &workspace_info.cargo_metadata;
+    let suite_dirs = loader::suite_source_dirs(meta);
+    let targets = watch_targets(&workspace_info, &suite_dirs);
+    // The run rewrites these, so they are fingerprinted as it leaves them.
+    let rewritten = [
+        state::resolve_state_path(args.state_file.as_deref(), meta),
+        seeds::resolve_lock_path(args.seed_lock.as_deref(), meta),
+    ];
+    let settings = (
+        manifest_path.clone(),
+        args.seed,
+        args.only.clone(),
+        args.skip.clone(),
+        args.max_passes,
+        args.no_proc_macros,
+        args.with_build_scripts,
+    );
+    let fingerprint = |mut sources: freshness::Sources| {
+        sources.add(&workspace_info.root.join("Cargo.lock"));
+        for path in rewritten.iter().flatten() {
+            sources.add(path);
+        }
+        freshness::fingerprint(&sources, &settings)
+    };
+
+    let exports = args.diagnostics_json.is_some()
+        || args.diagnostics_sarif.is_some()
+        || args.patch.is_some()
+        || args.trace_dir.is_some()
+        || args.profile_trace.is_some()
+        || args.diff
+        || args.profile;
+    let sources = freshness::Sources::scan(&targets);
+    if !exports && freshness::is_fresh(&workspace_info.root, &fingerprint(sources)) {
+        info!("✅ target/.forgen/ is up to date; no inputs changed since the last run");
+        return Ok(());
+    }
+
+    let sources = freshness::Sources::scan(&targets);
+    let mut session = load_session(args, OutputMode::Save)?;
+    let outcome = run_and_persist(&mut session, true, OutputMode::Save)?;
+    if outcome.errors > 0 {
+        anyhow::bail!("plugins reported {} error(s)", outcome.errors);
+    }
+    freshness::record(&workspace_info.root, &fingerprint(sources))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cli, Command as CliCommand, ForgenCommand};
+    use clap::Parser;
+
+    fn args(subcommand: &str, manifest: Option<&str>, cargo_args: &[&str]) -> Vec<String> {
+        let cargo_args: Vec<String> = cargo_args.iter().map(|arg| arg.to_string()).collect();
+        cargo_command_args(subcommand, manifest.map(Path::new), &cargo_args)
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn passes_the_manifest_on_to_cargo() {
+        assert_eq!(
+            args("build", Some("/ws/Cargo.toml"), &["--release"]),
+            ["build", "--manifest-path", "/ws/Cargo.toml", "--release"]
+        );
+        assert_eq!(args("test", None, &["--release"]), ["test", "--release"]);
+    }
+
+    #[test]
+    fn a_manifest_in_the_cargo_args_wins() {
+        assert_eq!(
+            args(
+                "build",
+                Some("/ws/Cargo.toml"),
+                &["--manifest-path", "a/Cargo.toml"]
+            ),
+            ["build", "--manifest-path", "a/Cargo.toml"]
+        );
+        assert_eq!(
+            args(
+                "run",
+                Some("/ws/Cargo.toml"),
+                &["--manifest-path=a/Cargo.toml"]
+            ),
+            ["run", "--manifest-path=a/Cargo.toml"]
+        );
+    }
+
+    #[test]
+    fn arguments_after_a_separator_belong_to_the_program() {
+        assert_eq!(
+            args(
+                "run",
+                Some("/ws/Cargo.toml"),
+                &["--", "--manifest-path", "x"]
+            ),
+            [
+                "run",
+                "--manifest-path",
+                "/ws/Cargo.toml",
+                "--",
+                "--manifest-path",
+                "x"
+            ]
+        );
+    }
+
+    fn run_args(command: &[&str]) -> RunArgs {
+        let cli = Cli::try_parse_from(["cargo", "forgen"].iter().chain(command)).unwrap();
+        let CliCommand::Forgen(args) = cli.command;
+        match args.command {
+            Some(ForgenCommand::Build(cargo_args)) => cargo_args.run,
+            other => panic!("not a build command: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn lists_the_options_a_daemon_ignores() {
+        assert!(daemon_ignored_flags(&run_args(&["build", "--verbose"])).is_empty());
+        assert_eq!(
+            daemon_ignored_flags(&run_args(&[
+                "build",
+                "--seed",
+                "3",
+                "--diff",
+                "--",
+                "--release"
+            ])),
+            ["--seed", "--diff"]
+        );
+    }
+}
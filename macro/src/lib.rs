@@ -1,26 +1,30 @@
+#![feature(proc_macro_diagnostic)]
 #![feature(proc_macro_tracked_path)]
 
 extern crate proc_macro;
 
-use proc_macro::{tracked, Group, Span, TokenStream, TokenTree};
+use forgen_api::saved::{
+    apply_resolved_replacements, diagnostics_path, normalize_rel_path, remove_forgen_attr_line,
+    replacement_path, resolve_saved_replacements, SavedReplacement,
+};
+use proc_macro::{tracked, Diagnostic, Group, Level, Span, TokenStream, TokenTree};
 use serde::Deserialize;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use syn::LitStr;
 
+/// Plugin diagnostic written by the CLI to
+/// `target/.forgen/<mirrored-path>.diagnostics.json`. Only the fields needed to
+/// re-emit it are read.
 #[derive(Debug, Clone, Deserialize)]
-struct SavedReplacement {
-    index: usize,
-    old_text: String,
-    new_text: String,
-}
-
-#[derive(Debug, Clone)]
-struct ResolvedReplacement {
-    start: usize,
-    end: usize,
-    new_text: String,
+struct SavedDiagnostic {
+    plugin_id: String,
+    severity: String,
+    file: String,
+    line: usize,
+    column: usize,
+    message: String,
 }
 
 #[proc_macro_attribute]
@@ -57,13 +61,12 @@ fn expand_file(attr: TokenStream, input: TokenStream) -> Result<TokenStream, Str
         ));
     }
 
-    let json_path = workspace_root
-        .join("target")
-        .join(".forgen")
-        .join(format!("{}.json", normalize_rel_path(&declared_rel_path)));
+    let json_path = replacement_path(&workspace_root, &declared_rel_path);
 
     track(&json_path);
 
+    emit_saved_diagnostics(&workspace_root, &declared_rel_path, &input)?;
+
     if !json_path.exists() {
         return Ok(input);
     }
@@ -112,8 +115,10 @@ fn expand_file(attr: TokenStream, input: TokenStream) -> Result<TokenStream, Str
             "forgen::file could not derive the original source body token stream".to_string()
         })?;
 
-    let resolved = resolve_saved_replacements(&original_source, &saved)?;
-    let rewritten_full_source = apply_resolved_replacements(&original_source, &resolved)?;
+    let resolved = resolve_saved_replacements(&original_source, &saved)
+        .map_err(|e| format!("forgen::file {e}"))?;
+    let rewritten_full_source = apply_resolved_replacements(&original_source, &resolved)
+        .map_err(|e| format!("forgen::file {e}"))?;
     let rewritten_without_attr =
         remove_forgen_attr_line(&rewritten_full_source, &declared_rel_path).ok_or_else(|| {
             format!(
@@ -137,153 +142,101 @@ fn expand_file(attr: TokenStream, input: TokenStream) -> Result<TokenStream, Str
     Ok(merged)
 }
 
-fn parse_attr_path(attr: TokenStream) -> Result<String, String> {
-    syn::parse::<LitStr>(attr)
-        .map(|lit| lit.value())
-        .map_err(|_| {
-            "forgen::file expected a single string literal path like `\"src/lib.rs\"`".to_string()
-        })
-}
-
-fn resolve_saved_replacements(
-    source: &str,
-    saved: &[SavedReplacement],
-) -> Result<Vec<ResolvedReplacement>, String> {
-    let mut out = Vec::with_capacity(saved.len());
-
-    for rep in saved {
-        let start = find_occurrence_start(source, &rep.old_text, rep.index).ok_or_else(|| {
-            format!(
-                "forgen::file could not find occurrence {} of `{}` in source",
-                rep.index, rep.old_text
-            )
-        })?;
-
-        let end = start + rep.old_text.len();
-
-        if !source.is_char_boundary(start) || !source.is_char_boundary(end) {
-            return Err(format!(
-                "forgen::file resolved a replacement to non-character boundaries: [{}..{}]",
-                start, end
-            ));
-        }
+/// Re-emit the diagnostics plugins reported for this file as compiler
+/// warnings and errors, pointing at the input token closest to each location.
+fn emit_saved_diagnostics(
+    workspace_root: &Path,
+    declared_rel_path: &str,
+    input: &TokenStream,
+) -> Result<(), String> {
+    let diagnostics_path = diagnostics_path(workspace_root, declared_rel_path);
 
-        if &source[start..end] != rep.old_text {
-            return Err(format!(
-                "forgen::file resolved replacement text mismatch at [{}..{}]",
-                start, end
-            ));
-        }
+    track(&diagnostics_path);
 
-        out.push(ResolvedReplacement {
-            start,
-            end,
-            new_text: rep.new_text.clone(),
-        });
+    if !diagnostics_path.exists() {
+        return Ok(());
     }
 
-    out.sort_by_key(|r| (r.start, r.end));
+    let json = fs::read_to_string(&diagnostics_path).map_err(|e| {
+        format!(
+            "forgen::file failed to read diagnostics file `{}`: {}",
+            diagnostics_path.display(),
+            e
+        )
+    })?;
 
-    for pair in out.windows(2) {
-        let a = &pair[0];
-        let b = &pair[1];
-        if a.end > b.start {
-            return Err(format!(
-                "forgen::file found overlapping saved replacements: [{}..{}) overlaps [{}..{})",
-                a.start, a.end, b.start, b.end
+    let saved: Vec<SavedDiagnostic> = serde_json::from_str(&json).map_err(|e| {
+        format!(
+            "forgen::file failed to parse diagnostics file `{}` as JSON: {}",
+            diagnostics_path.display(),
+            e
+        )
+    })?;
+
+    for diagnostic in saved {
+        let level = match diagnostic.severity.as_str() {
+            "error" => Level::Error,
+            _ => Level::Warning,
+        };
+        let span =
+            span_at(input, diagnostic.line, diagnostic.column).unwrap_or_else(Span::call_site);
+        let mut emitted = Diagnostic::spanned(
+            span,
+            level,
+            format!("[{}] {}", diagnostic.plugin_id, diagnostic.message),
+        );
+        // Input spans are not always precise (crate-root input is reparsed
+        // and everything points at the first line), so spell out the
+        // location when the span does not land on it.
+        if span.line() != diagnostic.line {
+            emitted = emitted.note(format!(
+                "reported at {}:{}:{}",
+                diagnostic.file, diagnostic.line, diagnostic.column
             ));
         }
+        emitted.emit();
     }
 
-    Ok(out)
+    Ok(())
 }
 
-fn apply_resolved_replacements(
-    source: &str,
-    replacements: &[ResolvedReplacement],
-) -> Result<String, String> {
-    let mut out = source.to_string();
-
-    for rep in replacements.iter().rev() {
-        if rep.start > rep.end || rep.end > out.len() {
-            return Err(format!(
-                "forgen::file found out-of-bounds replacement range [{}..{})",
-                rep.start, rep.end
-            ));
-        }
-        if !out.is_char_boundary(rep.start) || !out.is_char_boundary(rep.end) {
-            return Err(format!(
-                "forgen::file found non-character-boundary replacement range [{}..{})",
-                rep.start, rep.end
-            ));
-        }
-
-        out.replace_range(rep.start..rep.end, &rep.new_text);
-    }
-
-    Ok(out)
+/// Span of the last token in `stream` that starts at or before the 1-based
+/// `line` / `column`.
+fn span_at(stream: &TokenStream, line: usize, column: usize) -> Option<Span> {
+    let mut best = None;
+    find_span_at(stream, (line, column), &mut best);
+    best
 }
 
-fn find_occurrence_start(source: &str, needle: &str, target_index: usize) -> Option<usize> {
-    if needle.is_empty() {
-        let mut seen = 0usize;
-        for i in 0..=source.len() {
-            if !source.is_char_boundary(i) {
-                continue;
-            }
-            if seen == target_index {
-                return Some(i);
-            }
-            seen += 1;
+fn find_span_at(stream: &TokenStream, target: (usize, usize), best: &mut Option<Span>) {
+    for tree in stream.clone() {
+        let span = match &tree {
+            TokenTree::Group(group) => group.span_open(),
+            _ => tree.span(),
+        };
+        if (span.line(), span.column()) > target {
+            return;
         }
-        return None;
-    }
+        *best = Some(span);
 
-    let mut seen = 0usize;
-    for i in 0..source.len() {
-        if !source.is_char_boundary(i) {
-            continue;
-        }
-        if source[i..].starts_with(needle) {
-            if seen == target_index {
-                return Some(i);
+        if let TokenTree::Group(group) = &tree {
+            let close = group.span_close();
+            if (close.line(), close.column()) <= target {
+                *best = Some(close);
+            } else {
+                find_span_at(&group.stream(), target, best);
+                return;
             }
-            seen += 1;
-        }
-    }
-
-    None
-}
-
-fn remove_forgen_attr_line(source: &str, declared_rel_path: &str) -> Option<String> {
-    let normalized = normalize_rel_path(declared_rel_path);
-
-    let candidates = [
-        format!("#![forgen::file(\"{}\")]", declared_rel_path),
-        format!("#![forgen::file(\"{}\")]", normalized),
-    ];
-
-    for candidate in candidates {
-        if let Some(s) = remove_line_containing(source, &candidate) {
-            return Some(s);
         }
     }
-
-    None
 }
 
-fn remove_line_containing(source: &str, needle: &str) -> Option<String> {
-    let idx = source.find(needle)?;
-    let line_start = source[..idx].rfind('\n').map(|i| i + 1).unwrap_or(0);
-    let line_end = source[idx..]
-        .find('\n')
-        .map(|off| idx + off + 1)
-        .unwrap_or(source.len());
-
-    let mut out = String::with_capacity(source.len().saturating_sub(line_end - line_start));
-    out.push_str(&source[..line_start]);
-    out.push_str(&source[line_end..]);
-    Some(out)
+fn parse_attr_path(attr: TokenStream) -> Result<String, String> {
+    syn::parse::<LitStr>(attr)
+        .map(|lit| lit.value())
+        .map_err(|_| {
+            "forgen::file expected a single string literal path like `\"src/lib.rs\"`".to_string()
+        })
 }
 
 fn choose_body_candidate_pair(
@@ -691,10 +644,6 @@ fn find_workspace_root(start_file: &Path) -> Option<PathBuf> {
     }
 }
 
-fn normalize_rel_path(path: &str) -> String {
-    path.replace('\\', "/")
-}
-
 fn normalize_path(path: &Path) -> String {
     path.to_string_lossy().replace('\\', "/")
 }
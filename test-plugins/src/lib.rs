@@ -6,17 +6,21 @@
 
 mod plugins;
 
-use forgen_api::{plugin_suite, FileReplacement, SuiteRuntime, WorkspaceContext};
+use forgen_api::{
+    plugin_suite, FileReplacement, Registration, Schedule, SuiteRuntime, WorkspaceContext,
+};
 
 fn run(ctx: &WorkspaceContext, runtime: &mut SuiteRuntime) -> Vec<FileReplacement> {
-    let mut out = Vec::new();
+    // Register plugins here — just regular Rust values, no FFI. Each phase
+    // runs to its own fixed point before the next one starts, so the logger
+    // also sees the binding that `seeded-binding` generates.
+    let schedule = Schedule::new()
+        .phases(["expand", "instrument", "report"])
+        .register(Registration::new(&plugins::seeded_binding::SeededBindingPlugin).phase("expand"))
+        .register(Registration::new(&plugins::f64_logger::F64LoggerPlugin).phase("instrument"))
+        .register(Registration::new(&plugins::example::ExamplePlugin).phase("report"));
 
-    // Register plugins here — just regular Rust method calls, no FFI.
-    out.extend(runtime.run_plugin(&plugins::example::ExamplePlugin, ctx));
-    out.extend(runtime.run_plugin(&plugins::seeded_binding::SeededBindingPlugin, ctx));
-    out.extend(runtime.run_plugin(&plugins::f64_logger::F64LoggerPlugin, ctx));
-
-    out
+    runtime.run_schedule(&schedule, ctx)
 }
 
 plugin_suite!(run);
@@ -9,9 +9,11 @@
 //!   - Traversing the file tree (`DirNode` / `FsEntry`)
 //!   - Walking the raw CST (`RawNode` / `Child`) including comments
 //!   - Using `SyntaxKind` to pattern-match on node/token types
+//!   - Reporting diagnostics (`PluginRuntime::warn`)
 //!
-//! It does not modify any files — it just prints an analysis report to stderr
-//! so you can verify it loaded and received the workspace context correctly.
+//! It does not modify any files — it prints an analysis report to stderr so
+//! you can verify it loaded and received the workspace context correctly, and
+//! reports TODO / FIXME / HACK comments as warnings.
 
 use forgen_api::syntax::raw::{Child, RawNode};
 use forgen_api::syntax::SyntaxKind;
@@ -31,11 +33,15 @@ impl Plugin for ExamplePlugin {
         "example-plugin"
     }
 
-    fn run(
-        &self,
-        ctx: &WorkspaceContext,
-        _runtime: &mut PluginRuntime<'_>,
-    ) -> Vec<FileReplacement> {
+    fn version(&self) -> Option<&str> {
+        Some(env!("CARGO_PKG_VERSION"))
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("Reports workspace facts and warns about TODO / FIXME / HACK comments")
+    }
+
+    fn run(&self, ctx: &WorkspaceContext, runtime: &mut PluginRuntime<'_>) -> Vec<FileReplacement> {
         let sep = "=".repeat(56);
         eprintln!("[example-plugin] {sep}");
         eprintln!("[example-plugin]  Workspace : {}", ctx.workspace_root);
@@ -94,6 +100,7 @@ impl Plugin for ExamplePlugin {
                     "[example-plugin]      TODO @ [{:5}..{:5}]  {snippet}",
                     range.start, range.end,
                 );
+                runtime.warn(&file.path, *range, format!("unresolved comment: {snippet}"));
             }
         }
 
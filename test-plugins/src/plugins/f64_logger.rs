@@ -1,4 +1,6 @@
-use forgen_api::{FileReplacement, Plugin, PluginRuntime, Replacement, WorkspaceContext};
+use forgen_api::{
+    FileContext, Interest, ItemKind, Plugin, PluginRuntime, Replacement, Template, WorkspaceContext,
+};
 
 /// Inserts a `println!` trace line after every `let` binding whose type is
 /// `f64`, whether the annotation is written explicitly (`let x: f64 = …`)
@@ -7,6 +9,8 @@ use forgen_api::{FileReplacement, Plugin, PluginRuntime, Replacement, WorkspaceC
 /// This plugin is a built-in example that ships with `cargo-forgen`. It also
 /// serves as a reference implementation for authors writing their own dylib
 /// plugins: notice that it only depends on `forgen-api` — no `ra_ap_*` crates.
+/// It only looks at one file at a time, so it implements `run_file` and lets
+/// the runtime process files in parallel.
 pub struct F64LoggerPlugin;
 
 impl Plugin for F64LoggerPlugin {
@@ -14,34 +18,52 @@ impl Plugin for F64LoggerPlugin {
         "f64-logger"
     }
 
-    fn run(&self, ctx: &WorkspaceContext, runtime: &mut PluginRuntime<'_>) -> Vec<FileReplacement> {
-        let mut results = Vec::new();
+    fn version(&self) -> Option<&str> {
+        Some(env!("CARGO_PKG_VERSION"))
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("Inserts a `println!` trace after every `f64` let binding")
+    }
 
-        for file in &ctx.files {
-            let mut replacements = Vec::new();
+    // Files without `let` bindings are never relevant. Inferred types can
+    // change when other files do, so the output is not cached per file.
+    fn interest(&self) -> Interest {
+        Interest::all_files().with_items([ItemKind::LetBindings])
+    }
 
-            for binding in file.bindings_of_type("f64") {
-                if already_logged(file, runtime.plugin_id(), &binding.name) {
-                    continue;
-                }
+    fn run_file(
+        &self,
+        file: &FileContext,
+        _ctx: &WorkspaceContext,
+        runtime: &mut PluginRuntime<'_>,
+    ) -> Vec<Replacement> {
+        let mut replacements = Vec::new();
 
-                replacements.push(Replacement::insert(
-                    // Insertion point: right after the closing `;` of the statement.
-                    binding.range.end,
-                    format!("println!(\"{name}: {{}}\", {name});", name = binding.name,),
-                ));
+        for binding in file.bindings_of_type("f64") {
+            if already_logged(file, runtime.plugin_id(), &binding.name) {
+                continue;
             }
 
-            if !replacements.is_empty() {
-                results.push(FileReplacement::new(file.path.clone(), replacements));
+            // Insertion point: right after the closing `;` of the statement.
+            match Template::stmt(r#"println!("$name: {}", $name);"#)
+                .ident("name", &binding.name)
+                .insert_at(binding.range.end)
+            {
+                Ok(replacement) => replacements.push(replacement),
+                Err(err) => runtime.warn(
+                    &file.path,
+                    binding.range,
+                    format!("skipping `{}`: {err}", binding.name),
+                ),
             }
         }
 
-        results
+        replacements
     }
 }
 
-fn already_logged(file: &forgen_api::FileContext, plugin_id: &str, binding_name: &str) -> bool {
+fn already_logged(file: &FileContext, plugin_id: &str, binding_name: &str) -> bool {
     file.generated_regions_for(plugin_id).any(|region| {
         let start = region.inner_range.start as usize;
         let end = region.inner_range.end as usize;
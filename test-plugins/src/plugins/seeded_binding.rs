@@ -1,5 +1,76 @@
 use forgen_api::rand::Rng;
-use forgen_api::{FileReplacement, Plugin, PluginRuntime, Replacement, WorkspaceContext};
+use forgen_api::{
+    FileReplacement, Plugin, PluginRuntime, Replacement, TextRange, WorkspaceContext,
+};
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// `[workspace.metadata.forgen.plugins.seeded-binding]`, optionally
+/// overridden per package by `[package.metadata.forgen.plugins.seeded-binding]`.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SeededBindingConfig {
+    /// Workspace-relative files to touch. Empty means every file.
+    files: Vec<String>,
+    /// Name of the `let` binding after which the seeded binding is inserted.
+    anchor: String,
+    /// Name of the generated binding.
+    binding: String,
+    /// Inclusive range the seeded value is drawn from.
+    min: u8,
+    max: u8,
+}
+
+/// [`SeededBindingConfig`] after [`SeededBindingConfig::validate`].
+struct ValidConfig {
+    files: Vec<String>,
+    anchor: String,
+    binding: syn::Ident,
+    min: u8,
+    max: u8,
+}
+
+impl SeededBindingConfig {
+    /// Check the settings, or say why they cannot be used.
+    fn validate(self) -> Result<ValidConfig, String> {
+        let invalid = |problem: String| {
+            format!("invalid configuration for plugin `seeded-binding`: {problem}")
+        };
+        if self.min > self.max {
+            return Err(invalid(format!(
+                "`min` ({}) is greater than `max` ({})",
+                self.min, self.max
+            )));
+        }
+        // `binding` ends up in generated code; `anchor` is looked up as a
+        // binding name, so anything else would silently match nothing.
+        let ident = |key: &str, value: &str| {
+            syn::parse_str::<syn::Ident>(value)
+                .map_err(|_| invalid(format!("key `{key}`: `{value}` is not a valid identifier")))
+        };
+        ident("anchor", &self.anchor)?;
+        let binding = ident("binding", &self.binding)?;
+        Ok(ValidConfig {
+            files: self.files,
+            anchor: self.anchor,
+            binding,
+            min: self.min,
+            max: self.max,
+        })
+    }
+}
+
+impl Default for SeededBindingConfig {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            anchor: "counter".to_owned(),
+            binding: "seeded_runtime_value".to_owned(),
+            min: 10,
+            max: 99,
+        }
+    }
+}
 
 pub struct SeededBindingPlugin;
 
@@ -8,11 +79,35 @@ impl Plugin for SeededBindingPlugin {
         "seeded-binding"
     }
 
+    fn version(&self) -> Option<&str> {
+        Some(env!("CARGO_PKG_VERSION"))
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("Inserts a `let` binding with a seeded random value")
+    }
+
     fn run(&self, ctx: &WorkspaceContext, runtime: &mut PluginRuntime<'_>) -> Vec<FileReplacement> {
         let mut results = Vec::new();
+        // Workspace settings apply to every file; report each problem once.
+        let mut reported = HashSet::new();
 
         for file in &ctx.files {
-            if file.path != "test/src/main.rs" {
+            let config = runtime
+                .config_for_file::<SeededBindingConfig>(&file.path)
+                .map_err(|err| err.to_string())
+                .and_then(SeededBindingConfig::validate);
+            let config = match config {
+                Ok(config) => config,
+                Err(message) => {
+                    if reported.insert(message.clone()) {
+                        runtime.error(&file.path, TextRange::new(0, 0), message);
+                    }
+                    continue;
+                }
+            };
+
+            if !config.files.is_empty() && !config.files.contains(&file.path) {
                 continue;
             }
 
@@ -24,23 +119,18 @@ impl Plugin for SeededBindingPlugin {
                 continue;
             }
 
-            let Some(anchor) = file.binding("counter") else {
+            let Some(anchor) = file.binding(&config.anchor) else {
                 continue;
             };
 
             let mut rng = runtime.rng_for_file(&file.path);
-            let sample: u8 = rng.gen_range(10..=99);
-
-            results.push(FileReplacement::new(
-                file.path.clone(),
-                vec![Replacement::insert(
-                    anchor.range.end,
-                    format!(
-                        "let seeded_runtime_value: f64 = {sample} as f64;",
-                        sample = sample,
-                    ),
-                )],
-            ));
+            let sample: u8 = rng.gen_range(config.min..=config.max);
+
+            let replacement = Replacement::insert(
+                anchor.range.end,
+                format!("let {}: f64 = {sample} as f64;", config.binding),
+            );
+            results.push(FileReplacement::new(file.path.clone(), vec![replacement]));
         }
 
         results